@@ -0,0 +1,722 @@
+use crate::{
+    constant::MAX_ARRAY_LENGTH,
+    error::{Error, Result},
+    value::{range_len, Array, Value},
+};
+use std::{cell::RefCell, fmt, rc::Rc};
+
+/// Calls function values back through whichever backend is running, for
+/// builtins such as `find` that take a function
+pub trait Caller<F> {
+    fn call(&mut self, function: &Value<F>, args: Vec<Value<F>>) -> Result<Value<F>>;
+
+    /// Whether a variable called `name` is visible to the running code, for
+    /// `defined`
+    fn is_defined(&self, name: &str) -> bool;
+}
+
+/// The parts of a backend's user function that builtins such as `arity`
+/// can inspect
+pub trait Signature {
+    fn name(&self) -> &str;
+    fn params(&self) -> &[String];
+    fn doc(&self) -> Option<&str>;
+}
+
+pub type BuiltinFn<F> = fn(&[Value<F>], &mut dyn Caller<F>) -> Result<Value<F>>;
+
+impl<F: fmt::Display + Signature + 'static> Value<F> {
+    /// Every builtin, installed as a global by both backends
+    pub const BUILTIN_FUNCTIONS: &'static [(&'static str, BuiltinFn<F>)] = &[
+        ("len", builtin_len as BuiltinFn<F>),
+        ("type", builtin_type as BuiltinFn<F>),
+        ("clock", builtin_clock as BuiltinFn<F>),
+        ("assert", builtin_assert as BuiltinFn<F>),
+        ("divmod", builtin_divmod as BuiltinFn<F>),
+        ("floor_div", builtin_floor_div as BuiltinFn<F>),
+        ("is_nil", builtin_is_nil as BuiltinFn<F>),
+        ("default", builtin_default as BuiltinFn<F>),
+        ("abs", builtin_abs as BuiltinFn<F>),
+        ("sign", builtin_sign as BuiltinFn<F>),
+        ("is_nan", builtin_is_nan as BuiltinFn<F>),
+        ("is_finite", builtin_is_finite as BuiltinFn<F>),
+        ("hex", builtin_hex as BuiltinFn<F>),
+        ("bin", builtin_bin as BuiltinFn<F>),
+        ("read_file", builtin_file_access_denied as BuiltinFn<F>),
+        ("write_file", builtin_file_access_denied as BuiltinFn<F>),
+        ("zip", builtin_zip as BuiltinFn<F>),
+        ("enumerate", builtin_enumerate as BuiltinFn<F>),
+        ("deep_copy", builtin_deep_copy as BuiltinFn<F>),
+        ("deepequal", builtin_deepequal as BuiltinFn<F>),
+        ("find", builtin_find as BuiltinFn<F>),
+        ("find_index", builtin_find_index as BuiltinFn<F>),
+        ("count", builtin_count as BuiltinFn<F>),
+        ("arity", builtin_arity as BuiltinFn<F>),
+        ("name", builtin_name as BuiltinFn<F>),
+        ("doc", builtin_doc as BuiltinFn<F>),
+        ("starts_with", builtin_starts_with as BuiltinFn<F>),
+        ("ends_with", builtin_ends_with as BuiltinFn<F>),
+        ("ord", builtin_ord as BuiltinFn<F>),
+        ("chr", builtin_chr as BuiltinFn<F>),
+        ("range", builtin_range as BuiltinFn<F>),
+        ("to_array", builtin_to_array as BuiltinFn<F>),
+        ("defined", builtin_defined as BuiltinFn<F>),
+    ];
+}
+
+/// `read_file` and `write_file`, or the stubs that refuse to touch the file
+/// system while file access is disabled
+pub fn file_functions<F>(enabled: bool) -> [(&'static str, BuiltinFn<F>); 2] {
+    if enabled {
+        [
+            ("read_file", builtin_read_file as BuiltinFn<F>),
+            ("write_file", builtin_write_file as BuiltinFn<F>),
+        ]
+    } else {
+        [
+            ("read_file", builtin_file_access_denied as BuiltinFn<F>),
+            ("write_file", builtin_file_access_denied as BuiltinFn<F>),
+        ]
+    }
+}
+
+/// Built-in function: len(value) -> number
+/// Returns the length of arrays, ranges and strings
+fn builtin_len<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    if args.len() != 1 {
+        return Err(Error::runtime(format!(
+            "len() takes exactly 1 argument ({} given)",
+            args.len()
+        )));
+    }
+
+    match &args[0] {
+        Value::Array(arr) => Ok(Value::Number(arr.borrow().len() as f64)),
+        Value::Range { start, end, step } => {
+            Ok(Value::Number(range_len(*start, *end, *step) as f64))
+        }
+        Value::String(s) => Ok(Value::Number(s.len() as f64)),
+        _ => Err(Error::runtime(format!(
+            "object of type '{}' has no len()",
+            args[0].type_name()
+        ))),
+    }
+}
+
+/// Built-in function: type(value) -> string
+/// Returns the type name of the value
+fn builtin_type<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    if args.len() != 1 {
+        return Err(Error::runtime(format!(
+            "type() takes exactly 1 argument ({} given)",
+            args.len()
+        )));
+    }
+
+    Ok(Value::String(args[0].type_name().to_string()))
+}
+
+/// Built-in function: clock() -> number
+/// Returns the current time in seconds since the UNIX epoch
+pub fn builtin_clock<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    if !args.is_empty() {
+        return Err(Error::runtime(format!(
+            "clock() takes no arguments ({} given)",
+            args.len()
+        )));
+    }
+    Ok(Value::Number(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64(),
+    ))
+}
+
+/// Built-in function: assert(condition, message?) -> nil
+/// Throws a runtime error if condition is falsy
+fn builtin_assert<F: fmt::Display>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(Error::runtime(format!(
+            "assert() takes 1 or 2 arguments ({} given)",
+            args.len()
+        )));
+    }
+
+    match args {
+        [condition, message] => {
+            if !condition.is_truthy() {
+                return Err(Error::assertion_with_message(message.to_string()));
+            }
+        }
+        [condition] => {
+            if !condition.is_truthy() {
+                return Err(Error::assertion());
+            }
+        }
+        _ => {
+            return Err(Error::runtime(format!(
+                "assert() takes 1 or 2 arguments ({} given)",
+                args.len()
+            )));
+        }
+    }
+
+    Ok(Value::Nil)
+}
+
+/// Built-in function: divmod(a, b) -> [quotient, remainder]
+/// Returns the floored quotient and the remainder with the sign of b
+fn builtin_divmod<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Number(_), Value::Number(b)] if *b == 0.0 => {
+            Err(Error::runtime("divmod() division by zero".to_string()))
+        }
+        [Value::Number(a), Value::Number(b)] => {
+            let quotient = (a / b).floor();
+            let remainder = a - b * quotient;
+            Ok(Value::Array(Rc::new(RefCell::new(vec![
+                Value::Number(quotient),
+                Value::Number(remainder),
+            ]))))
+        }
+        [a, b] => Err(Error::runtime(format!(
+            "divmod() expects two numbers, found '{}' and '{}'",
+            a.type_name(),
+            b.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "divmod() takes exactly 2 arguments ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: floor_div(a, b) -> number
+/// Returns a / b rounded down, so floor_div(-7, 2) is -4; a builtin rather
+/// than a `//` operator, since `//` starts a line comment
+fn builtin_floor_div<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Number(_), Value::Number(b)] if *b == 0.0 => {
+            Err(Error::runtime("floor_div() division by zero".to_string()))
+        }
+        [Value::Number(a), Value::Number(b)] => Ok(Value::Number((a / b).floor())),
+        [a, b] => Err(Error::runtime(format!(
+            "floor_div() expects two numbers, found '{}' and '{}'",
+            a.type_name(),
+            b.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "floor_div() takes exactly 2 arguments ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: is_nil(value) -> boolean
+/// Returns true only for nil
+fn builtin_is_nil<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [value] => Ok(Value::Boolean(matches!(value, Value::Nil))),
+        _ => Err(Error::runtime(format!(
+            "is_nil() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: default(value, fallback) -> value
+/// Returns fallback when value is nil, otherwise value
+fn builtin_default<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Nil, fallback] => Ok(fallback.clone()),
+        [value, _] => Ok(value.clone()),
+        _ => Err(Error::runtime(format!(
+            "default() takes exactly 2 arguments ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// The single number passed to the builtin `name`
+fn number_argument<F>(name: &str, args: &[Value<F>]) -> Result<f64> {
+    match args {
+        [Value::Number(n)] => Ok(*n),
+        [value] => Err(Error::runtime(format!(
+            "{name}() expects a number, found '{}'",
+            value.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "{name}() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: abs(n) -> number
+/// Returns the absolute value of n
+fn builtin_abs<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    Ok(Value::Number(number_argument("abs", args)?.abs()))
+}
+
+/// Built-in function: sign(n) -> number
+/// Returns -1, 0 or 1 by the sign of n, and nan for nan
+fn builtin_sign<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    let n = number_argument("sign", args)?;
+    let sign = if n == 0.0 || n.is_nan() {
+        n
+    } else {
+        n.signum()
+    };
+    Ok(Value::Number(sign))
+}
+
+/// Built-in function: is_nan(n) -> boolean
+/// Returns true if n is nan, such as the result of 0 / 0
+fn builtin_is_nan<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    Ok(Value::Boolean(number_argument("is_nan", args)?.is_nan()))
+}
+
+/// Built-in function: is_finite(n) -> boolean
+/// Returns false for inf, -inf and nan
+fn builtin_is_finite<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    Ok(Value::Boolean(
+        number_argument("is_finite", args)?.is_finite(),
+    ))
+}
+
+/// The single non-negative integer passed to the builtin `name`
+fn integer_argument<F>(name: &str, args: &[Value<F>]) -> Result<u64> {
+    let n = number_argument(name, args)?;
+    if n < 0.0 || n.fract() != 0.0 || n >= u64::MAX as f64 {
+        return Err(Error::runtime(format!(
+            "{name}() expects a non-negative integer, got {n}"
+        )));
+    }
+    Ok(n as u64)
+}
+
+/// Built-in function: hex(n) -> string
+/// Returns n in base 16, so hex(255) is "0xff"
+fn builtin_hex<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    Ok(Value::String(format!(
+        "{:#x}",
+        integer_argument("hex", args)?
+    )))
+}
+
+/// Built-in function: bin(n) -> string
+/// Returns n in base 2, so bin(5) is "0b101"
+fn builtin_bin<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    Ok(Value::String(format!(
+        "{:#b}",
+        integer_argument("bin", args)?
+    )))
+}
+
+/// Stand-in for the file builtins until the embedder enables file access
+fn builtin_file_access_denied<F>(_args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    Err(Error::runtime("file access is disabled".to_string()))
+}
+
+/// Built-in function: read_file(path) -> string
+/// Returns the contents of the file at path
+fn builtin_read_file<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::String(path)] => Ok(Value::String(std::fs::read_to_string(path)?)),
+        [path] => Err(Error::runtime(format!(
+            "read_file() expects a string path, found '{}'",
+            path.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "read_file() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: write_file(path, contents) -> nil
+/// Replaces the file at path with contents, creating it if needed
+fn builtin_write_file<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::String(path), Value::String(contents)] => {
+            std::fs::write(path, contents)?;
+            Ok(Value::Nil)
+        }
+        [path, contents] => Err(Error::runtime(format!(
+            "write_file() expects two strings, found '{}' and '{}'",
+            path.type_name(),
+            contents.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "write_file() takes exactly 2 arguments ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+fn pair<F>(first: Value<F>, second: Value<F>) -> Value<F> {
+    Value::Array(Rc::new(RefCell::new(vec![first, second])))
+}
+
+/// Built-in function: zip(a, b) -> array
+/// Returns [a[i], b[i]] pairs, stopping at the end of the shorter array
+fn builtin_zip<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Array(a), Value::Array(b)] => {
+            let pairs = a
+                .borrow()
+                .iter()
+                .zip(b.borrow().iter())
+                .map(|(x, y)| pair(x.clone(), y.clone()))
+                .collect();
+            Ok(Value::Array(Rc::new(RefCell::new(pairs))))
+        }
+        [a, b] => Err(Error::runtime(format!(
+            "zip() expects two arrays, found '{}' and '{}'",
+            a.type_name(),
+            b.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "zip() takes exactly 2 arguments ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: enumerate(arr) -> array
+/// Returns [index, element] pairs for each element of arr
+fn builtin_enumerate<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Array(arr)] => {
+            let pairs = arr
+                .borrow()
+                .iter()
+                .enumerate()
+                .map(|(i, value)| pair(Value::Number(i as f64), value.clone()))
+                .collect();
+            Ok(Value::Array(Rc::new(RefCell::new(pairs))))
+        }
+        [value] => Err(Error::runtime(format!(
+            "enumerate() expects an array, found '{}'",
+            value.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "enumerate() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: deep_copy(value) -> value
+/// Returns value with every nested array copied, so mutating the copy never
+/// affects the original
+fn builtin_deep_copy<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [value] => Ok(value.deep_copy()),
+        _ => Err(Error::runtime(format!(
+            "deep_copy() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: deepequal(a, b) -> boolean
+/// Like `a == b`, but numbers, including those nested in arrays, must be
+/// exactly equal, and `nan` equals nothing
+fn builtin_deepequal<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [a, b] => Ok(Value::Boolean(a.strict_eq(b))),
+        _ => Err(Error::runtime(format!(
+            "deepequal() takes exactly 2 arguments ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// The array and function arguments of `find` and `find_index`
+fn predicate_arguments<'a, F>(
+    name: &str,
+    args: &'a [Value<F>],
+) -> Result<(&'a Array<F>, &'a Value<F>)> {
+    match args {
+        [Value::Array(arr), pred @ (Value::Function(_) | Value::BuiltinFunction { .. })] => {
+            Ok((arr, pred))
+        }
+        [arr, pred] => Err(Error::runtime(format!(
+            "{name}() expects an array and a function, found '{}' and '{}'",
+            arr.type_name(),
+            pred.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "{name}() takes exactly 2 arguments ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Index and value of the first element of `arr` satisfying `pred`
+fn first_match<F>(
+    arr: &Array<F>,
+    pred: &Value<F>,
+    caller: &mut dyn Caller<F>,
+) -> Result<Option<(usize, Value<F>)>> {
+    // iterate over a snapshot, as pred may modify the array
+    let elements = arr.borrow().clone();
+    for (i, element) in elements.into_iter().enumerate() {
+        if caller.call(pred, vec![element.clone()])?.is_truthy() {
+            return Ok(Some((i, element)));
+        }
+    }
+    Ok(None)
+}
+
+/// Built-in function: find(arr, pred) -> value
+/// Returns the first element for which pred(element) is truthy, or nil
+fn builtin_find<F>(args: &[Value<F>], caller: &mut dyn Caller<F>) -> Result<Value<F>> {
+    let (arr, pred) = predicate_arguments("find", args)?;
+    let found = first_match(arr, pred, caller)?;
+    Ok(found.map_or(Value::Nil, |(_, element)| element))
+}
+
+/// Built-in function: find_index(arr, pred) -> number
+/// Returns the index of the first element for which pred(element) is truthy,
+/// or -1
+fn builtin_find_index<F>(args: &[Value<F>], caller: &mut dyn Caller<F>) -> Result<Value<F>> {
+    let (arr, pred) = predicate_arguments("find_index", args)?;
+    let found = first_match(arr, pred, caller)?;
+    Ok(Value::Number(found.map_or(-1.0, |(i, _)| i as f64)))
+}
+
+/// Built-in function: count(arr, value_or_pred) -> number
+/// Returns how many elements equal value, or, given a function, how many
+/// elements pred(element) is truthy for
+fn builtin_count<F>(args: &[Value<F>], caller: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Array(arr), pred @ (Value::Function(_) | Value::BuiltinFunction { .. })] => {
+            // iterate over a snapshot, as pred may modify the array
+            let elements = arr.borrow().clone();
+            let mut count = 0;
+            for element in elements {
+                if caller.call(pred, vec![element])?.is_truthy() {
+                    count += 1;
+                }
+            }
+            Ok(Value::Number(count as f64))
+        }
+        [Value::Array(arr), value] => {
+            let count = arr
+                .borrow()
+                .iter()
+                .filter(|element| *element == value)
+                .count();
+            Ok(Value::Number(count as f64))
+        }
+        [arr, _] => Err(Error::runtime(format!(
+            "count() expects an array, found '{}'",
+            arr.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "count() takes exactly 2 arguments ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: arity(f) -> number
+/// Returns how many parameters a user function takes, or nil for a builtin,
+/// whose arguments are checked when it runs
+fn builtin_arity<F: Signature>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Function(function)] => Ok(Value::Number(function.params().len() as f64)),
+        [Value::BuiltinFunction { .. }] => Ok(Value::Nil),
+        [value] => Err(Error::runtime(format!(
+            "arity() expects a function, found '{}'",
+            value.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "arity() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: name(f) -> string
+/// Returns the name a function was declared or registered with
+fn builtin_name<F: Signature>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Function(function)] => Ok(Value::String(function.name().to_string())),
+        [Value::BuiltinFunction { name, .. }] => Ok(Value::String(name.clone())),
+        [value] => Err(Error::runtime(format!(
+            "name() expects a function, found '{}'",
+            value.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "name() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: doc(f) -> string
+/// Returns the `///` comment written right before a user function, or nil
+fn builtin_doc<F: Signature>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Function(function)] => Ok(function
+            .doc()
+            .map_or(Value::Nil, |doc| Value::String(doc.to_string()))),
+        [Value::BuiltinFunction { .. }] => Ok(Value::Nil),
+        [value] => Err(Error::runtime(format!(
+            "doc() expects a function, found '{}'",
+            value.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "doc() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// The two string arguments of `starts_with` and `ends_with`
+fn string_arguments<'a, F>(name: &str, args: &'a [Value<F>]) -> Result<(&'a str, &'a str)> {
+    match args {
+        [Value::String(s), Value::String(affix)] => Ok((s, affix)),
+        [a, b] => Err(Error::runtime(format!(
+            "{name}() expects two strings, found '{}' and '{}'",
+            a.type_name(),
+            b.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "{name}() takes exactly 2 arguments ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: starts_with(s, prefix) -> boolean
+/// Returns whether s begins with prefix
+fn builtin_starts_with<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    let (s, prefix) = string_arguments("starts_with", args)?;
+    Ok(Value::Boolean(s.starts_with(prefix)))
+}
+
+/// Built-in function: ends_with(s, suffix) -> boolean
+/// Returns whether s finishes with suffix
+fn builtin_ends_with<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    let (s, suffix) = string_arguments("ends_with", args)?;
+    Ok(Value::Boolean(s.ends_with(suffix)))
+}
+
+/// Built-in function: ord(c) -> number
+/// Returns the Unicode code point of the one-character string c, so
+/// ord("A") is 65
+fn builtin_ord<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::String(s)] => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Value::Number(u32::from(c) as f64)),
+                _ => Err(Error::runtime(format!(
+                    "ord() expects a single character, got a string of length {}",
+                    s.chars().count()
+                ))),
+            }
+        }
+        [value] => Err(Error::runtime(format!(
+            "ord() expects a string, found '{}'",
+            value.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "ord() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: chr(n) -> string
+/// Returns the character with Unicode code point n, so chr(65) is "A"
+fn builtin_chr<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    let n = integer_argument("chr", args)?;
+    u32::try_from(n)
+        .ok()
+        .and_then(char::from_u32)
+        .map(|c| Value::String(c.to_string()))
+        .ok_or_else(|| Error::runtime(format!("chr() expects a Unicode code point, got {n}")))
+}
+
+/// Built-in function: range(end) | range(start, end, step?) -> range
+/// Counts from `start`, 0 by default, up to but not including `end` in steps
+/// of `step`, 1 by default, without storing the numbers
+fn builtin_range<F: fmt::Display>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(Error::runtime(format!(
+            "range() takes 1 to 3 arguments ({} given)",
+            args.len()
+        )));
+    }
+    let numbers = args
+        .iter()
+        .map(|arg| match arg {
+            Value::Number(n) if n.fract() == 0.0 => Ok(*n),
+            arg => Err(Error::runtime(format!(
+                "range() arguments must be integers, got {arg}"
+            ))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (start, end, step) = match numbers[..] {
+        [end] => (0.0, end, 1.0),
+        [start, end] => (start, end, 1.0),
+        [start, end, step] => (start, end, step),
+        _ => unreachable!(),
+    };
+    if step == 0.0 {
+        return Err(Error::runtime("range() step must not be zero".to_string()));
+    }
+    Ok(Value::Range { start, end, step })
+}
+
+/// Built-in function: to_array(range) -> array
+/// Returns the numbers of a range in a new array, or a copy of an array
+fn builtin_to_array<F>(args: &[Value<F>], _: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::Range { start, end, step }] => {
+            let length = range_len(*start, *end, *step);
+            if length > MAX_ARRAY_LENGTH {
+                return Err(Error::runtime(format!(
+                    "to_array() would make an array of {length} elements (at most {MAX_ARRAY_LENGTH})"
+                )));
+            }
+            Ok(Value::from(
+                (0..length)
+                    .map(|i| Value::Number(start + i as f64 * step))
+                    .collect::<Vec<_>>(),
+            ))
+        }
+        [Value::Array(arr)] => Ok(Value::from(arr.borrow().clone())),
+        [value] => Err(Error::runtime(format!(
+            "to_array() expects a range or an array, found '{}'",
+            value.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "to_array() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
+
+/// Built-in function: defined(name) -> boolean
+/// Returns whether a variable called `name` is in scope; the VM keeps no
+/// names for locals, so its chunk records those in scope at each call
+fn builtin_defined<F>(args: &[Value<F>], caller: &mut dyn Caller<F>) -> Result<Value<F>> {
+    match args {
+        [Value::String(name)] => Ok(Value::Boolean(caller.is_defined(name))),
+        [value] => Err(Error::runtime(format!(
+            "defined() expects a string, found '{}'",
+            value.type_name()
+        ))),
+        _ => Err(Error::runtime(format!(
+            "defined() takes exactly 1 argument ({} given)",
+            args.len()
+        ))),
+    }
+}
@@ -1,34 +1,56 @@
 use super::{
-    buildin::BUILTIN_FUNCTIONS,
     opcode::OpCode,
     value::{Proto, Value},
 };
 use crate::{
-    constant::{CONSTANTS_SIZE, GLOBALS_SIZE},
+    constant::{CONSTANTS_SIZE, GLOBALS_SIZE, PROTOS_SIZE},
+    error::{Error, Result, Warning},
     location::Location,
 };
-use std::fmt;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Write},
+};
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
     code: Vec<u8>,
     constants: Vec<Value>,
+    /// Function prototypes, referenced by index from `Closure`
+    protos: Vec<Proto>,
     globals: Vec<String>,
     locations: Vec<Location>,
+    /// Variable names of the values ops act on, by the op's ip, for error
+    /// messages: the callee of a `Call`, the array of an `Index` or `IndexSet`
+    operand_names: HashMap<usize, String>,
+    /// Names of the locals in scope at each `Call`, by the op's ip, so that
+    /// `defined` can see locals although the VM keeps only their slots
+    local_names: HashMap<usize, Vec<String>>,
+    warnings: Vec<Warning>,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chunk {
     pub fn new() -> Self {
         let mut globals = Vec::with_capacity(GLOBALS_SIZE);
-        for (name, _) in BUILTIN_FUNCTIONS {
+        for (name, _) in Value::BUILTIN_FUNCTIONS {
             globals.push(name.to_string());
         }
 
         Self {
             code: Vec::with_capacity(CONSTANTS_SIZE),
             constants: Vec::with_capacity(CONSTANTS_SIZE),
+            protos: Vec::new(),
             globals,
             locations: Vec::new(),
+            operand_names: HashMap::new(),
+            local_names: HashMap::new(),
+            warnings: Vec::new(),
         }
     }
 
@@ -40,16 +62,187 @@ impl Chunk {
         self.constants.get(index)
     }
 
+    pub fn proto(&self, index: usize) -> Option<&Proto> {
+        self.protos.get(index)
+    }
+
     pub fn global(&self, index: usize) -> Option<&String> {
         self.globals.get(index)
     }
 
+    pub fn operand_name(&self, ip: usize) -> Option<&str> {
+        self.operand_names.get(&ip).map(String::as_str)
+    }
+
+    pub fn local_names(&self, ip: usize) -> &[String] {
+        self.local_names.get(&ip).map_or(&[], Vec::as_slice)
+    }
+
+    /// Diagnostics the compiler reported without failing
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
     pub fn current_ip(&self) -> usize {
         self.code.len()
     }
 
+    /// Where the code at `ip` came from, or the start of the script for an
+    /// ip past the end
     pub fn location_at(&self, ip: usize) -> Location {
-        self.locations.get(ip).expect("location not found").clone()
+        self.locations
+            .get(ip)
+            .copied()
+            .unwrap_or_else(Location::new)
+    }
+
+    /// Decode the instruction at `ip`, returning its opcode and the ip of the next instruction
+    pub fn decode_at(&self, ip: usize) -> Option<(OpCode, usize)> {
+        let op = OpCode::try_from(self.code(ip)?).ok()?;
+        if op == OpCode::Closure {
+            // the upvalue count operand must be present to size the instruction
+            self.code(ip + 2)?;
+        }
+        let next = ip + self.instruction_len(op, ip);
+        (next <= self.code.len()).then_some((op, next))
+    }
+
+    /// Check that the code is well-formed before running it: every opcode is
+    /// known, every instruction fits in the code with its operands, the
+    /// constants, globals and protos named exist, and every jump and
+    /// function lands on the start of an instruction
+    pub fn validate(&self) -> Result<()> {
+        // one past the end counts as a start, as a jump may leave the code
+        let mut starts = vec![false; self.code.len() + 1];
+        let mut jumps = Vec::new();
+        let mut ip = 0;
+        while ip < self.code.len() {
+            let op = OpCode::try_from(self.code[ip]).map_err(|_| {
+                Error::invalid_bytecode(ip, format!("unknown opcode {}", self.code[ip]))
+            })?;
+            let next = ip + self.instruction_len(op, ip);
+            if next > self.code.len() {
+                return Err(Error::invalid_bytecode(
+                    ip,
+                    format!("{op} runs past the end of the code"),
+                ));
+            }
+
+            let operand = self.code.get(ip + 1).copied().unwrap_or(0) as usize;
+            let jump = || u16::from_be_bytes([self.code[ip + 1], self.code[ip + 2]]) as usize;
+            match op {
+                OpCode::Constant if operand >= self.constants.len() => {
+                    return Err(Error::invalid_bytecode(
+                        ip,
+                        format!("constant {operand} does not exist"),
+                    ));
+                }
+                OpCode::DefineGlobal | OpCode::GetGlobal | OpCode::SetGlobal
+                    if operand >= self.globals.len() =>
+                {
+                    return Err(Error::invalid_bytecode(
+                        ip,
+                        format!("global {operand} does not exist"),
+                    ));
+                }
+                OpCode::Closure if operand >= self.protos.len() => {
+                    return Err(Error::invalid_bytecode(
+                        ip,
+                        format!("function {operand} does not exist"),
+                    ));
+                }
+                OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfTrue | OpCode::ForIter => {
+                    jumps.push((ip, next + jump()));
+                }
+                OpCode::Loop => match next.checked_sub(jump()) {
+                    Some(target) => jumps.push((ip, target)),
+                    None => {
+                        return Err(Error::invalid_bytecode(
+                            ip,
+                            "loop goes back past the start of the code".to_string(),
+                        ))
+                    }
+                },
+                _ => {}
+            }
+            starts[ip] = true;
+            ip = next;
+        }
+        starts[self.code.len()] = true;
+
+        let is_start = |ip: usize| starts.get(ip).copied().unwrap_or(false);
+        for (ip, target) in jumps {
+            if !is_start(target) {
+                return Err(Error::invalid_bytecode(
+                    ip,
+                    format!("jump to {target} is not the start of an instruction"),
+                ));
+            }
+        }
+        for proto in &self.protos {
+            if !is_start(proto.start_ip) {
+                return Err(Error::invalid_bytecode(
+                    proto.start_ip,
+                    format!("function '{}' does not start an instruction", proto.name),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Length in bytes of the instruction at `ip`, including its operands
+    fn instruction_len(&self, op: OpCode, ip: usize) -> usize {
+        let operands = match op {
+            OpCode::Constant
+            | OpCode::DefineGlobal
+            | OpCode::GetGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::Call
+            | OpCode::Print
+            | OpCode::EPrint
+            | OpCode::PrintWith
+            | OpCode::EPrintWith
+            | OpCode::Array
+            | OpCode::Unpack
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue => 1,
+            OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::JumpIfTrue
+            | OpCode::Loop
+            | OpCode::ForIter => 2,
+            // proto index, upvalue count, then an (is_local, index) pair per upvalue
+            OpCode::Closure => 2 + 2 * self.code(ip + 2).unwrap_or(0) as usize,
+            OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Negate
+            | OpCode::Equal
+            | OpCode::NotEqual
+            | OpCode::LessThan
+            | OpCode::LessEqual
+            | OpCode::GreaterThan
+            | OpCode::GreaterEqual
+            | OpCode::Not
+            | OpCode::Return
+            | OpCode::Pop
+            | OpCode::Dup
+            | OpCode::Swap
+            | OpCode::Over
+            | OpCode::CheckCount
+            | OpCode::Nop
+            | OpCode::Index
+            | OpCode::IndexSet
+            | OpCode::Slice
+            | OpCode::SliceSet => 0,
+        };
+        1 + operands
     }
 }
 
@@ -66,223 +259,391 @@ impl Chunk {
         self.locations.push(location);
     }
 
-    pub fn add_constant(&mut self, value: Value) -> u8 {
-        if let Some(index) = self.constants.iter().position(|v| v == &value) {
-            return index as u8;
+    pub fn add_constant(&mut self, value: Value) -> Result<u8> {
+        // numbers must match to the bit, or one literal would stand in for a
+        // slightly different one, or `0` for `-0`
+        let same = |constant: &Value| match (constant, &value) {
+            (Value::Number(a), Value::Number(b)) => a.to_bits() == b.to_bits(),
+            (constant, value) => constant == value,
+        };
+        if let Some(index) = self.constants.iter().position(same) {
+            return Ok(index as u8);
         }
 
+        if self.constants.len() == CONSTANTS_SIZE {
+            return Err(Error::too_many("constants in one script", CONSTANTS_SIZE));
+        }
         self.constants.push(value);
-        self.constants.len() as u8 - 1
+        Ok((self.constants.len() - 1) as u8)
+    }
+
+    pub fn add_proto(&mut self, proto: Proto) -> Result<u8> {
+        if self.protos.len() == PROTOS_SIZE {
+            return Err(Error::too_many("functions in one script", PROTOS_SIZE));
+        }
+        self.protos.push(proto);
+        Ok((self.protos.len() - 1) as u8)
     }
 
-    pub fn add_global(&mut self, name: String) -> u8 {
+    /// Remember that the op about to be written at the current ip acts on
+    /// the variable `name`
+    pub fn add_operand_name(&mut self, name: String) {
+        self.operand_names.insert(self.current_ip(), name);
+    }
+
+    /// Remember the names of the locals in scope at the `Call` about to be
+    /// written at the current ip
+    pub fn add_local_names(&mut self, names: Vec<String>) {
+        if !names.is_empty() {
+            self.local_names.insert(self.current_ip(), names);
+        }
+    }
+
+    pub fn add_warning(&mut self, warning: Warning) {
+        self.warnings.push(warning);
+    }
+
+    pub fn add_global(&mut self, name: String) -> Result<u8> {
         if let Some(index) = self.resolve_global(&name) {
-            return index;
+            return Ok(index);
         }
 
+        if self.globals.len() == GLOBALS_SIZE {
+            return Err(Error::too_many("global variables", GLOBALS_SIZE));
+        }
         self.globals.push(name);
-        self.globals.len() as u8 - 1
+        Ok((self.globals.len() - 1) as u8)
     }
 
     pub fn resolve_global(&self, name: &str) -> Option<u8> {
         self.globals.iter().position(|s| s == name).map(|i| i as u8)
     }
 
-    pub fn patch_jump(&mut self, offset: usize) {
+    pub fn patch_jump(&mut self, offset: usize) -> Result<()> {
         let jump = self.current_ip() - offset - 2; // 2 is the length of the jump instruction
-        self.code[offset] = (jump >> 8) as u8;
-        self.code[offset + 1] = jump as u8;
+        self.write_jump_operand(offset, jump)
     }
 
     /// Patch a jump instruction to jump to the target
-    pub fn patch_jump_with_target(&mut self, offset: usize, target: usize) {
+    pub fn patch_jump_with_target(&mut self, offset: usize, target: usize) -> Result<()> {
         let (op, jump) = if offset < target {
             (OpCode::Jump, target - offset - 2)
         } else {
             (OpCode::Loop, offset - target + 2)
         };
         self.code[offset - 1] = op as u8;
-        self.code[offset] = (jump >> 8) as u8;
-        self.code[offset + 1] = jump as u8;
+        self.write_jump_operand(offset, jump)
+    }
+
+    /// Store `jump` in the two bytes at `offset`, unless it is too far to fit
+    fn write_jump_operand(&mut self, offset: usize, jump: usize) -> Result<()> {
+        let jump = u16::try_from(jump).map_err(|_| Error::jump_too_large())?;
+        self.code[offset..offset + 2].copy_from_slice(&jump.to_be_bytes());
+        Ok(())
     }
 
+    /// Return nil from a function body that can run off its end
     pub fn end_with_return(&mut self) {
-        if let Some(op) = self.code.last() {
-            if *op == OpCode::Return as u8 {
-                return;
-            }
-        }
         self.write(OpCode::Nil as u8);
         self.write(OpCode::Return as u8);
     }
 }
 
+/// Optimizations
+impl Chunk {
+    /// Tidy the code from `start` on, which no run has reached yet: a
+    /// constant pushed only to be popped again, as by `1;`, is blanked out
+    /// with `Nop`s, which are then squeezed out of the code
+    pub fn peephole(&mut self, start: usize) {
+        let instructions = self.instructions_from(start);
+        let targets = instructions
+            .iter()
+            .filter_map(|&(ip, op, next)| self.jump_target(ip, op, next))
+            .collect::<HashSet<_>>();
+        for pair in instructions.windows(2) {
+            let [(ip, OpCode::Constant, _), (pop, OpCode::Pop, next)] = *pair else {
+                continue;
+            };
+            // a jump may land on the `Pop` with some other value to drop
+            if !targets.contains(&pop) {
+                self.code[ip..next].fill(OpCode::Nop as u8);
+            }
+        }
+        self.compact(start);
+    }
+
+    /// Drop the `Nop`s from `start` on, moving the code after each one back
+    /// and pointing jumps, functions, locations and operand names at the new
+    /// places
+    fn compact(&mut self, start: usize) {
+        let instructions = self.instructions_from(start);
+        if !instructions.iter().any(|&(_, op, _)| op == OpCode::Nop) {
+            return;
+        }
+
+        // where each ip from `start` to one past the end moves to
+        let mut moved = Vec::with_capacity(self.code.len() - start + 1);
+        let mut removed = 0;
+        for &(ip, op, next) in &instructions {
+            moved.extend((ip..next).map(|ip| ip - removed));
+            if op == OpCode::Nop {
+                removed += 1;
+            }
+        }
+        moved.push(self.code.len() - removed);
+        let new_ip = |ip: usize| ip.checked_sub(start).map_or(ip, |i| moved[i]);
+
+        let mut code = self.code[..start].to_vec();
+        let mut locations = self.locations[..start].to_vec();
+        for &(ip, op, next) in &instructions {
+            if op == OpCode::Nop {
+                continue;
+            }
+            code.extend_from_slice(&self.code[ip..next]);
+            locations.extend_from_slice(&self.locations[ip..next]);
+            if let Some(target) = self.jump_target(ip, op, next) {
+                let (from, to) = (new_ip(next), new_ip(target));
+                let jump = if op == OpCode::Loop {
+                    from - to
+                } else {
+                    to - from
+                };
+                // squeezing code out only ever shortens a jump
+                code[new_ip(ip) + 1..new_ip(next)].copy_from_slice(&(jump as u16).to_be_bytes());
+            }
+        }
+        self.code = code;
+        self.locations = locations;
+
+        for proto in &mut self.protos {
+            proto.start_ip = new_ip(proto.start_ip);
+        }
+        self.operand_names = std::mem::take(&mut self.operand_names)
+            .into_iter()
+            .map(|(ip, name)| (new_ip(ip), name))
+            .collect();
+        self.local_names = std::mem::take(&mut self.local_names)
+            .into_iter()
+            .map(|(ip, names)| (new_ip(ip), names))
+            .collect();
+    }
+
+    /// The opcode and end of each instruction from `start` on, by its ip
+    fn instructions_from(&self, start: usize) -> Vec<(usize, OpCode, usize)> {
+        let mut instructions = Vec::new();
+        let mut ip = start;
+        while let Some((op, next)) = self.decode_at(ip) {
+            instructions.push((ip, op, next));
+            ip = next;
+        }
+        instructions
+    }
+
+    /// Where the jump at `ip`, ending at `next`, goes, if `op` is a jump
+    fn jump_target(&self, ip: usize, op: OpCode, next: usize) -> Option<usize> {
+        let jump = || u16::from_be_bytes([self.code[ip + 1], self.code[ip + 2]]) as usize;
+        match op {
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfTrue | OpCode::ForIter => {
+                Some(next + jump())
+            }
+            OpCode::Loop => next.checked_sub(jump()),
+            _ => None,
+        }
+    }
+}
+
 /// Debug utilities for the Chunk
 impl Chunk {
     pub fn disassemble(&self, name: &str) {
-        self.disassemble_recursive(name, 0);
+        print!("{}", self.disassembly(name));
     }
 
-    fn disassemble_recursive(&self, name: &str, depth: usize) {
+    /// The listing printed by `disassemble`
+    pub fn disassembly(&self, name: &str) -> String {
+        self.disassembly_from(name, 0)
+    }
+
+    /// Like `disassembly`, but listing only the code from `start` on, such
+    /// as the code a REPL line added to the chunk
+    pub fn disassembly_from(&self, name: &str, start: usize) -> String {
+        let mut out = String::new();
+        self.disassemble_recursive(&mut out, name, start, 0)
+            .expect("writing to a String cannot fail");
+        out
+    }
+
+    fn disassemble_recursive(
+        &self,
+        out: &mut String,
+        name: &str,
+        start: usize,
+        depth: usize,
+    ) -> fmt::Result {
         let indent = "  ".repeat(depth);
-        println!("{indent}== {name} ==");
+        writeln!(out, "{indent}== {name} ==")?;
 
         if !self.constants.is_empty() {
-            println!("{indent}=== Constants ===");
+            writeln!(out, "{indent}=== Constants ===")?;
             for (i, constant) in self.constants.iter().enumerate() {
-                match constant {
-                    Value::Proto(Proto {
-                        name,
-                        params,
-                        start_ip,
-                        ..
-                    }) => {
-                        println!(
-                            "{indent}constants[{i}] = function {name}({params_str}) at @{start_ip}",
-                            params_str = params.join(", ")
-                        );
-                    }
-                    _ => {
-                        println!("{indent}constants[{i}] = {constant:?}");
-                    }
-                }
+                writeln!(out, "{indent}constants[{i}] = {constant:?}")?;
+            }
+        }
+
+        if !self.protos.is_empty() {
+            writeln!(out, "{indent}=== Protos ===")?;
+            for (i, proto) in self.protos.iter().enumerate() {
+                writeln!(
+                    out,
+                    "{indent}protos[{i}] = function {}({}) at @{}",
+                    proto.name,
+                    proto.params.join(", "),
+                    proto.start_ip
+                )?;
             }
         }
 
         if !self.globals.is_empty() {
-            println!("{indent}=== Globals ===");
+            writeln!(out, "{indent}=== Globals ===")?;
             for (i, global) in self.globals.iter().enumerate() {
-                println!("{indent}globals[{i}] = {global:?}");
+                writeln!(out, "{indent}globals[{i}] = {global:?}")?;
             }
         }
 
-        println!("{indent}=== Code ===");
-        let mut offset = 0;
+        writeln!(out, "{indent}=== Code ===")?;
+        let mut offset = start;
         while offset < self.code.len() {
-            offset = self.disassemble_instruction_with_indent(offset, depth);
+            offset = self.disassemble_instruction_with_indent(out, offset, depth)?;
         }
+        Ok(())
     }
 
-    fn disassemble_instruction_with_indent(&self, offset: usize, depth: usize) -> usize {
+    fn disassemble_instruction_with_indent(
+        &self,
+        out: &mut String,
+        offset: usize,
+        depth: usize,
+    ) -> std::result::Result<usize, fmt::Error> {
         let indent = "  ".repeat(depth);
         let instruction = self.code[offset];
-        let op = OpCode::try_from(instruction).unwrap_or_else(|_| {
-            println!("{indent}{offset:4} UNKNOWN_OP {instruction}");
-            return OpCode::Nil; // fallback
-        });
+        let Ok(op) = OpCode::try_from(instruction) else {
+            writeln!(out, "{indent}{offset:4} UNKNOWN_OP {instruction}")?;
+            return Ok(offset + 1);
+        };
 
+        let next = offset + self.instruction_len(op, offset);
         match op {
             OpCode::Constant => {
                 let operand = self.code[offset + 1];
-                print!("{indent}{offset:4} {:15}", op);
+                write!(out, "{indent}{offset:4} {:15}", op)?;
                 if let Some(constant) = self.constants.get(operand as usize) {
-                    match constant {
-                        Value::Proto(Proto { name, params, .. }) => {
-                            println!(" {} ; function {}({})", operand, name, params.join(", "));
-                        }
-                        _ => {
-                            println!(" {} ; {:?}", operand, constant);
-                        }
-                    }
+                    writeln!(out, " {} ; {:?}", operand, constant)?;
                 } else {
-                    println!(" {} ; INVALID_CONSTANT", operand);
+                    writeln!(out, " {} ; INVALID_CONSTANT", operand)?;
                 }
-                offset + 2
             }
             OpCode::DefineGlobal | OpCode::GetGlobal | OpCode::SetGlobal => {
                 let operand = self.code[offset + 1];
-                print!("{indent}{offset:4} {:15}", op);
+                write!(out, "{indent}{offset:4} {:15}", op)?;
                 if let Some(global) = self.globals.get(operand as usize) {
-                    println!(" {} ; {:?}", operand, global);
+                    writeln!(out, " {} ; {:?}", operand, global)?;
                 } else {
-                    println!(" {} ; INVALID_GLOBAL", operand);
+                    writeln!(out, " {} ; INVALID_GLOBAL", operand)?;
                 }
-                offset + 2
             }
             OpCode::GetLocal | OpCode::SetLocal => {
                 let operand = self.code[offset + 1];
-                println!("{indent}{offset:4} {op:15} {operand} ; local[{operand}]");
-                offset + 2
+                writeln!(
+                    out,
+                    "{indent}{offset:4} {op:15} {operand} ; local[{operand}]"
+                )?;
             }
-            OpCode::JumpIfFalse | OpCode::Jump | OpCode::JumpIfTrue => {
+            OpCode::JumpIfFalse | OpCode::Jump | OpCode::JumpIfTrue | OpCode::ForIter => {
                 let high = self.code[offset + 1] as u16;
                 let low = self.code[offset + 2] as u16;
                 let jump_offset = (high << 8) | low;
-                println!(
+                writeln!(
+                    out,
                     "{indent}{offset:4} {op:15} ; -> {}",
                     offset + 3 + jump_offset as usize
-                );
-                offset + 3
+                )?;
             }
             OpCode::Loop => {
                 let high = self.code[offset + 1] as u16;
                 let low = self.code[offset + 2] as u16;
                 let jump_offset = (high << 8) | low;
-                println!(
+                writeln!(
+                    out,
                     "{indent}{offset:4} {op:15} ; -> {}",
                     offset + 3 - jump_offset as usize
-                );
-                offset + 3
+                )?;
             }
             OpCode::Call => {
                 let arg_count = self.code[offset + 1] as usize;
-                println!("{indent}{offset:4} {op:15} {arg_count} ; call");
-                offset + 2
+                writeln!(out, "{indent}{offset:4} {op:15} {arg_count} ; call")?;
             }
             OpCode::Array => {
                 let element_count = self.code[offset + 1] as usize;
-                println!("{indent}{offset:4} {op:15} {element_count} ; create array with {element_count} elements");
-                offset + 2
+                writeln!(out, "{indent}{offset:4} {op:15} {element_count} ; create array with {element_count} elements")?;
+            }
+            OpCode::Unpack => {
+                let count = self.code[offset + 1] as usize;
+                writeln!(
+                    out,
+                    "{indent}{offset:4} {op:15} {count} ; unpack array into {count} values"
+                )?;
             }
             OpCode::Index => {
-                println!("{indent}{offset:4} {op:15} ; array[index]");
-                offset + 1
+                writeln!(out, "{indent}{offset:4} {op:15} ; array[index]")?;
             }
             OpCode::IndexSet => {
-                println!("{indent}{offset:4} {op:15} ; array[index] = value");
-                offset + 1
+                writeln!(out, "{indent}{offset:4} {op:15} ; array[index] = value")?;
+            }
+            OpCode::Slice => {
+                writeln!(out, "{indent}{offset:4} {op:15} ; array[start:end]")?;
+            }
+            OpCode::SliceSet => {
+                writeln!(out, "{indent}{offset:4} {op:15} ; array[start:end] = value")?;
             }
             OpCode::Closure => {
                 let proto_index = self.code[offset + 1];
                 let upvalue_count = self.code[offset + 2];
-                print!("{indent}{offset:4} {op:15} {proto_index} ; ");
-
-                if let Some(constant) = self.constants.get(proto_index as usize) {
-                    if let Value::Proto(proto) = constant {
-                        println!(
-                            "closure for function '{}' with {} upvalues",
-                            proto.name, upvalue_count
-                        );
-
-                        // Print upvalue details
-                        let mut current_offset = offset + 3;
-                        for i in 0..upvalue_count {
-                            let is_local = self.code[current_offset];
-                            let index = self.code[current_offset + 1];
-                            println!(
-                                "{indent}     upvalue[{}]: {} index {}",
-                                i,
-                                if is_local == 1 { "local" } else { "upvalue" },
-                                index
-                            );
-                            current_offset += 2;
-                        }
-
-                        return current_offset;
+                write!(out, "{indent}{offset:4} {op:15} {proto_index} ; ")?;
+
+                if let Some(proto) = self.protos.get(proto_index as usize) {
+                    writeln!(
+                        out,
+                        "closure for function '{}' with {} upvalues",
+                        proto.name, upvalue_count
+                    )?;
+
+                    // Print upvalue details
+                    let mut current_offset = offset + 3;
+                    for i in 0..upvalue_count {
+                        let is_local = self.code[current_offset];
+                        let index = self.code[current_offset + 1];
+                        writeln!(
+                            out,
+                            "{indent}     upvalue[{}]: {} index {}",
+                            i,
+                            if is_local == 1 { "local" } else { "upvalue" },
+                            index
+                        )?;
+                        current_offset += 2;
                     }
+                } else {
+                    writeln!(out, "INVALID_PROTO")?;
                 }
-                println!("INVALID_PROTO");
-                offset + 3 + (upvalue_count as usize * 2)
             }
             OpCode::GetUpvalue | OpCode::SetUpvalue => {
                 let upvalue_index = self.code[offset + 1];
-                println!("{indent}{offset:4} {op:15} {upvalue_index} ; upvalue[{upvalue_index}]");
-                offset + 2
+                writeln!(
+                    out,
+                    "{indent}{offset:4} {op:15} {upvalue_index} ; upvalue[{upvalue_index}]"
+                )?;
             }
-            OpCode::Print => {
+            OpCode::Print | OpCode::EPrint | OpCode::PrintWith | OpCode::EPrintWith => {
                 let count = self.code[offset + 1] as usize;
-                println!("{indent}{offset:4} {op:15} {count} ; print");
-                offset + 2
+                writeln!(out, "{indent}{offset:4} {op:15} {count} ; print")?;
             }
             OpCode::Add
             | OpCode::Subtract
@@ -294,14 +655,13 @@ impl Chunk {
             | OpCode::LessEqual
             | OpCode::GreaterThan
             | OpCode::GreaterEqual => {
-                println!("{indent}{offset:4} {op:15} ; binary operation");
-                offset + 1
+                writeln!(out, "{indent}{offset:4} {op:15} ; binary operation")?;
             }
             _ => {
-                println!("{indent}{offset:4} {:?}", op);
-                offset + 1
+                writeln!(out, "{indent}{offset:4} {:?}", op)?;
             }
         }
+        Ok(next)
     }
 }
 
@@ -309,8 +669,8 @@ impl fmt::Display for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Chunk {{ code: {:?}, constants: {:?}, globals: {:?} }}",
-            self.code, self.constants, self.globals
+            "Chunk {{ code: {:?}, constants: {:?}, protos: {:?}, globals: {:?} }}",
+            self.code, self.constants, self.protos, self.globals
         )
     }
 }
@@ -5,15 +5,28 @@ use super::{
     value::{Proto, Value},
 };
 use crate::{
-    error::{Error, Result},
+    constant::OPERANDS_SIZE,
+    error::{Error, Result, Warning},
     location::Location,
-    parser::{expr, stmt, BinaryOp, Expr, LocatedStmt, Stmt, UnaryOp},
+    parser::{
+        expr, hoist_functions, stmt, BinaryOp, Expr, LocatedStmt, Pattern, PrintArgs, Stmt,
+        SwitchCase, UnaryOp,
+    },
 };
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
 
 pub struct Compiler {
     chunk: Chunk,
     env: EnvRef,
     location: Location,
+    /// Names of top-level function declarations
+    functions: HashSet<String>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Compiler {
@@ -22,18 +35,66 @@ impl Compiler {
             chunk: Chunk::new(),
             env: Env::new_global(),
             location: Location::new(),
+            functions: HashSet::new(),
+        }
+    }
+
+    /// Compile onto the end of `chunk`, so the code already in it and the
+    /// globals and functions it defines stay valid
+    pub fn with_chunk(chunk: Chunk) -> Self {
+        Self {
+            chunk,
+            ..Self::new()
         }
     }
 
     pub fn compile(mut self, stmts: &[LocatedStmt]) -> Result<Chunk> {
+        let start = self.chunk.current_ip();
+        // every top-level name is known up front, so hoisted function bodies
+        // can refer to globals declared further down
         for stmt in stmts {
-            self.location = stmt.location();
-            stmt.as_inner()
-                .accept(&mut self)
-                .map_err(|e| e.at_location(self.location))?;
+            let names = match stmt.as_inner() {
+                Stmt::VarDecl { name, .. } => vec![name],
+                Stmt::MultiVarDecl(decls) => decls.iter().map(|(name, _)| name).collect(),
+                Stmt::FuncDecl { name, .. } => {
+                    self.functions.insert(name.clone());
+                    vec![name]
+                }
+                Stmt::DestructureDecl { names, .. } => names.iter().collect(),
+                _ => Vec::new(),
+            };
+            for name in names {
+                self.chunk
+                    .add_global(name.clone())
+                    .map_err(|e| e.or_at_location(stmt.location()))?;
+            }
+        }
+
+        for stmt in hoist_functions(stmts) {
+            self.compile_stmt(stmt)?;
         }
+        self.chunk.peephole(start);
+        Ok(self.chunk)
+    }
+
+    /// Compile a lone expression at `location`, leaving its value on top of
+    /// the stack for a REPL to show
+    pub fn compile_expr(mut self, expr: &Expr, location: Location) -> Result<Chunk> {
+        self.location = location;
+        expr.accept(&mut self)
+            .map_err(|e| e.or_at_location(location))?;
         Ok(self.chunk)
     }
+
+    /// Compile one statement, emitting its code and errors at its location
+    fn compile_stmt(&mut self, stmt: &LocatedStmt) -> Result<()> {
+        let enclosing = std::mem::replace(&mut self.location, stmt.location());
+        stmt.as_inner()
+            .accept(self)
+            .map_err(|e| e.or_at_location(stmt.location()))?;
+        self.location = enclosing;
+        Ok(())
+    }
 }
 
 impl Compiler {
@@ -51,23 +112,54 @@ impl Compiler {
     }
 
     fn begin_loop(&mut self) {
-        self.env.borrow_mut().begin_loop();
+        self.env.borrow_mut().begin_loop(None);
     }
 
     fn end_loop(&mut self, continue_target: usize) -> Result<()> {
         if let Some(loop_context) = self.env.borrow_mut().end_loop() {
             for break_jump in loop_context.break_jumps {
-                self.chunk.patch_jump(break_jump);
+                self.chunk.patch_jump(break_jump)?;
             }
             for continue_jump in loop_context.continue_jumps {
                 self.chunk
-                    .patch_jump_with_target(continue_jump, continue_target);
+                    .patch_jump_with_target(continue_jump, continue_target)?;
             }
         }
 
         Ok(())
     }
 
+    /// Bind the value on top of the stack to `pattern`, as locals of the
+    /// current scope
+    fn bind_pattern(&mut self, pattern: &Pattern) -> Result<()> {
+        let patterns = match pattern {
+            Pattern::Name(name) => return self.env.borrow_mut().add_local(name.clone()),
+            Pattern::Tuple(patterns) => patterns,
+        };
+        self.emit_op_with_operand(
+            OpCode::Unpack,
+            Self::count_operand(patterns.len(), "names to unpack")?,
+        );
+
+        // every element takes a slot, a nested pattern's under a name user
+        // code cannot use, and is then unpacked from there in turn
+        let first_slot = self.env.borrow().locals.len();
+        for pattern in patterns {
+            let name = match pattern {
+                Pattern::Name(name) => name.clone(),
+                Pattern::Tuple(_) => "pattern element".to_string(),
+            };
+            self.env.borrow_mut().add_local(name)?;
+        }
+        for (slot, pattern) in (first_slot..).zip(patterns) {
+            if let Pattern::Tuple(_) = pattern {
+                self.emit_op_with_operand(OpCode::GetLocal, slot as u8);
+                self.bind_pattern(pattern)?;
+            }
+        }
+        Ok(())
+    }
+
     fn begin_enclosed_scope(&mut self) {
         let enclosed = Env::new_enclosed(self.env.clone());
         self.env = enclosed;
@@ -89,9 +181,43 @@ impl Compiler {
         Ok(())
     }
 
-    fn emit_constant(&mut self, value: Value) {
-        let index = self.chunk.add_constant(value);
+    fn emit_constant(&mut self, value: Value) -> Result<()> {
+        let index = self.chunk.add_constant(value)?;
         self.emit_op_with_operand(OpCode::Constant, index);
+        Ok(())
+    }
+
+    /// `count` as the one-byte operand of an instruction taking that many
+    /// `what`
+    fn count_operand(count: usize, what: &str) -> Result<u8> {
+        u8::try_from(count).map_err(|_| Error::too_many(what, OPERANDS_SIZE - 1))
+    }
+
+    /// Push the values of `args` and print them with `op`, or with
+    /// `op_with` after also pushing the separator and terminator when
+    /// either is given
+    fn compile_print(&mut self, args: &PrintArgs, op: OpCode, op_with: OpCode) -> Result<()> {
+        for expr in &args.values {
+            expr.accept(self)?;
+        }
+        if args.sep.is_none() && args.end.is_none() {
+            self.emit_op_with_operand(
+                op,
+                Self::count_operand(args.values.len(), "values to print")?,
+            );
+            return Ok(());
+        }
+        for (option, default) in [(&args.sep, " "), (&args.end, "\n")] {
+            match option {
+                Some(expr) => expr.accept(self)?,
+                None => self.emit_constant(Value::String(default.to_string()))?,
+            }
+        }
+        self.emit_op_with_operand(
+            op_with,
+            Self::count_operand(args.values.len(), "values to print")?,
+        );
+        Ok(())
     }
 
     fn emit_jump(&mut self, op: OpCode) -> usize {
@@ -115,11 +241,126 @@ impl Compiler {
         self.emit_byte(operand);
     }
 
-    fn emit_loop(&mut self, loop_start: usize) {
+    /// Push a slice bound, with nil for a missing one
+    fn compile_bound(&mut self, bound: Option<&Expr>) -> Result<()> {
+        match bound {
+            Some(bound) => bound.accept(self),
+            None => {
+                self.emit_op(OpCode::Nil);
+                Ok(())
+            }
+        }
+    }
+
+    /// Name the array an `Index` or `IndexSet` acts on, if it is a variable
+    fn add_array_name(&mut self, array: &Expr) {
+        if let Expr::Variable(name) = array {
+            self.chunk.add_operand_name(name.clone());
+        }
+    }
+
+    /// Report a non-fatal problem at the current statement
+    fn warn(&mut self, message: &str) {
+        let warning = Warning::new(message.to_string(), self.location);
+        self.chunk.add_warning(warning);
+    }
+
+    /// Apply `op` to the value on top of the stack and `right`
+    fn binary_step(&mut self, op: &BinaryOp, right: &Expr, location: Location) -> Result<()> {
+        match op {
+            BinaryOp::LogicalAnd => {
+                self.emit_op(OpCode::Dup); // keep left value on stack
+                let left_jump = self.emit_jump(OpCode::JumpIfFalse);
+
+                self.emit_op(OpCode::Pop); // pop left value
+                right.accept(self)?;
+                let right_jump = self.emit_jump(OpCode::Jump);
+
+                self.chunk.patch_jump(left_jump)?;
+                self.chunk.patch_jump(right_jump)?;
+            }
+            BinaryOp::LogicalOr => {
+                self.emit_op(OpCode::Dup);
+                let left_jump = self.emit_jump(OpCode::JumpIfTrue);
+
+                self.emit_op(OpCode::Pop);
+                right.accept(self)?;
+                let right_jump = self.emit_jump(OpCode::Jump);
+
+                self.chunk.patch_jump(left_jump)?;
+                self.chunk.patch_jump(right_jump)?;
+            }
+            _ => {
+                right.accept(self)?;
+                // runtime errors from the operator point at it, not the statement
+                let enclosing = std::mem::replace(&mut self.location, location);
+                self.emit_binary_op(op);
+                self.location = enclosing;
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_binary_op(&mut self, op: &BinaryOp) {
+        match op {
+            BinaryOp::Add => self.emit_op(OpCode::Add),
+            BinaryOp::Subtract => self.emit_op(OpCode::Subtract),
+            BinaryOp::Multiply => self.emit_op(OpCode::Multiply),
+            BinaryOp::Divide => self.emit_op(OpCode::Divide),
+            BinaryOp::Equal => self.emit_op(OpCode::Equal),
+            BinaryOp::NotEqual => self.emit_op(OpCode::NotEqual),
+            BinaryOp::LessThan => self.emit_op(OpCode::LessThan),
+            BinaryOp::LessEqual => self.emit_op(OpCode::LessEqual),
+            BinaryOp::GreaterThan => self.emit_op(OpCode::GreaterThan),
+            BinaryOp::GreaterEqual => self.emit_op(OpCode::GreaterEqual),
+            BinaryOp::LogicalAnd | BinaryOp::LogicalOr => unreachable!(),
+        }
+    }
+
+    /// Run `compile` on code that can never run, only for the errors it
+    /// reports, then throw away the code and scope changes it made
+    fn check_dead_code(&mut self, compile: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        let chunk = self.chunk.clone();
+        let scratch = Rc::new(RefCell::new(self.env.borrow().clone()));
+        let env = std::mem::replace(&mut self.env, scratch);
+        let result = compile(self);
+        self.chunk = chunk;
+        self.env = env;
+        result
+    }
+
+    /// Check the body of a loop that never runs, as `check_dead_code` does
+    fn check_dead_loop(&mut self, body: &LocatedStmt, increment: Option<&Expr>) -> Result<()> {
+        self.check_dead_code(|compiler| {
+            let loop_start = compiler.chunk.current_ip();
+            compiler.begin_loop();
+            compiler.compile_stmt(body)?;
+            if let Some(increment) = increment {
+                increment.accept(compiler)?;
+            }
+            compiler.end_loop(loop_start)
+        })
+    }
+
+    /// Test a loop's condition, returning the jump out of the loop to patch
+    /// after the body, or none when the condition is always true
+    fn emit_loop_condition(&mut self, condition: &Expr) -> Result<Option<usize>> {
+        if constant_truthiness(condition) == Some(true) {
+            return Ok(None);
+        }
+        condition.accept(self)?;
+        Ok(Some(self.emit_jump(OpCode::JumpIfFalse)))
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) -> Result<()> {
         let offset = self.chunk.current_ip() - loop_start + 3; // +3 for the jump instruction
+        let [high, low] = u16::try_from(offset)
+            .map_err(|_| Error::jump_too_large())?
+            .to_be_bytes();
         self.emit_byte(OpCode::Loop as u8);
-        self.emit_byte((offset >> 8) as u8);
-        self.emit_byte(offset as u8);
+        self.emit_byte(high);
+        self.emit_byte(low);
+        Ok(())
     }
 }
 
@@ -130,12 +371,12 @@ impl stmt::Visitor<Result<()>> for Compiler {
         Ok(())
     }
 
-    fn visit_print(&mut self, exprs: &[Expr]) -> Result<()> {
-        for expr in exprs {
-            expr.accept(self)?;
-        }
-        self.emit_op_with_operand(OpCode::Print, exprs.len() as u8);
-        Ok(())
+    fn visit_print(&mut self, args: &PrintArgs) -> Result<()> {
+        self.compile_print(args, OpCode::Print, OpCode::PrintWith)
+    }
+
+    fn visit_eprint(&mut self, args: &PrintArgs) -> Result<()> {
+        self.compile_print(args, OpCode::EPrint, OpCode::EPrintWith)
     }
 
     fn visit_var_decl(&mut self, name: &str, initializer: Option<&Expr>) -> Result<()> {
@@ -146,20 +387,52 @@ impl stmt::Visitor<Result<()>> for Compiler {
         }
 
         if self.env.borrow().is_global() {
-            let global_index = self.chunk.add_global(name.to_string());
-            self.emit_op_with_operand(OpCode::DefineGlobal, global_index as u8);
+            let global_index = self.chunk.add_global(name.to_string())?;
+            self.emit_op_with_operand(OpCode::DefineGlobal, global_index);
         } else {
-            self.env.borrow_mut().add_local(name.to_string());
+            self.env.borrow_mut().add_local(name.to_string())?;
         }
         Ok(())
     }
 
-    fn visit_func_decl(&mut self, name: &str, params: &[String], body: &[Stmt]) -> Result<()> {
+    fn visit_multi_var_decl(&mut self, decls: &[(String, Option<Expr>)]) -> Result<()> {
+        for (name, initializer) in decls {
+            self.visit_var_decl(name, initializer.as_ref())?;
+        }
+        Ok(())
+    }
+
+    fn visit_destructure_decl(&mut self, names: &[String], initializer: &Expr) -> Result<()> {
+        initializer.accept(self)?;
+        self.emit_op_with_operand(
+            OpCode::Unpack,
+            Self::count_operand(names.len(), "names to unpack")?,
+        );
+
+        if self.env.borrow().is_global() {
+            // the last element is on top of the stack
+            for name in names.iter().rev() {
+                let global_index = self.chunk.add_global(name.to_string())?;
+                self.emit_op_with_operand(OpCode::DefineGlobal, global_index);
+            }
+        } else {
+            self.env.borrow_mut().add_locals(names)?;
+        }
+        Ok(())
+    }
+
+    fn visit_func_decl(
+        &mut self,
+        name: &str,
+        params: &[String],
+        body: &[LocatedStmt],
+        doc: Option<&str>,
+    ) -> Result<()> {
         // predeclare function name for recursion support
         let index = if self.env.borrow().is_global() {
-            Some(self.chunk.add_global(name.to_string()))
+            Some(self.chunk.add_global(name.to_string())?)
         } else {
-            self.env.borrow_mut().add_local(name.to_string());
+            self.env.borrow_mut().add_local(name.to_string())?;
             None
         };
 
@@ -168,27 +441,34 @@ impl stmt::Visitor<Result<()>> for Compiler {
 
         self.begin_enclosed_scope(); // new enclosed env
 
-        self.env.borrow_mut().add_locals(params);
+        self.env.borrow_mut().add_locals(params)?;
         for stmt in body {
-            stmt.accept(self)?;
+            self.compile_stmt(stmt)?;
+        }
+        // the last byte alone cannot tell: it may be an operand that happens
+        // to equal `Return`, or end a branch that others jump past
+        if !matches!(
+            body.last().map(|stmt| stmt.as_inner()),
+            Some(Stmt::Return { .. })
+        ) {
+            self.chunk.end_with_return();
         }
-        self.chunk.end_with_return();
         let upvalues = self.env.borrow().upvalues.clone();
 
         self.end_enclosed_scope()?;
 
-        self.chunk.patch_jump(skip); // jump here
+        self.chunk.patch_jump(skip)?; // jump here
 
-        let proto = Value::Proto(Proto {
+        let proto_index = self.chunk.add_proto(Proto {
             name: name.to_string(),
             params: params.to_vec(),
+            doc: doc.map(str::to_string),
             start_ip,
             upvalues: upvalues.clone(),
-        });
-        let proto_index = self.chunk.add_constant(proto);
+        })?;
 
         self.emit_op_with_operand(OpCode::Closure, proto_index);
-        self.emit_byte(upvalues.len() as u8);
+        self.emit_byte(Self::count_operand(upvalues.len(), "captured variables")?);
         for upvalue in &upvalues {
             self.emit_byte(if upvalue.is_local { 1 } else { 0 });
             self.emit_byte(upvalue.index as u8);
@@ -204,65 +484,163 @@ impl stmt::Visitor<Result<()>> for Compiler {
     fn visit_if(
         &mut self,
         condition: &Expr,
-        then_branch: &Stmt,
-        else_branch: Option<&Stmt>,
+        then_branch: &LocatedStmt,
+        else_branch: Option<&LocatedStmt>,
     ) -> Result<()> {
+        // a constant condition leaves one branch dead, so only emit the other
+        match constant_truthiness(condition) {
+            Some(true) => {
+                if let Some(else_branch) = else_branch {
+                    self.warn("unreachable else branch: condition is always true");
+                    self.check_dead_code(|compiler| compiler.compile_stmt(else_branch))?;
+                }
+                return self.compile_stmt(then_branch);
+            }
+            Some(false) => {
+                self.warn("unreachable code: condition is always false");
+                self.check_dead_code(|compiler| compiler.compile_stmt(then_branch))?;
+                return else_branch.map_or(Ok(()), |else_branch| self.compile_stmt(else_branch));
+            }
+            None => {}
+        }
+
         condition.accept(self)?;
 
         let then_jump = self.emit_jump(OpCode::JumpIfFalse);
-        then_branch.accept(self)?;
+        self.compile_stmt(then_branch)?;
 
         let else_jump = self.emit_jump(OpCode::Jump);
 
-        self.chunk.patch_jump(then_jump);
+        self.chunk.patch_jump(then_jump)?;
 
         if let Some(else_branch) = else_branch {
-            else_branch.accept(self)?;
+            self.compile_stmt(else_branch)?;
         }
 
-        self.chunk.patch_jump(else_jump);
+        self.chunk.patch_jump(else_jump)?;
         Ok(())
     }
 
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> Result<()> {
+    fn visit_while(&mut self, condition: &Expr, body: &LocatedStmt) -> Result<()> {
+        if constant_truthiness(condition) == Some(false) {
+            self.warn("unreachable code: condition is always false");
+            return self.check_dead_loop(body, None);
+        }
+
         self.begin_loop();
 
         let loop_start = self.chunk.current_ip();
 
-        condition.accept(self)?;
+        let exit_jump = self.emit_loop_condition(condition)?;
+
+        self.compile_stmt(body)?;
+        self.emit_loop(loop_start)?;
+
+        if let Some(exit_jump) = exit_jump {
+            self.chunk.patch_jump(exit_jump)?;
+        }
+
+        self.end_loop(loop_start)?;
+
+        Ok(())
+    }
+
+    fn visit_while_let(
+        &mut self,
+        name: &str,
+        initializer: &Expr,
+        body: &LocatedStmt,
+    ) -> Result<()> {
+        self.begin_loop();
+
+        let loop_start = self.chunk.current_ip();
+
+        initializer.accept(self)?;
+        self.emit_op(OpCode::Dup);
+        self.emit_op(OpCode::Nil);
+        self.emit_op(OpCode::NotEqual);
         let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
 
-        body.accept(self)?;
-        self.emit_loop(loop_start);
+        // the value left on the stack becomes the binding for this iteration
+        self.begin_scope();
+        self.env.borrow_mut().add_local(name.to_string())?;
+        self.compile_stmt(body)?;
+        self.end_scope()?;
+        self.emit_loop(loop_start)?;
 
-        self.chunk.patch_jump(exit_jump);
+        self.chunk.patch_jump(exit_jump)?;
+        self.emit_op(OpCode::Pop); // pop the nil that ended the loop
 
         self.end_loop(loop_start)?;
 
         Ok(())
     }
 
+    fn visit_repeat(&mut self, count: &Expr, body: &LocatedStmt) -> Result<()> {
+        self.begin_scope();
+
+        count.accept(self)?;
+        self.emit_op(OpCode::CheckCount);
+        // the remaining iterations live in a local that user code cannot name
+        let counter = "repeat count";
+        self.env.borrow_mut().add_local(counter.to_string())?;
+        let slot = self.env.borrow().resolve_local(counter).unwrap();
+
+        let loop_start = self.chunk.current_ip();
+
+        self.begin_loop();
+
+        self.emit_op_with_operand(OpCode::GetLocal, slot);
+        self.emit_constant(Value::Number(0.0))?;
+        self.emit_op(OpCode::GreaterThan);
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+
+        // count down before the body so `continue` can jump straight to the check
+        self.emit_op_with_operand(OpCode::GetLocal, slot);
+        self.emit_constant(Value::Number(1.0))?;
+        self.emit_op(OpCode::Subtract);
+        self.emit_op_with_operand(OpCode::SetLocal, slot);
+        self.emit_op(OpCode::Pop);
+
+        self.compile_stmt(body)?;
+        self.emit_loop(loop_start)?;
+
+        self.chunk.patch_jump(exit_jump)?;
+
+        self.end_loop(loop_start)?;
+
+        self.end_scope()?;
+
+        Ok(())
+    }
+
     fn visit_for(
         &mut self,
-        initializer: Option<&Stmt>,
+        initializer: Option<&LocatedStmt>,
         condition: &Expr,
         increment: Option<&Expr>,
-        body: &Stmt,
+        body: &LocatedStmt,
     ) -> Result<()> {
         self.begin_scope();
 
         if let Some(init) = initializer {
-            init.accept(self)?;
+            self.compile_stmt(init)?;
+        }
+
+        if constant_truthiness(condition) == Some(false) {
+            // the initializer still runs, but the body never does
+            self.warn("unreachable code: condition is always false");
+            self.check_dead_loop(body, increment)?;
+            return self.end_scope();
         }
 
         let loop_start = self.chunk.current_ip();
 
         self.begin_loop();
 
-        condition.accept(self)?;
-        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        let exit_jump = self.emit_loop_condition(condition)?;
 
-        body.accept(self)?;
+        self.compile_stmt(body)?;
         let continue_target = if let Some(inc) = increment {
             let target = self.chunk.current_ip();
             inc.accept(self)?;
@@ -271,9 +649,11 @@ impl stmt::Visitor<Result<()>> for Compiler {
         } else {
             loop_start
         };
-        self.emit_loop(loop_start);
+        self.emit_loop(loop_start)?;
 
-        self.chunk.patch_jump(exit_jump);
+        if let Some(exit_jump) = exit_jump {
+            self.chunk.patch_jump(exit_jump)?;
+        }
 
         self.end_loop(continue_target)?;
 
@@ -282,22 +662,126 @@ impl stmt::Visitor<Result<()>> for Compiler {
         Ok(())
     }
 
+    fn visit_for_in(
+        &mut self,
+        pattern: &Pattern,
+        iterable: &Expr,
+        body: &LocatedStmt,
+    ) -> Result<()> {
+        // the iterable and the index of its next element live in locals
+        // that user code cannot name, which `ForIter` reads and advances
+        self.begin_scope();
+        iterable.accept(self)?;
+        self.env
+            .borrow_mut()
+            .add_local("for-in iterable".to_string())?;
+        self.emit_constant(Value::Number(0.0))?;
+        self.env
+            .borrow_mut()
+            .add_local("for-in index".to_string())?;
+
+        let loop_start = self.chunk.current_ip();
+
+        self.begin_loop();
+
+        let exit_jump = self.emit_jump(OpCode::ForIter);
+
+        // each element is bound in a scope of its own, popped every iteration
+        self.begin_scope();
+        self.bind_pattern(pattern)?;
+        self.compile_stmt(body)?;
+        self.end_scope()?;
+        self.emit_loop(loop_start)?;
+
+        self.chunk.patch_jump(exit_jump)?;
+
+        self.end_loop(loop_start)?;
+
+        self.end_scope()
+    }
+
     fn visit_return(&mut self, value: Option<&Expr>) -> Result<()> {
+        // the parser rejects this too, but statements can be built by hand;
+        // the tree-walker fails the same way rather than ending the script
+        if self.env.borrow().enclosing.is_none() {
+            return Err(Error::compilation("'return' outside function".to_string()));
+        }
         if let Some(value) = value {
             value.accept(self)?;
         } else {
             self.emit_op(OpCode::Nil);
         }
 
+        // `Return` drops the whole frame, so the locals of any enclosing
+        // blocks need no pops of their own
         self.emit_op(OpCode::Return);
         Ok(())
     }
 
-    fn visit_break(&mut self) -> Result<()> {
+    fn visit_switch(&mut self, subject: &Expr, cases: &[SwitchCase]) -> Result<()> {
+        // the subject lives in a local that user code cannot name, so each
+        // case can compare against it
+        self.begin_scope();
+        subject.accept(self)?;
+        let name = "switch subject";
+        self.env.borrow_mut().add_local(name.to_string())?;
+        let slot = self.env.borrow().resolve_local(name).unwrap();
+
+        self.env.borrow_mut().begin_switch();
+
+        // test the cases in order, jumping to the body of the first to match
+        let mut body_jumps = Vec::with_capacity(cases.len());
+        for case in cases {
+            let Some(value) = &case.value else {
+                body_jumps.push(None);
+                continue;
+            };
+            self.emit_op_with_operand(OpCode::GetLocal, slot);
+            value.accept(self)?;
+            self.emit_op(OpCode::Equal);
+            let next_test = self.emit_jump(OpCode::JumpIfFalse);
+            body_jumps.push(Some(self.emit_jump(OpCode::Jump)));
+            self.chunk.patch_jump(next_test)?;
+        }
+        // no case matched: go to `default`, or past the switch
+        let no_match = self.emit_jump(OpCode::Jump);
+
+        // the bodies follow one another, so each falls through to the next
+        let mut default_start = None;
+        for (case, body_jump) in cases.iter().zip(body_jumps) {
+            let body_start = self.chunk.current_ip();
+            match body_jump {
+                Some(jump) => self.chunk.patch_jump_with_target(jump, body_start)?,
+                None => default_start = Some(body_start),
+            }
+            stmt::Visitor::visit_block(self, &case.body)?;
+        }
+        match default_start {
+            Some(start) => self.chunk.patch_jump_with_target(no_match, start)?,
+            None => self.chunk.patch_jump(no_match)?,
+        }
+
+        let switch_end = self.chunk.current_ip();
+        self.end_loop(switch_end)?;
+        self.end_scope()
+    }
+
+    fn visit_break(&mut self, value: Option<&Expr>) -> Result<()> {
         if !self.env.borrow().in_loop() {
             return Err(Error::compilation("break outside of loop".to_string()));
         }
 
+        if let Some(value) = value {
+            let Some(slot) = self.env.borrow().loop_value_slot() else {
+                return Err(Error::compilation(
+                    "only 'break' in a 'loop' can carry a value".to_string(),
+                ));
+            };
+            value.accept(self)?;
+            self.emit_op_with_operand(OpCode::SetLocal, slot);
+            self.emit_op(OpCode::Pop);
+        }
+
         // pop locals that are in the loop
         let pop_count = self.env.borrow().get_loop_locals_to_pop();
         for _ in 0..pop_count {
@@ -310,12 +794,12 @@ impl stmt::Visitor<Result<()>> for Compiler {
     }
 
     fn visit_continue(&mut self) -> Result<()> {
-        if !self.env.borrow().in_loop() {
+        if !self.env.borrow().can_continue() {
             return Err(Error::compilation("continue outside of loop".to_string()));
         }
 
-        // pop locals that are in the loop
-        let pop_count = self.env.borrow().get_loop_locals_to_pop();
+        // pop locals that are in the loop, including those of any switch in it
+        let pop_count = self.env.borrow().get_continue_locals_to_pop();
         for _ in 0..pop_count {
             self.emit_op(OpCode::Pop);
         }
@@ -325,29 +809,33 @@ impl stmt::Visitor<Result<()>> for Compiler {
         Ok(())
     }
 
-    fn visit_block(&mut self, statements: &[Stmt]) -> Result<()> {
+    fn visit_block(&mut self, statements: &[LocatedStmt]) -> Result<()> {
         self.begin_scope();
         for stmt in statements {
-            stmt.accept(self)?;
+            self.compile_stmt(stmt)?;
         }
         self.end_scope()?;
         Ok(())
     }
+
+    fn visit_empty(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl expr::Visitor<Result<()>> for Compiler {
     fn visit_number(&mut self, value: f64) -> Result<()> {
-        self.emit_constant(Value::Number(value));
+        self.emit_constant(Value::Number(value))?;
         Ok(())
     }
 
     fn visit_string(&mut self, value: &str) -> Result<()> {
-        self.emit_constant(Value::String(value.to_string()));
+        self.emit_constant(Value::String(value.to_string()))?;
         Ok(())
     }
 
     fn visit_boolean(&mut self, value: bool) -> Result<()> {
-        self.emit_constant(Value::Boolean(value));
+        self.emit_constant(Value::Boolean(value))?;
         Ok(())
     }
 
@@ -378,58 +866,81 @@ impl expr::Visitor<Result<()>> for Compiler {
         for element in elements {
             element.accept(self)?;
         }
-        self.emit_op_with_operand(OpCode::Array, elements.len() as u8);
+        self.emit_op_with_operand(
+            OpCode::Array,
+            Self::count_operand(elements.len(), "array elements")?,
+        );
         Ok(())
     }
 
-    fn visit_binary(&mut self, left: &Expr, op: &BinaryOp, right: &Expr) -> Result<()> {
-        match op {
-            BinaryOp::LogicalAnd => {
-                left.accept(self)?;
-                self.emit_op(OpCode::Dup); // keep left value on stack
-                let left_jump = self.emit_jump(OpCode::JumpIfFalse);
-
-                self.emit_op(OpCode::Pop); // pop left value
-                right.accept(self)?;
-                let right_jump = self.emit_jump(OpCode::Jump);
+    fn visit_binary(
+        &mut self,
+        left: &Expr,
+        op: &BinaryOp,
+        right: &Expr,
+        location: Location,
+    ) -> Result<()> {
+        // compile the left spine of a chain such as `a + b + c` in a loop
+        // rather than recursing once per operator, so long chains are fine
+        let mut steps = vec![(op, right, location)];
+        let mut leftmost = left;
+        while let Expr::Binary {
+            left,
+            operator,
+            right,
+            location,
+        } = leftmost
+        {
+            steps.push((operator, right, *location));
+            leftmost = left;
+        }
 
-                self.chunk.patch_jump(left_jump);
-                self.chunk.patch_jump(right_jump);
-            }
-            BinaryOp::LogicalOr => {
-                left.accept(self)?;
-                self.emit_op(OpCode::Dup);
-                let left_jump = self.emit_jump(OpCode::JumpIfTrue);
+        leftmost.accept(self)?;
+        for (op, right, location) in steps.into_iter().rev() {
+            self.binary_step(op, right, location)?;
+        }
+        Ok(())
+    }
 
-                self.emit_op(OpCode::Pop);
-                right.accept(self)?;
-                let right_jump = self.emit_jump(OpCode::Jump);
+    fn visit_chained_comparison(
+        &mut self,
+        operands: &[Expr],
+        operators: &[BinaryOp],
+    ) -> Result<()> {
+        let (last_op, middle_ops) = operators.split_last().unwrap();
+        let (last, middle) = operands[1..].split_last().unwrap();
+
+        // keep each middle operand under the result so it is evaluated once:
+        // [a, b] -> Swap -> [b, a] -> Over -> [b, a, b] -> compare -> [b, a < b]
+        operands[0].accept(self)?;
+        let mut fail_jumps = Vec::new();
+        for (op, operand) in middle_ops.iter().zip(middle) {
+            operand.accept(self)?;
+            self.emit_op(OpCode::Swap);
+            self.emit_op(OpCode::Over);
+            self.emit_binary_op(op);
+            fail_jumps.push(self.emit_jump(OpCode::JumpIfFalse));
+        }
+        last.accept(self)?;
+        self.emit_binary_op(last_op);
+        let end_jump = self.emit_jump(OpCode::Jump);
 
-                self.chunk.patch_jump(left_jump);
-                self.chunk.patch_jump(right_jump);
-            }
-            _ => {
-                left.accept(self)?;
-                right.accept(self)?;
-                match op {
-                    BinaryOp::Add => self.emit_op(OpCode::Add),
-                    BinaryOp::Subtract => self.emit_op(OpCode::Subtract),
-                    BinaryOp::Multiply => self.emit_op(OpCode::Multiply),
-                    BinaryOp::Divide => self.emit_op(OpCode::Divide),
-                    BinaryOp::Equal => self.emit_op(OpCode::Equal),
-                    BinaryOp::NotEqual => self.emit_op(OpCode::NotEqual),
-                    BinaryOp::LessThan => self.emit_op(OpCode::LessThan),
-                    BinaryOp::LessEqual => self.emit_op(OpCode::LessEqual),
-                    BinaryOp::GreaterThan => self.emit_op(OpCode::GreaterThan),
-                    BinaryOp::GreaterEqual => self.emit_op(OpCode::GreaterEqual),
-                    _ => unreachable!(),
-                }
-            }
+        for jump in fail_jumps {
+            self.chunk.patch_jump(jump)?;
         }
+        self.emit_op(OpCode::Pop); // pop the kept middle operand
+        self.emit_op(OpCode::False);
+
+        self.chunk.patch_jump(end_jump)?;
         Ok(())
     }
 
     fn visit_unary(&mut self, op: &UnaryOp, operand: &Expr) -> Result<()> {
+        // fold negative literals, `-0` included, into a single constant
+        if let (UnaryOp::Negate, Expr::Number(value)) = (op, operand) {
+            return self.emit_constant(Value::Number(-value));
+        }
+
         operand.accept(self)?;
         match op {
             UnaryOp::Negate => self.emit_op(OpCode::Negate),
@@ -456,6 +967,20 @@ impl expr::Visitor<Result<()>> for Compiler {
             }
         };
 
+        // a function declared at the top level rarely means to become data
+        if matches!(op, OpCode::SetGlobal) && self.functions.contains(name) {
+            if let Some(type_name) = non_function_type(value) {
+                let article = if type_name.starts_with('a') {
+                    "an"
+                } else {
+                    "a"
+                };
+                self.warn(&format!(
+                    "function '{name}' is reassigned to {article} {type_name}"
+                ));
+            }
+        }
+
         self.emit_op_with_operand(op, index);
         Ok(())
     }
@@ -464,6 +989,7 @@ impl expr::Visitor<Result<()>> for Compiler {
         array.accept(self)?;
         index.accept(self)?;
         value.accept(self)?;
+        self.add_array_name(array);
         self.emit_op(OpCode::IndexSet);
         Ok(())
     }
@@ -471,16 +997,154 @@ impl expr::Visitor<Result<()>> for Compiler {
     fn visit_index(&mut self, array: &Expr, index: &Expr) -> Result<()> {
         array.accept(self)?;
         index.accept(self)?;
+        self.add_array_name(array);
         self.emit_op(OpCode::Index);
         Ok(())
     }
 
-    fn visit_call(&mut self, callee: &Expr, arguments: &[Expr]) -> Result<()> {
+    fn visit_slice(
+        &mut self,
+        array: &Expr,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+    ) -> Result<()> {
+        array.accept(self)?;
+        self.compile_bound(start)?;
+        self.compile_bound(end)?;
+        self.emit_op(OpCode::Slice);
+        Ok(())
+    }
+
+    fn visit_slice_assign(
+        &mut self,
+        array: &Expr,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+        value: &Expr,
+    ) -> Result<()> {
+        array.accept(self)?;
+        self.compile_bound(start)?;
+        self.compile_bound(end)?;
+        value.accept(self)?;
+        self.emit_op(OpCode::SliceSet);
+        Ok(())
+    }
+
+    fn visit_call(&mut self, callee: &Expr, arguments: &[Expr], location: Location) -> Result<()> {
+        // literals can never evaluate to a function, so fail before running
+        if let Some(type_name) = literal_type(callee) {
+            return Err(Error::not_callable_literal(type_name));
+        }
+
         for argument in arguments {
             argument.accept(self)?;
         }
         callee.accept(self)?;
-        self.emit_op_with_operand(OpCode::Call, arguments.len() as u8);
+        if let Expr::Variable(name) = callee {
+            self.chunk.add_operand_name(name.clone());
+        }
+        // any call may reach `defined`, which needs the names of the locals
+        let names = self.env.borrow().visible_names();
+        self.chunk.add_local_names(names);
+        let enclosing = std::mem::replace(&mut self.location, location);
+        self.emit_op_with_operand(
+            OpCode::Call,
+            Self::count_operand(arguments.len(), "arguments")?,
+        );
+        self.location = enclosing;
         Ok(())
     }
+
+    fn visit_loop(&mut self, body: &[LocatedStmt]) -> Result<()> {
+        // the value lives in a local that user code cannot name, which
+        // `break value;` sets; the parser only allows a `loop` where nothing
+        // but locals is on the stack, so that local's slot is the stack top
+        self.begin_scope();
+        self.emit_op(OpCode::Nil);
+        let name = "loop value";
+        self.env.borrow_mut().add_local(name.to_string())?;
+        let slot = self.env.borrow().resolve_local(name).unwrap();
+
+        self.env.borrow_mut().begin_loop(Some(slot));
+
+        let loop_start = self.chunk.current_ip();
+        stmt::Visitor::visit_block(self, body)?;
+        self.emit_loop(loop_start)?;
+
+        self.end_loop(loop_start)?;
+
+        // leave the value local on the stack as the loop's result
+        self.env.borrow_mut().end_scope()?;
+
+        Ok(())
+    }
+}
+
+/// Type of a literal, which can never evaluate to a function
+fn literal_type(expr: &Expr) -> Option<&'static str> {
+    match expr {
+        Expr::Number(_) => Some("number"),
+        Expr::String(_) => Some("string"),
+        Expr::Boolean(_) => Some("boolean"),
+        Expr::Nil => Some("nil"),
+        Expr::Array(_) => Some("array"),
+        _ => None,
+    }
+}
+
+/// Type of the value of `expr`, when it can never be a function, or just
+/// "non-function value" when which type depends on the operands
+fn non_function_type(expr: &Expr) -> Option<&'static str> {
+    if let Some(type_name) = literal_type(expr) {
+        return Some(type_name);
+    }
+    match expr {
+        Expr::Unary {
+            operator: UnaryOp::Not,
+            ..
+        }
+        | Expr::ChainedComparison { .. } => Some("boolean"),
+        Expr::Unary {
+            operator: UnaryOp::Negate,
+            ..
+        } => Some("number"),
+        Expr::Binary {
+            left,
+            operator: BinaryOp::LogicalAnd | BinaryOp::LogicalOr,
+            right,
+            ..
+        } => match (non_function_type(left)?, non_function_type(right)?) {
+            (left, right) if left == right => Some(left),
+            _ => Some("non-function value"),
+        },
+        Expr::Binary {
+            operator:
+                BinaryOp::Equal
+                | BinaryOp::NotEqual
+                | BinaryOp::LessThan
+                | BinaryOp::LessEqual
+                | BinaryOp::GreaterThan
+                | BinaryOp::GreaterEqual,
+            ..
+        } => Some("boolean"),
+        Expr::Binary { .. } => Some("non-function value"),
+        Expr::Slice { .. } => Some("array"),
+        Expr::Assign { value, .. } | Expr::IndexAssign { value, .. } => non_function_type(value),
+        _ => None,
+    }
+}
+
+/// Truthiness of a condition that is fixed at compile time, if any
+fn constant_truthiness(condition: &Expr) -> Option<bool> {
+    match condition {
+        Expr::Boolean(b) => Some(*b),
+        Expr::Nil => Some(false),
+        Expr::Number(n) => Some(*n != 0.0),
+        Expr::String(s) => Some(!s.is_empty()),
+        Expr::Unary {
+            operator: UnaryOp::Not,
+            operand,
+        } => constant_truthiness(operand).map(|truthy| !truthy),
+        _ => None,
+    }
 }
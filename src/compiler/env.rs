@@ -1,5 +1,8 @@
 use super::value::UpvalueInfo;
-use crate::error::{Error, Result};
+use crate::{
+    constant::LOCALS_SIZE,
+    error::{Error, Result},
+};
 
 #[derive(Debug, Clone)]
 pub struct Local {
@@ -11,14 +14,18 @@ use std::{cell::RefCell, rc::Rc};
 
 pub type EnvRef = Rc<RefCell<Env>>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LoopContext {
     pub break_jumps: Vec<usize>,
     pub continue_jumps: Vec<usize>,
     pub scope_depth: usize,
+    /// The local holding the value of a `loop`, which `break` may set
+    pub value_slot: Option<u8>,
+    /// Whether this is a `switch`, which `break` leaves but `continue` skips
+    pub is_switch: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Env {
     pub locals: Vec<Local>,
     pub upvalues: Vec<UpvalueInfo>,
@@ -77,18 +84,26 @@ impl Env {
         self.scope_depth == 0
     }
 
-    pub fn add_locals(&mut self, names: &[String]) {
+    pub fn add_locals(&mut self, names: &[String]) -> Result<()> {
         for name in names {
-            self.add_local(name.clone());
+            self.add_local(name.clone())?;
         }
+        Ok(())
     }
 
-    pub fn add_local(&mut self, name: String) {
+    pub fn add_local(&mut self, name: String) -> Result<()> {
+        if self.locals.len() == LOCALS_SIZE {
+            return Err(Error::too_many(
+                "local variables in one function",
+                LOCALS_SIZE,
+            ));
+        }
         self.locals.push(Local {
             name,
             depth: self.scope_depth,
             is_captured: false,
         });
+        Ok(())
     }
 
     pub fn resolve_local(&self, name: &str) -> Option<u8> {
@@ -100,6 +115,22 @@ impl Env {
             .map(|(index, _)| index as u8)
     }
 
+    /// Names of the locals in scope here and in enclosing functions,
+    /// leaving out the hidden ones, whose names contain a space
+    pub fn visible_names(&self) -> Vec<String> {
+        let mut names = self
+            .enclosing
+            .as_ref()
+            .map_or_else(Vec::new, |enclosing| enclosing.borrow().visible_names());
+        names.extend(
+            self.locals
+                .iter()
+                .filter(|local| !local.name.contains(' '))
+                .map(|local| local.name.clone()),
+        );
+        names
+    }
+
     pub fn add_upvalue(&mut self, index: usize, is_local: bool) -> u8 {
         for (i, upvalue) in self.upvalues.iter().enumerate() {
             if upvalue.index == index && upvalue.is_local == is_local {
@@ -108,7 +139,7 @@ impl Env {
         }
 
         self.upvalues.push(UpvalueInfo { index, is_local });
-        self.upvalues.len() as u8 - 1
+        (self.upvalues.len() - 1) as u8
     }
 
     pub fn resolve_upvalue(&mut self, name: &str) -> Option<u8> {
@@ -126,16 +157,42 @@ impl Env {
         Some(self.add_upvalue(index as usize, is_local))
     }
 
-    pub fn begin_loop(&mut self) {
+    pub fn begin_loop(&mut self, value_slot: Option<u8>) {
         self.loop_contexts.push(LoopContext {
             break_jumps: Vec::new(),
             continue_jumps: Vec::new(),
             scope_depth: self.scope_depth,
+            value_slot,
+            is_switch: false,
         });
     }
 
+    pub fn begin_switch(&mut self) {
+        self.begin_loop(None);
+        if let Some(context) = self.loop_contexts.last_mut() {
+            context.is_switch = true;
+        }
+    }
+
+    /// The local holding the value of the innermost loop, if it is a `loop`
+    pub fn loop_value_slot(&self) -> Option<u8> {
+        self.loop_contexts
+            .last()
+            .and_then(|context| context.value_slot)
+    }
+
     pub fn get_loop_locals_to_pop(&self) -> usize {
-        if let Some(context) = self.loop_contexts.last() {
+        self.locals_to_pop(self.loop_contexts.last())
+    }
+
+    /// Like `get_loop_locals_to_pop`, but for the innermost loop that is not
+    /// a `switch`, which is the one `continue` goes back to
+    pub fn get_continue_locals_to_pop(&self) -> usize {
+        self.locals_to_pop(self.continue_context())
+    }
+
+    fn locals_to_pop(&self, context: Option<&LoopContext>) -> usize {
+        if let Some(context) = context {
             let mut pop_count = 0;
             for local in self.locals.iter().rev() {
                 if local.depth > context.scope_depth {
@@ -150,6 +207,13 @@ impl Env {
         }
     }
 
+    fn continue_context(&self) -> Option<&LoopContext> {
+        self.loop_contexts
+            .iter()
+            .rev()
+            .find(|context| !context.is_switch)
+    }
+
     pub fn end_loop(&mut self) -> Option<LoopContext> {
         self.loop_contexts.pop()
     }
@@ -164,7 +228,12 @@ impl Env {
     }
 
     pub fn add_continue_jump(&mut self, jump_position: usize) -> Result<()> {
-        if let Some(context) = self.loop_contexts.last_mut() {
+        let context = self
+            .loop_contexts
+            .iter_mut()
+            .rev()
+            .find(|context| !context.is_switch);
+        if let Some(context) = context {
             context.continue_jumps.push(jump_position);
             Ok(())
         } else {
@@ -175,4 +244,9 @@ impl Env {
     pub fn in_loop(&self) -> bool {
         !self.loop_contexts.is_empty()
     }
+
+    /// Whether there is a loop, other than a `switch`, to `continue`
+    pub fn can_continue(&self) -> bool {
+        self.continue_context().is_some()
+    }
 }
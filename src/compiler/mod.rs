@@ -1,11 +1,10 @@
-mod buildin;
 pub mod chunk;
+#[allow(clippy::module_inception)]
 pub mod compiler;
 mod env;
 pub mod opcode;
 pub mod value;
 
-pub use buildin::BUILTIN_FUNCTIONS;
 pub use chunk::Chunk;
 pub use compiler::Compiler;
 pub use opcode::OpCode;
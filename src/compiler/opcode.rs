@@ -40,6 +40,8 @@ pub enum OpCode {
     JumpIfFalse,
     JumpIfTrue,
     Loop,
+    CheckCount, // Ensure the top of the stack is a valid repeat count
+    ForIter,    // Push the next element of a for-in, or jump when there is none
 
     // Functions
     Call = 60,
@@ -49,11 +51,20 @@ pub enum OpCode {
     Pop = 70,
     Print,
     Dup,
+    Swap,
+    Over,
+    EPrint,
+    PrintWith,  // Print with the separator and terminator on top of the values
+    EPrintWith, // EPrint with the separator and terminator on top of the values
+    Nop,        // Do nothing, standing in for a removed instruction
 
     // Arrays
     Array = 80,
     Index,
     IndexSet,
+    Unpack,
+    Slice,    // array[start:end], with nil for a missing bound
+    SliceSet, // array[start:end] = value
 
     // Closures and Upvalues
     Closure = 90, // Create closure from function prototype
@@ -97,14 +108,25 @@ impl TryFrom<u8> for OpCode {
             51 => Ok(OpCode::JumpIfFalse),
             52 => Ok(OpCode::JumpIfTrue),
             53 => Ok(OpCode::Loop),
+            54 => Ok(OpCode::CheckCount),
+            55 => Ok(OpCode::ForIter),
             60 => Ok(OpCode::Call),
             61 => Ok(OpCode::Return),
             70 => Ok(OpCode::Pop),
             71 => Ok(OpCode::Print),
             72 => Ok(OpCode::Dup),
+            73 => Ok(OpCode::Swap),
+            74 => Ok(OpCode::Over),
+            75 => Ok(OpCode::EPrint),
+            76 => Ok(OpCode::PrintWith),
+            77 => Ok(OpCode::EPrintWith),
+            78 => Ok(OpCode::Nop),
             80 => Ok(OpCode::Array),
             81 => Ok(OpCode::Index),
             82 => Ok(OpCode::IndexSet),
+            83 => Ok(OpCode::Unpack),
+            84 => Ok(OpCode::Slice),
+            85 => Ok(OpCode::SliceSet),
             90 => Ok(OpCode::Closure),
             91 => Ok(OpCode::GetUpvalue),
             92 => Ok(OpCode::SetUpvalue),
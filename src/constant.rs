@@ -1,3 +1,17 @@
 pub const STACK_SIZE: usize = 1 << 10;
 pub const GLOBALS_SIZE: usize = 1 << 8;
 pub const CONSTANTS_SIZE: usize = 1 << 8;
+pub const PROTOS_SIZE: usize = 1 << 8;
+pub const LOCALS_SIZE: usize = 1 << 8;
+/// Operand counts, such as the arguments of a call, fit in one byte
+pub const OPERANDS_SIZE: usize = 1 << 8;
+/// How many elements an array made from a range may have, so converting a
+/// huge range is an error rather than an allocation failure that aborts
+pub const MAX_ARRAY_LENGTH: usize = 1 << 24;
+/// How deeply expressions and statements may nest before parsing stops,
+/// keeping every recursive pass well within the native stack
+pub const MAX_NESTING_DEPTH: usize = 64;
+/// How many operators may follow one another along the left of a chain
+/// such as `a + b + c`; the backends walk that spine in a loop, but cloning
+/// or printing the tree still recurses down it
+pub const MAX_CHAIN_LENGTH: usize = 512;
@@ -21,6 +21,8 @@ pub struct Error {
     pub message: String,
     pub error_type: ErrorType,
     pub location: Option<Location>,
+    /// Where the error ends, for errors covering more than one position
+    pub end: Option<Location>,
     pub file: Option<String>,
 }
 
@@ -30,6 +32,7 @@ impl Error {
             message,
             error_type,
             location: None,
+            end: None,
             file: None,
         }
     }
@@ -39,6 +42,7 @@ impl Error {
             message,
             error_type,
             location: Some(location),
+            end: None,
             file: None,
         }
     }
@@ -53,10 +57,25 @@ impl Error {
             message,
             error_type,
             location: Some(location),
+            end: None,
             file: Some(file),
         }
     }
 
+    /// Extend the error from its location up to `end`
+    pub fn spanning(mut self, end: Location) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Display the error followed by the source lines it covers, underlined
+    pub fn with_source<'a>(&'a self, source: &'a str) -> WithSource<'a> {
+        WithSource {
+            error: self,
+            source,
+        }
+    }
+
     pub fn in_file(mut self, file: String) -> Self {
         self.file = Some(file);
         self
@@ -67,6 +86,13 @@ impl Error {
         self
     }
 
+    /// Attach `location` unless a nested statement or expression already gave a
+    /// more precise one
+    pub fn or_at_location(mut self, location: Location) -> Self {
+        self.location.get_or_insert(location);
+        self
+    }
+
     pub fn lexical(message: String, location: Location) -> Self {
         Self::with_location(ErrorType::Lexical, message, location)
     }
@@ -127,6 +153,63 @@ impl Error {
         Self::compilation("too many constants in chunk (max 256)".to_string())
     }
 
+    /// Create an error for calling a literal that can never be a function
+    pub fn not_callable_literal(type_name: &str) -> Self {
+        Self::compilation(format!(
+            "cannot call a {type_name} literal; only functions can be called"
+        ))
+    }
+
+    /// Create an error for calling a value that is not a function, naming the
+    /// variable it was read from when known
+    pub fn not_callable(name: Option<&str>, type_name: &str) -> Self {
+        match name {
+            Some(name) => Self::runtime(format!("cannot call '{name}' ({type_name})")),
+            None => Self::runtime(format!("cannot call a value of type '{type_name}'")),
+        }
+    }
+
+    /// Create an error for indexing, or assigning through an index, a value
+    /// that is not an array
+    pub fn not_indexable(type_name: &str) -> Self {
+        Self::runtime(format!(
+            "cannot index a value of type '{type_name}'; only arrays and ranges can be indexed"
+        ))
+    }
+
+    /// Create an error for changing or slicing a range, which only arrays support
+    pub fn range_unsupported(what: &str) -> Self {
+        Self::runtime(format!(
+            "ranges cannot be {what}; convert one with to_array() first"
+        ))
+    }
+
+    /// Create an error for a `for`-`in` over a value that is not an array or range
+    pub fn not_iterable(type_name: &str) -> Self {
+        Self::runtime(format!(
+            "cannot loop over a value of type '{type_name}'; only arrays and ranges can be"
+        ))
+    }
+
+    /// Create an error for destructuring a value that is not an array
+    pub fn destructure_non_array(type_name: &str) -> Self {
+        Self::runtime(format!(
+            "cannot destructure value of type '{type_name}', expected array"
+        ))
+    }
+
+    /// Create an error for destructuring an array into the wrong number of names
+    pub fn destructure_length(length: usize, names: usize) -> Self {
+        Self::runtime(format!(
+            "cannot destructure array of length {length} into {names} names"
+        ))
+    }
+
+    /// Create an error for indexing an array with something other than a number
+    pub fn non_number_index(type_name: &str) -> Self {
+        Self::runtime(format!("array index must be a number, found '{type_name}'"))
+    }
+
     /// Create a global pool overflow error
     pub fn global_overflow() -> Self {
         Self::compilation("too many globals in chunk (max 256)".to_string())
@@ -137,6 +220,12 @@ impl Error {
         Self::vm_runtime(format!("invalid opcode: {}", opcode))
     }
 
+    /// Create an error for bytecode that is not well-formed, found before
+    /// running any of it
+    pub fn invalid_bytecode(ip: usize, problem: String) -> Self {
+        Self::vm_runtime(format!("invalid bytecode at {ip}: {problem}"))
+    }
+
     /// Create a type error for operations
     pub fn type_error(operation: &str, expected: &str, found: &str) -> Self {
         Self::vm_runtime(format!(
@@ -145,6 +234,17 @@ impl Error {
         ))
     }
 
+    /// Create a compilation error for a table or count that no longer fits
+    /// in a one-byte operand
+    pub fn too_many(what: &str, limit: usize) -> Self {
+        Self::compilation(format!("too many {what} (at most {limit})"))
+    }
+
+    /// Create a compilation error for a jump too far for its two-byte operand
+    pub fn jump_too_large() -> Self {
+        Self::compilation(format!("jump too large (at most {} bytes)", u16::MAX))
+    }
+
     /// Create an arity error for function calls
     pub fn arity_error(function: &str, expected: usize, found: usize) -> Self {
         Self::vm_runtime(format!(
@@ -159,10 +259,22 @@ impl Error {
     }
 
     /// Create an array index out of bounds error
-    pub fn index_out_of_bounds(index: usize, length: usize) -> Self {
-        Self::vm_runtime(format!(
-            "array index {} out of bounds (length: {})",
-            index, length
+    /// `name` is the indexed array's variable, when it is one
+    pub fn index_out_of_bounds(index: usize, length: usize, name: Option<&str>) -> Self {
+        let array = match name {
+            Some(name) => format!(" for '{name}'"),
+            None => String::new(),
+        };
+        Self::runtime(format!(
+            "array index {index} out of bounds{array} (length: {length})"
+        ))
+    }
+
+    /// Create an error for a slice reaching past the end of its array, or
+    /// ending before it starts
+    pub fn slice_out_of_bounds(start: usize, end: usize, length: usize) -> Self {
+        Self::runtime(format!(
+            "array slice [{start}:{end}] out of bounds (length: {length})"
         ))
     }
 
@@ -171,6 +283,11 @@ impl Error {
         Self::vm_runtime(format!("undefined variable '{name}'"))
     }
 
+    /// Create an error for assigning or redefining a constant global
+    pub fn assign_to_constant(name: &str) -> Self {
+        Self::vm_runtime(format!("cannot assign to constant '{name}'"))
+    }
+
     /// Create an undefined function error
     pub fn undefined_function(name: &str) -> Self {
         Self::vm_runtime(format!("undefined function '{name}'"))
@@ -248,6 +365,73 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+/// A non-fatal diagnostic, reported without stopping compilation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub message: String,
+    pub location: Location,
+}
+
+impl Warning {
+    pub fn new(message: String, location: Location) -> Self {
+        Self { message, location }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: Warning: {}",
+            self.location.line, self.location.column, self.message
+        )
+    }
+}
+
+pub struct WithSource<'a> {
+    error: &'a Error,
+    source: &'a str,
+}
+
+impl fmt::Display for WithSource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        let Some(start) = self.error.location else {
+            return Ok(());
+        };
+        let end = self.error.end.unwrap_or(start);
+
+        for (index, text) in self.source.lines().enumerate() {
+            let line = index + 1;
+            if line < start.line || line > end.line {
+                continue;
+            }
+            write!(f, "\n{line:4} | {text}")?;
+
+            let len = text.chars().count();
+            let from = if line == start.line {
+                start.column
+            } else {
+                text.chars().take_while(|ch| ch.is_whitespace()).count() + 1
+            };
+            let to = if line == end.line {
+                end.column.min(len.max(from))
+            } else {
+                len
+            };
+            if to >= from {
+                write!(
+                    f,
+                    "\n     | {}{}",
+                    " ".repeat(from - 1),
+                    "^".repeat(to - from + 1)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
         Error::io(err.to_string())
@@ -280,6 +464,12 @@ impl Error {
         self
     }
 
+    /// Create an error naming the opcode that raised it
+    pub fn with_opcode(mut self, opcode: impl fmt::Debug) -> Self {
+        self.message = format!("{} in {:?}", self.message, opcode);
+        self
+    }
+
     /// Create an error with stack size context
     pub fn with_stack_size(mut self, size: usize) -> Self {
         self.message = format!("{} (stack size: {})", self.message, size);
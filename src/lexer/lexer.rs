@@ -24,13 +24,21 @@ impl Lexer {
                 continue;
             }
             if ch == '/' && self.peek() == Some('/') {
-                self.skip_line_comment();
+                if let Some(doc) = self.line_comment() {
+                    tokens.push(Token {
+                        token_type: TokenType::DocComment(doc),
+                        location,
+                    });
+                }
                 continue;
             }
             if ch == '/' && self.peek() == Some('*') {
                 self.skip_block_comment()?;
                 continue;
             }
+            if ch == '\\' && self.skip_line_continuation() {
+                continue;
+            }
             tokens.push(self.scan_token(ch, location)?);
         }
 
@@ -48,7 +56,7 @@ impl Lexer {
 
     fn consume_char(&mut self) -> Option<(char, Location)> {
         let ch = self.peek()?;
-        let location = self.location.clone();
+        let location = self.location;
         self.advance();
         Some((ch, location))
     }
@@ -67,6 +75,30 @@ impl Lexer {
         }
     }
 
+    /// Skip a `//` comment, returning its text when it is a `///` doc comment
+    fn line_comment(&mut self) -> Option<String> {
+        let start = self.location.offset + 1; // past the second '/'
+        self.skip_line_comment();
+        let text = self.input[start..self.location.offset]
+            .iter()
+            .collect::<String>();
+        let doc = text.strip_prefix('/').filter(|doc| !doc.starts_with('/'))?;
+        let doc = doc.trim_end_matches(['\n', '\r']);
+        Some(doc.strip_prefix(' ').unwrap_or(doc).to_string())
+    }
+
+    /// Skip the newline after a `\\`, joining the two lines
+    fn skip_line_continuation(&mut self) -> bool {
+        if self.peek() == Some('\r') && self.input.get(self.location.offset + 1) == Some(&'\n') {
+            self.advance();
+        }
+        if self.peek() == Some('\n') {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
     fn skip_block_comment(&mut self) -> Result<()> {
         self.advance(); // skip *
 
@@ -151,7 +183,14 @@ impl Lexer {
                 _ => Ok(TokenType::GreaterThan),
             },
             '"' | '\'' => self.scan_string(start, ch),
+            '.' if self.peek().is_some_and(|ch| ch.is_ascii_digit()) => self.scan_number(start),
+            '.' => Err(Error::lexical(
+                "unexpected character: . (a number cannot start with '.' unless digits follow, e.g. .5)"
+                    .to_string(),
+                start,
+            )),
             '0'..='9' => self.scan_number(start),
+            'r' if self.peek() == Some('#') => self.scan_raw_identifier(),
             'a'..='z' | 'A'..='Z' | '_' => Ok(self.scan_identifier(start)),
             _ => Err(Error::lexical(format!("unexpected character: {ch}"), start)),
         }?;
@@ -163,29 +202,70 @@ impl Lexer {
     }
 
     fn scan_number(&mut self, start: Location) -> Result<TokenType> {
-        while let Some(ch) = self.peek() {
-            if !ch.is_ascii_digit() {
-                break;
-            }
+        if self.input[start.offset] == '0' && matches!(self.peek(), Some('o' | 'O')) {
             self.advance();
+            return self.scan_integer(8, "octal");
         }
-        if let Some(ch) = self.peek() {
-            if ch == '.' {
+
+        // `.5` starts directly in the fractional part
+        if self.input[start.offset] != '.' {
+            self.skip_digits();
+            if self.peek() == Some('.') {
                 self.advance();
             }
         }
+        self.skip_digits();
+
+        if self.peek() == Some('.') {
+            return Err(Error::lexical(
+                "unexpected character: . (a number can contain only one '.')".to_string(),
+                self.location,
+            ));
+        }
+
+        let text = self.input[start.offset..self.location.offset]
+            .iter()
+            .collect::<String>();
+        let number = text
+            .parse::<f64>()
+            .map_err(|_| Error::lexical(format!("invalid number literal '{text}'"), start))?;
+        Ok(TokenType::Number(number))
+    }
+
+    /// Scan the digits of an integer literal after its base prefix, such as
+    /// the `17` of `0o17`
+    fn scan_integer(&mut self, radix: u32, base_name: &str) -> Result<TokenType> {
+        let mut digits = String::new();
+        while let Some(ch) = self.peek().filter(|ch| ch.is_alphanumeric() || *ch == '_') {
+            if !ch.is_digit(radix) {
+                return Err(Error::lexical(
+                    format!("invalid digit '{ch}' in {base_name} literal"),
+                    self.location,
+                ));
+            }
+            digits.push(ch);
+            self.advance();
+        }
+
+        if digits.is_empty() {
+            return Err(Error::lexical(
+                format!("{base_name} literal has no digits"),
+                self.location,
+            ));
+        }
+        let number = u64::from_str_radix(&digits, radix).map_err(|_| {
+            Error::lexical(format!("{base_name} literal is too large"), self.location)
+        })?;
+        Ok(TokenType::Number(number as f64))
+    }
+
+    fn skip_digits(&mut self) {
         while let Some(ch) = self.peek() {
             if !ch.is_ascii_digit() {
                 break;
             }
             self.advance();
         }
-        let number = self.input[start.offset..self.location.offset]
-            .iter()
-            .collect::<String>()
-            .parse::<f64>()
-            .unwrap();
-        Ok(TokenType::Number(number))
     }
 
     fn scan_string(&mut self, start: Location, delimiter: char) -> Result<TokenType> {
@@ -204,13 +284,34 @@ impl Lexer {
         ))
     }
 
-    fn scan_identifier(&mut self, start: Location) -> TokenType {
+    /// Scan `r#name`, the identifier `name` even when `name` is a keyword
+    fn scan_raw_identifier(&mut self) -> Result<TokenType> {
+        self.advance(); // skip #
+        if !matches!(self.peek(), Some('a'..='z' | 'A'..='Z' | '_')) {
+            return Err(Error::lexical(
+                "expected an identifier after 'r#'".to_string(),
+                self.location,
+            ));
+        }
+        let name_start = self.location.offset;
+        self.skip_identifier_chars();
+        let name = self.input[name_start..self.location.offset]
+            .iter()
+            .collect::<String>();
+        Ok(TokenType::Identifier(name))
+    }
+
+    fn skip_identifier_chars(&mut self) {
         while let Some(ch) = self.peek() {
             if !ch.is_alphanumeric() && ch != '_' {
                 break;
             }
             self.advance();
         }
+    }
+
+    fn scan_identifier(&mut self, start: Location) -> TokenType {
+        self.skip_identifier_chars();
         let identifier = self.input[start.offset..self.location.offset]
             .iter()
             .collect::<String>();
@@ -220,7 +321,12 @@ impl Lexer {
             "fn" => TokenType::Fn,
             "if" => TokenType::If,
             "else" => TokenType::Else,
+            "elif" => TokenType::Elif,
             "while" => TokenType::While,
+            "repeat" => TokenType::Repeat,
+            "loop" => TokenType::Loop,
+            "switch" => TokenType::Switch,
+            "case" => TokenType::Case,
             "for" => TokenType::For,
             "return" => TokenType::Return,
             "break" => TokenType::Break,
@@ -230,7 +336,10 @@ impl Lexer {
             "false" => TokenType::Boolean(false),
             "and" => TokenType::And,
             "or" => TokenType::Or,
+            // `not x` is another spelling of `!x`
+            "not" => TokenType::Bang,
             "print" => TokenType::Print,
+            "eprint" => TokenType::Eprint,
             _ => TokenType::Identifier(identifier),
         }
     }
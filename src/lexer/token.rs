@@ -1,4 +1,5 @@
 use crate::location::Location;
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
@@ -8,13 +9,20 @@ pub enum TokenType {
     Boolean(bool),
     Identifier(String),
     Nil,
+    /// The text of a `///` comment, without the slashes
+    DocComment(String),
 
     // Keywords
     Let,
     Fn,
     If,
     Else,
+    Elif,
     While,
+    Repeat,
+    Loop,
+    Switch,
+    Case,
     For,
     Break,
     Continue,
@@ -22,6 +30,7 @@ pub enum TokenType {
     And,
     Or,
     Print,
+    Eprint,
 
     // Operators
     Plus,
@@ -55,8 +64,53 @@ pub enum TokenType {
     Eof,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl TokenType {
+    /// The reserved word spelling this token, if it is a keyword
+    pub fn keyword(&self) -> Option<&'static str> {
+        let keyword = match self {
+            TokenType::Nil => "nil",
+            TokenType::Boolean(true) => "true",
+            TokenType::Boolean(false) => "false",
+            TokenType::Let => "let",
+            TokenType::Fn => "fn",
+            TokenType::If => "if",
+            TokenType::Else => "else",
+            TokenType::Elif => "elif",
+            TokenType::While => "while",
+            TokenType::Repeat => "repeat",
+            TokenType::Loop => "loop",
+            TokenType::Switch => "switch",
+            TokenType::Case => "case",
+            TokenType::For => "for",
+            TokenType::Break => "break",
+            TokenType::Continue => "continue",
+            TokenType::Return => "return",
+            TokenType::And => "and",
+            TokenType::Or => "or",
+            TokenType::Print => "print",
+            TokenType::Eprint => "eprint",
+            _ => return None,
+        };
+        Some(keyword)
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub location: Location,
 }
+
+/// Shows where the token came from, e.g. `Identifier("x") @ 1:5`, so a failed
+/// `assert_eq!` on tokens points straight at the source
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} @ {}", self.token_type, self.location)
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at {}", self.token_type, self.location)
+    }
+}
@@ -1,29 +1,42 @@
+pub mod buildin;
 pub mod compiler;
 pub mod constant;
 pub mod error;
 pub mod lexer;
 pub mod location;
 pub mod parser;
+pub mod repl;
 pub mod treewalk;
+pub mod value;
 pub mod vm;
 
-use compiler::Compiler;
-use error::{Error, Result};
-use lexer::Lexer;
-use parser::Parser;
+use compiler::{Chunk, Compiler};
+use error::{Error, Result, Warning};
+use lexer::{Lexer, Token};
+use parser::{LocatedStmt, Parser};
+use repl::Repl;
 use treewalk::Interpreter;
 use vm::VM;
 
+use std::fmt;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
 
-pub fn run_file_with_tr(filename: &str) {
+/// With `call_main`, a zero-argument `main` is called after the script runs
+pub fn run_file_with_tr(filename: &str, call_main: bool) {
     let mut interpreter = Interpreter::new();
     match fs::read_to_string(filename) {
-        Ok(source) => match run_with_tr(source.to_string(), &mut interpreter) {
+        Ok(source) => match parse_source(&source, call_main).and_then(|(stmts, warnings)| {
+            for warning in warnings {
+                eprintln!("{filename}:{warning}");
+            }
+            interpreter.interpret(&stmts)
+        }) {
             Ok(_) => (),
             Err(error) => {
-                eprintln!("{}", error.in_file(filename.to_string()));
+                let error = error.in_file(filename.to_string());
+                eprintln!("{}", error.with_source(&source));
                 std::process::exit(1);
             }
         },
@@ -34,12 +47,28 @@ pub fn run_file_with_tr(filename: &str) {
     }
 }
 
-pub fn run_file_with_vm(filename: &str) {
+/// With `call_main`, a zero-argument `main` is called after the script runs;
+/// with `time`, how long each phase took is reported on stderr afterwards
+pub fn run_file_with_vm(filename: &str, call_main: bool, time: bool) {
+    if time {
+        return run_file_timed(filename, call_main);
+    }
+    let compile = if call_main {
+        compile_with_main
+    } else {
+        compile
+    };
     match fs::read_to_string(filename) {
-        Ok(source) => match run_with_vm(source) {
+        Ok(source) => match compile(&source).and_then(|chunk| {
+            for warning in chunk.warnings() {
+                eprintln!("{filename}:{warning}");
+            }
+            VM::new(chunk).run()
+        }) {
             Ok(_) => (),
             Err(error) => {
-                eprintln!("{}", error.in_file(filename.to_string()));
+                let error = error.in_file(filename.to_string());
+                eprintln!("{}", error.with_source(&source));
                 std::process::exit(1);
             }
         },
@@ -50,9 +79,33 @@ pub fn run_file_with_vm(filename: &str) {
     }
 }
 
+fn run_file_timed(filename: &str, call_main: bool) {
+    let source = match fs::read_to_string(filename) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("{}", Error::from(error));
+            std::process::exit(1);
+        }
+    };
+    let mut vm = VM::new(Chunk::new());
+    let result = run_with_vm_timed(&source, call_main, &mut vm);
+    for warning in vm.chunk().warnings() {
+        eprintln!("{filename}:{warning}");
+    }
+    match result {
+        Ok(times) => eprint!("{times}"),
+        Err(error) => {
+            let error = error.in_file(filename.to_string());
+            eprintln!("{}", error.with_source(&source));
+            std::process::exit(1);
+        }
+    }
+}
+
 pub fn run_prompt() {
     println!("Interactive Interpreter - Type 'exit' to quit");
-    let mut interpreter = Interpreter::new();
+    println!("Commands: :vm and :tr pick the backend, :ast and :bytecode dump the last input");
+    let mut repl = Repl::new();
     loop {
         print!("> ");
         io::stdout().flush().unwrap();
@@ -65,8 +118,9 @@ pub fn run_prompt() {
             break;
         }
 
-        match run_with_tr(input.to_string(), &mut interpreter) {
-            Ok(_) => (),
+        match repl.handle(input) {
+            Ok(Some(reply)) => println!("{reply}"),
+            Ok(None) => (),
             Err(error) => {
                 eprintln!("{}", error.in_file("<stdin>".to_string()));
             }
@@ -84,18 +138,64 @@ pub fn print_usage(program_name: &str) {
     Options:
       --tr      Use tree-walk interpreter
       --vm      Use bytecode VM (default)
+      --main    Call a zero-argument main() after the script runs
+      --time    Report how long each phase took on the VM
       --help    Display help information
     
     When no SCRIPT is provided, runs in interactive mode."
     );
 }
 
-pub fn run_with_tr(source: String, interpreter: &mut Interpreter) -> Result<()> {
-    let mut lexer = Lexer::new(source);
-    let tokens = lexer.tokenize()?;
+/// Lex `source` into tokens, ending with `Eof`
+pub fn tokenize(source: &str) -> Result<Vec<Token>> {
+    Lexer::new(source.to_string()).tokenize()
+}
 
-    let mut parser = Parser::new(tokens);
-    let stmts = parser.parse()?;
+/// Lex and parse `source` into top-level statements
+pub fn parse(source: &str) -> Result<Vec<LocatedStmt>> {
+    Parser::new(tokenize(source)?).parse()
+}
+
+/// Like `parse`, but ending with a call to a zero-argument `main` if the
+/// script declares one at the top level
+pub fn parse_with_main(source: &str) -> Result<Vec<LocatedStmt>> {
+    let mut stmts = parse(source)?;
+    parser::call_main(&mut stmts);
+    Ok(stmts)
+}
+
+/// Lex, parse and compile `source` into bytecode for the VM
+pub fn compile(source: &str) -> Result<Chunk> {
+    compile_source(source, false)
+}
+
+/// Like `compile`, but calling `main` as `parse_with_main` does
+pub fn compile_with_main(source: &str) -> Result<Chunk> {
+    compile_source(source, true)
+}
+
+/// Parse `source`, keeping the parser's warnings
+fn parse_source(source: &str, call_main: bool) -> Result<(Vec<LocatedStmt>, Vec<Warning>)> {
+    let mut parser = Parser::new(tokenize(source)?);
+    let mut stmts = parser.parse()?;
+    if call_main {
+        parser::call_main(&mut stmts);
+    }
+    Ok((stmts, parser.warnings().to_vec()))
+}
+
+/// Compile `source`, keeping the parser's warnings along with the compiler's
+fn compile_source(source: &str, call_main: bool) -> Result<Chunk> {
+    let (stmts, warnings) = parse_source(source, call_main)?;
+    let mut chunk = Compiler::new().compile(&stmts)?;
+    for warning in warnings {
+        chunk.add_warning(warning);
+    }
+    Ok(chunk)
+}
+
+pub fn run_with_tr(source: String, interpreter: &mut Interpreter) -> Result<()> {
+    let stmts = parse(&source)?;
 
     interpreter.interpret(&stmts)?;
     Ok(())
@@ -103,17 +203,81 @@ pub fn run_with_tr(source: String, interpreter: &mut Interpreter) -> Result<()>
 
 /// Run with bytecode VM (alternative execution method)
 pub fn run_with_vm(source: String) -> Result<()> {
-    let mut lexer = Lexer::new(source);
-    let tokens = lexer.tokenize()?;
+    let chunk = compile(&source)?;
+
+    let mut vm = VM::new(chunk);
+    vm.run()?;
+
+    Ok(())
+}
+
+/// Like `run_with_vm`, but reading the source from `reader`, such as piped
+/// stdin; the lexer needs the whole source, so it is read in full first
+pub fn run_reader_with_vm(mut reader: impl Read) -> Result<()> {
+    let mut source = String::new();
+    reader.read_to_string(&mut source)?;
+    run_with_vm(source)
+}
+
+/// How long each phase of running a script on the VM took
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTimes {
+    pub lex: Duration,
+    pub parse: Duration,
+    pub compile: Duration,
+    pub run: Duration,
+}
+
+impl PhaseTimes {
+    pub fn total(&self) -> Duration {
+        self.lex + self.parse + self.compile + self.run
+    }
+}
+
+/// One phase per line, then the total
+impl fmt::Display for PhaseTimes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (phase, time) in [
+            ("lex", self.lex),
+            ("parse", self.parse),
+            ("compile", self.compile),
+            ("run", self.run),
+            ("total", self.total()),
+        ] {
+            writeln!(f, "{phase:<8}{time:.3?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Like `run_with_vm`, but timing each phase and running on `vm`, whose
+/// chunk keeps the compiler's warnings once compilation succeeds
+pub fn run_with_vm_timed(source: &str, call_main: bool, vm: &mut VM) -> Result<PhaseTimes> {
+    let start = Instant::now();
+    let tokens = Lexer::new(source.to_string()).tokenize()?;
+    let lexed = Instant::now();
 
     let mut parser = Parser::new(tokens);
-    let stmts = parser.parse()?;
+    let mut stmts = parser.parse()?;
+    if call_main {
+        parser::call_main(&mut stmts);
+    }
+    let parsed = Instant::now();
 
-    let compiler = Compiler::new();
-    let chunk = compiler.compile(&stmts)?;
+    let mut chunk = Compiler::with_chunk(vm.chunk().clone()).compile(&stmts)?;
+    for warning in parser.warnings() {
+        chunk.add_warning(warning.clone());
+    }
+    vm.extend_chunk(chunk);
+    let compiled = Instant::now();
 
-    let mut vm = VM::new(chunk);
     vm.run()?;
+    let ran = Instant::now();
 
-    Ok(())
+    Ok(PhaseTimes {
+        lex: lexed - start,
+        parse: parsed - lexed,
+        compile: compiled - parsed,
+        run: ran - compiled,
+    })
 }
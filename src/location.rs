@@ -1,10 +1,19 @@
+use std::fmt;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Location {
     pub line: usize,
     pub column: usize,
+    /// Index into the source's chars, not its bytes
     pub offset: usize,
 }
 
+impl Default for Location {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Location {
     pub fn new() -> Self {
         Self {
@@ -15,7 +24,7 @@ impl Location {
     }
 
     pub fn advance(&mut self, ch: char) {
-        self.offset += ch.len_utf8();
+        self.offset += 1;
         if ch == '\n' {
             self.line += 1;
             self.column = 1;
@@ -25,6 +34,13 @@ impl Location {
     }
 }
 
+/// Formats as `line:column`
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 /// A wrapper that adds location information to any AST node
 #[derive(Debug, Clone, PartialEq)]
 pub struct Located<T> {
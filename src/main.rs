@@ -7,9 +7,23 @@ fn main() {
     match args.as_slice() {
         [_] => run_prompt(),
         [_, option] if option == "--help" => print_usage(&args[0]),
-        [_, filename] => run_file_with_vm(filename),
-        [_, filename, option] if option == "--tr" => run_file_with_tr(filename),
-        [_, filename, option] if option == "--vm" => run_file_with_vm(filename),
+        [_, filename, options @ ..] => {
+            let call_main = options.iter().any(|option| option == "--main");
+            let time = options.iter().any(|option| option == "--time");
+            let backends: Vec<_> = options
+                .iter()
+                .filter(|option| *option != "--main" && *option != "--time")
+                .collect();
+            match backends.as_slice() {
+                [] => run_file_with_vm(filename, call_main, time),
+                [option] if *option == "--tr" && !time => run_file_with_tr(filename, call_main),
+                [option] if *option == "--vm" => run_file_with_vm(filename, call_main, time),
+                _ => {
+                    print_usage(&args[0]);
+                    std::process::exit(1);
+                }
+            }
+        }
         _ => {
             print_usage(&args[0]);
             std::process::exit(1);
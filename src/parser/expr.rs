@@ -1,6 +1,8 @@
+use super::stmt::LocatedStmt;
 use crate::{
     error::{self, Error},
     lexer::TokenType,
+    location::Location,
 };
 use std::fmt;
 
@@ -15,15 +17,22 @@ pub enum Expr {
     Nil,
 
     // Expressions
+    /// `location` is the operator's, so runtime errors point at it
     Binary {
         left: Box<Expr>,
         operator: BinaryOp,
         right: Box<Expr>,
+        location: Location,
     },
     Unary {
         operator: UnaryOp,
         operand: Box<Expr>,
     },
+    /// `a < b <= c` evaluates every operand once, as `a < b and b <= c`
+    ChainedComparison {
+        operands: Vec<Expr>,
+        operators: Vec<BinaryOp>,
+    },
     Assign {
         name: String,
         value: Box<Expr>,
@@ -37,10 +46,31 @@ pub enum Expr {
         array: Box<Expr>,
         index: Box<Expr>,
     },
+    /// `array[start:end]`, where a missing bound means the start or end of
+    /// the array
+    Slice {
+        array: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+    },
+    /// `array[start:end] = value` replaces the slice with the elements of
+    /// `value`, which need not be as many
+    SliceAssign {
+        array: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+        value: Box<Expr>,
+    },
+    /// `location` is where the callee starts, so errors raised by the call,
+    /// such as a failed `assert`, point at it
     Call {
         callee: Box<Expr>,
         arguments: Vec<Expr>,
+        location: Location,
     },
+    /// `loop { ... }` runs its body until a `break`, and evaluates to the
+    /// value the `break` carries, or nil
+    Loop(Vec<LocatedStmt>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -138,12 +168,22 @@ pub trait Visitor<T> {
     fn visit_nil(&mut self) -> T;
     fn visit_identifier(&mut self, name: &str) -> T;
     fn visit_array(&mut self, elements: &[Expr]) -> T;
-    fn visit_binary(&mut self, left: &Expr, op: &BinaryOp, right: &Expr) -> T;
+    fn visit_binary(&mut self, left: &Expr, op: &BinaryOp, right: &Expr, location: Location) -> T;
     fn visit_unary(&mut self, op: &UnaryOp, operand: &Expr) -> T;
+    fn visit_chained_comparison(&mut self, operands: &[Expr], operators: &[BinaryOp]) -> T;
     fn visit_assign(&mut self, name: &str, value: &Expr) -> T;
     fn visit_index_assign(&mut self, array: &Expr, index: &Expr, value: &Expr) -> T;
     fn visit_index(&mut self, array: &Expr, index: &Expr) -> T;
-    fn visit_call(&mut self, callee: &Expr, arguments: &[Expr]) -> T;
+    fn visit_slice(&mut self, array: &Expr, start: Option<&Expr>, end: Option<&Expr>) -> T;
+    fn visit_slice_assign(
+        &mut self,
+        array: &Expr,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+        value: &Expr,
+    ) -> T;
+    fn visit_call(&mut self, callee: &Expr, arguments: &[Expr], location: Location) -> T;
+    fn visit_loop(&mut self, body: &[LocatedStmt]) -> T;
 }
 
 impl Expr {
@@ -159,8 +199,13 @@ impl Expr {
                 left,
                 operator,
                 right,
-            } => visitor.visit_binary(left, operator, right),
+                location,
+            } => visitor.visit_binary(left, operator, right, *location),
             Expr::Unary { operator, operand } => visitor.visit_unary(operator, operand),
+            Expr::ChainedComparison {
+                operands,
+                operators,
+            } => visitor.visit_chained_comparison(operands, operators),
             Expr::Assign { name, value } => visitor.visit_assign(name, value),
             Expr::IndexAssign {
                 array,
@@ -168,7 +213,21 @@ impl Expr {
                 value,
             } => visitor.visit_index_assign(array, index, value),
             Expr::Index { array, index } => visitor.visit_index(array, index),
-            Expr::Call { callee, arguments } => visitor.visit_call(callee, arguments),
+            Expr::Slice { array, start, end } => {
+                visitor.visit_slice(array, start.as_deref(), end.as_deref())
+            }
+            Expr::SliceAssign {
+                array,
+                start,
+                end,
+                value,
+            } => visitor.visit_slice_assign(array, start.as_deref(), end.as_deref(), value),
+            Expr::Call {
+                callee,
+                arguments,
+                location,
+            } => visitor.visit_call(callee, arguments, *location),
+            Expr::Loop(body) => visitor.visit_loop(body),
         }
     }
 }
@@ -1,7 +1,8 @@
 pub mod expr;
+#[allow(clippy::module_inception)]
 pub mod parser;
 pub mod stmt;
 
 pub use expr::{BinaryOp, Expr, UnaryOp};
-pub use parser::{LocatedStmt, Parser};
-pub use stmt::Stmt;
+pub use parser::{call_main, hoist_functions, Parser};
+pub use stmt::{LocatedStmt, Pattern, PrintArgs, Stmt, SwitchCase};
@@ -1,45 +1,154 @@
 use super::{
     expr::{BinaryOp, Expr, UnaryOp},
-    stmt::Stmt,
+    stmt::{LocatedStmt, Pattern, PrintArgs, Stmt, SwitchCase},
 };
 use crate::{
-    error::{Error, Result},
+    constant::{MAX_CHAIN_LENGTH, MAX_NESTING_DEPTH},
+    error::{Error, Result, Warning},
     lexer::token::{Token, TokenType},
-    location::Located,
+    location::{Located, Location},
 };
+use std::collections::{HashMap, HashSet};
 
-pub type LocatedStmt = Located<Stmt>;
+/// Order top-level statements so function declarations come first, letting
+/// a script call a function before its definition; `Parser::parse` rejects
+/// an `fn` whose name an earlier top-level declaration took, which hoisting
+/// would otherwise make the one to lose
+pub fn hoist_functions(stmts: &[LocatedStmt]) -> impl Iterator<Item = &LocatedStmt> {
+    let is_func = |stmt: &&LocatedStmt| matches!(stmt.as_inner(), Stmt::FuncDecl { .. });
+    stmts
+        .iter()
+        .filter(is_func)
+        .chain(stmts.iter().filter(move |stmt| !is_func(stmt)))
+}
+
+/// Call a top-level `main` taking no arguments once the rest of the script
+/// has run, for scripts opting in to an entry point; does nothing without one
+pub fn call_main(stmts: &mut Vec<LocatedStmt>) {
+    let main = stmts.iter().find(|stmt| {
+        matches!(stmt.as_inner(), Stmt::FuncDecl { name, params, .. }
+            if name == "main" && params.is_empty())
+    });
+    if let Some(main) = main {
+        let location = main.location();
+        let call = Expr::Call {
+            callee: Box::new(Expr::Variable("main".to_string())),
+            arguments: Vec::new(),
+            location,
+        };
+        stmts.push(Located::new(Stmt::Expression(call), location));
+    }
+}
+
+/// Add the names `stmt` declares at the top level to `declared`, failing
+/// for a function whose name is already there
+fn check_top_level_names(stmt: &LocatedStmt, declared: &mut HashSet<String>) -> Result<()> {
+    match stmt.as_inner() {
+        Stmt::FuncDecl { name, .. } if !declared.insert(name.clone()) => {
+            return Err(Error::syntax(
+                format!("'{name}' is already declared; top-level functions cannot be redefined"),
+                stmt.location(),
+            ));
+        }
+        Stmt::VarDecl { name, .. } => {
+            declared.insert(name.clone());
+        }
+        Stmt::MultiVarDecl(decls) => declared.extend(decls.iter().map(|(name, _)| name.clone())),
+        Stmt::DestructureDecl { names, .. } => declared.extend(names.iter().cloned()),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Drop the doc comment tokens, keeping the text of those right before an
+/// `fn`, keyed by the index of that `fn` among the remaining tokens
+fn take_doc_comments(tokens: Vec<Token>) -> (Vec<Token>, HashMap<usize, String>) {
+    let mut kept = Vec::with_capacity(tokens.len());
+    let mut docs = HashMap::new();
+    let mut lines = Vec::new();
+    for token in tokens {
+        match token.token_type {
+            TokenType::DocComment(line) => {
+                lines.push(line);
+                continue;
+            }
+            TokenType::Fn if !lines.is_empty() => {
+                docs.insert(kept.len(), lines.join("\n"));
+            }
+            _ => {}
+        }
+        lines.clear();
+        kept.push(token);
+    }
+    (kept, docs)
+}
+
+/// How many binary operators lie along the left edge of `expr`
+fn left_spine_length(mut expr: &Expr) -> usize {
+    let mut length = 0;
+    while let Expr::Binary { left, .. } = expr {
+        length += 1;
+        expr = left;
+    }
+    length
+}
 
 pub struct Parser {
     tokens: Vec<Token>,
+    docs: HashMap<usize, String>,
     current: usize,
     loop_depth: usize,     // Track if we're inside a loop
+    value_loop: bool,      // Track if the innermost loop is a `loop`, so `break` may carry a value
+    switch_depth: usize,   // Track if we're inside a `switch`, which `break` can leave
     function_depth: usize, // Track if we're inside a function
+    nesting_depth: usize,  // Track how deep the tree being built is
+    chain_length: usize,   // Track how long the chain of operators being built is
+    warnings: Vec<Warning>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
+        let (tokens, docs) = take_doc_comments(tokens);
         Self {
             tokens,
+            docs,
             current: 0,
             loop_depth: 0,
+            value_loop: false,
+            switch_depth: 0,
             function_depth: 0,
+            nesting_depth: 0,
+            chain_length: 0,
+            warnings: Vec::new(),
         }
     }
 
+    /// Suspicious but valid code seen so far
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
     pub fn parse(&mut self) -> Result<Vec<LocatedStmt>> {
         let mut statements = Vec::new();
 
+        let mut declared = HashSet::new();
         while !self.is_at_end() {
-            statements.push(self.located_stmt()?);
+            let stmt = self.located_stmt()?;
+            check_top_level_names(&stmt, &mut declared)?;
+            statements.push(stmt);
         }
 
         Ok(statements)
     }
 
     fn located_stmt(&mut self) -> Result<LocatedStmt> {
+        self.nested(|parser| parser.located(Self::stmt))
+    }
+
+    /// Run `parse`, tagging the statement with where it starts
+    fn located(&mut self, parse: fn(&mut Self) -> Result<Stmt>) -> Result<LocatedStmt> {
         let start_location = self.peek().location;
-        let stmt = self.stmt()?;
+        let stmt = parse(self)?;
         Ok(Located::new(stmt, start_location))
     }
 
@@ -49,29 +158,70 @@ impl Parser {
             TokenType::Fn => self.func_decl(),
             TokenType::If => self.if_stmt(),
             TokenType::While => self.while_stmt(),
+            TokenType::Repeat => self.repeat_stmt(),
+            TokenType::Switch => self.switch_stmt(),
             TokenType::For => self.for_stmt(),
             TokenType::Return => self.return_stmt(),
             TokenType::LeftBrace => self.block_stmt(),
             TokenType::Print => self.print_stmt(),
+            TokenType::Eprint => self.eprint_stmt(),
             TokenType::Break => self.break_stmt(),
             TokenType::Continue => self.continue_stmt(),
+            TokenType::Loop => self.loop_expr().map(Stmt::Expression),
+            TokenType::Semicolon => {
+                self.advance();
+                Ok(Stmt::Empty)
+            }
             _ => self.expr_stmt(),
         }
     }
 
     fn var_decl(&mut self) -> Result<Stmt> {
         self.advance();
+        if self.try_consume(TokenType::LeftParen).is_some() {
+            return self.destructure_decl();
+        }
+        let mut decls = vec![self.single_var_decl()?];
+        while self.try_consume(TokenType::Comma).is_some() {
+            decls.push(self.single_var_decl()?);
+        }
+        self.consume_semicolon()?;
+
+        if decls.len() == 1 {
+            let (name, initializer) = decls.remove(0);
+            return Ok(Stmt::VarDecl { name, initializer });
+        }
+        Ok(Stmt::MultiVarDecl(decls))
+    }
+
+    /// One `name` or `name = expr` of a `let` statement
+    fn single_var_decl(&mut self) -> Result<(String, Option<Expr>)> {
         let name = self.consume_identifier()?;
         let initializer = self
             .try_consume(TokenType::Equal)
             .is_some()
-            .then(|| self.expr())
+            .then(|| match self.peek().token_type {
+                TokenType::Loop => self.loop_expr(),
+                _ => self.expr(),
+            })
             .transpose()?;
+        Ok((name, initializer))
+    }
+
+    fn destructure_decl(&mut self) -> Result<Stmt> {
+        let names = self.parameters()?;
+        self.consume(TokenType::RightParen, "expected ')' after names")?;
+        self.consume(
+            TokenType::Equal,
+            "expected '=' after names, destructuring needs an initializer",
+        )?;
+        let initializer = self.expr()?;
         self.consume_semicolon()?;
-        Ok(Stmt::VarDecl { name, initializer })
+        Ok(Stmt::DestructureDecl { names, initializer })
     }
 
     fn func_decl(&mut self) -> Result<Stmt> {
+        let doc = self.docs.remove(&self.current);
         self.advance();
         let name = self.consume_identifier()?;
 
@@ -79,25 +229,43 @@ impl Parser {
         let params = self.parameters()?;
         self.consume(TokenType::RightParen, "expected ')' after parameters")?;
 
-        // Enter function scope
+        // Enter function scope, where loops around the declaration do not count
         self.function_depth += 1;
+        let value_loop = std::mem::take(&mut self.value_loop);
+        let switch_depth = std::mem::take(&mut self.switch_depth);
         let body = self.block()?;
+        self.value_loop = value_loop;
+        self.switch_depth = switch_depth;
         self.function_depth -= 1;
-        Ok(Stmt::FuncDecl { name, params, body })
+        Ok(Stmt::FuncDecl {
+            name,
+            params,
+            body,
+            doc,
+        })
     }
 
+    /// Also parses the rest of an `elif` ladder, as `elif` is `else if`
     fn if_stmt(&mut self) -> Result<Stmt> {
         self.advance();
-        let condition = self.expr()?;
-        let then_branch = Box::new(self.block_stmt()?);
+        let condition = self.condition()?;
+        self.check_unbraced_body()?;
+        let then_branch = Box::new(self.body_stmt()?);
+        if self.check(&TokenType::Elif) {
+            return Ok(Stmt::If {
+                condition,
+                then_branch,
+                else_branch: Some(Box::new(self.else_if()?)),
+            });
+        }
         let else_branch = self
             .try_consume(TokenType::Else)
             .is_some()
             .then(|| {
                 if self.check(&TokenType::If) {
-                    self.if_stmt()
+                    self.else_if()
                 } else {
-                    self.block_stmt()
+                    self.body_stmt()
                 }
             })
             .transpose()?
@@ -110,44 +278,99 @@ impl Parser {
         })
     }
 
+    /// The `if` an `elif` or `else if` chains on, which the backends visit
+    /// recursively, so each link counts as a level of nesting
+    fn else_if(&mut self) -> Result<LocatedStmt> {
+        self.nested(|parser| parser.located(Self::if_stmt))
+    }
+
     fn while_stmt(&mut self) -> Result<Stmt> {
         self.advance();
-        let condition = self.expr()?;
+        if self.try_consume(TokenType::Let).is_some() {
+            return self.while_let_stmt();
+        }
+        let condition = self.condition()?;
+        self.check_unbraced_body()?;
 
-        // Enter loop scope
-        self.loop_depth += 1;
-        let body = Box::new(self.block_stmt()?);
-        self.loop_depth -= 1;
+        let body = Box::new(self.loop_body(false, Self::body_stmt)?);
 
         Ok(Stmt::While { condition, body })
     }
 
+    /// The condition of an `if` or `while`, warning when it is an
+    /// assignment that is not wrapped in parentheses, as `==` was likely
+    /// meant
+    fn condition(&mut self) -> Result<Expr> {
+        let start = self.peek().location;
+        let opens_with_paren = self.check(&TokenType::LeftParen);
+        let condition = self.expr()?;
+        let closes_with_paren = self.tokens[self.current - 1].token_type == TokenType::RightParen;
+        if matches!(
+            condition,
+            Expr::Assign { .. } | Expr::IndexAssign { .. } | Expr::SliceAssign { .. }
+        ) && !(opens_with_paren && closes_with_paren)
+        {
+            self.warnings.push(Warning::new(
+                "assignment used as a condition; use '==' to compare, or wrap it in parentheses"
+                    .to_string(),
+                start,
+            ));
+        }
+        Ok(condition)
+    }
+
+    fn while_let_stmt(&mut self) -> Result<Stmt> {
+        let name = self.consume_identifier()?;
+        self.consume(TokenType::Equal, "expected '=' after name in 'while let'")?;
+        let initializer = self.expr()?;
+        self.check_unbraced_body()?;
+
+        let body = Box::new(self.loop_body(false, Self::body_stmt)?);
+
+        Ok(Stmt::WhileLet {
+            name,
+            initializer,
+            body,
+        })
+    }
+
+    fn repeat_stmt(&mut self) -> Result<Stmt> {
+        self.advance();
+        let count = self.expr()?;
+        self.check_unbraced_body()?;
+
+        let body = Box::new(self.loop_body(false, Self::body_stmt)?);
+
+        Ok(Stmt::Repeat { count, body })
+    }
+
     fn for_stmt(&mut self) -> Result<Stmt> {
         self.advance();
+        if let Some(pattern) = self.for_in_pattern() {
+            return self.for_in_stmt(pattern);
+        }
         let initializer = match self.peek().token_type {
-            TokenType::Let => Some(self.var_decl()?),
+            TokenType::Let => Some(self.located(Self::var_decl)?),
             TokenType::Semicolon => {
                 self.advance();
                 None
             }
-            _ => Some(self.expr_stmt()?),
+            _ => Some(self.located(Self::expr_stmt)?),
         }
         .map(Box::new);
 
         let condition = (!self.check(&TokenType::Semicolon))
             .then(|| self.expr())
             .transpose()?
-            .unwrap_or_else(|| Expr::Boolean(true));
+            .unwrap_or(Expr::Boolean(true));
         self.consume(TokenType::Semicolon, "expect ';' after loop condition")?;
 
-        let increment = (!self.check(&TokenType::RightParen))
+        let increment = (!self.check(&TokenType::LeftBrace))
             .then(|| self.expr())
             .transpose()?;
+        self.check_unbraced_body()?;
 
-        // Enter loop scope
-        self.loop_depth += 1;
-        let body = Box::new(self.block_stmt()?);
-        self.loop_depth -= 1;
+        let body = Box::new(self.loop_body(false, Self::body_stmt)?);
 
         Ok(Stmt::For {
             initializer,
@@ -157,6 +380,48 @@ impl Parser {
         })
     }
 
+    /// The pattern of a `for`-`in`, consuming it and the `in` after it; `in`
+    /// is not a keyword, so anything else is left for a C-style `for`
+    fn for_in_pattern(&mut self) -> Option<Pattern> {
+        let start = self.current;
+        let pattern = self.pattern().ok();
+        let is_in = matches!(&self.peek().token_type, TokenType::Identifier(name) if name == "in");
+        match pattern {
+            Some(pattern) if is_in => {
+                self.advance();
+                Some(pattern)
+            }
+            _ => {
+                self.current = start;
+                None
+            }
+        }
+    }
+
+    /// `name` or `(pattern, ...)`
+    fn pattern(&mut self) -> Result<Pattern> {
+        if self.try_consume(TokenType::LeftParen).is_none() {
+            return self.consume_identifier().map(Pattern::Name);
+        }
+        let mut patterns = vec![self.nested(Self::pattern)?];
+        while self.try_consume(TokenType::Comma).is_some() {
+            patterns.push(self.nested(Self::pattern)?);
+        }
+        self.consume(TokenType::RightParen, "expected ')' after names")?;
+        Ok(Pattern::Tuple(patterns))
+    }
+
+    fn for_in_stmt(&mut self, pattern: Pattern) -> Result<Stmt> {
+        let iterable = self.expr()?;
+        self.check_unbraced_body()?;
+        let body = Box::new(self.loop_body(false, Self::body_stmt)?);
+        Ok(Stmt::ForIn {
+            pattern,
+            iterable,
+            body,
+        })
+    }
+
     fn return_stmt(&mut self) -> Result<Stmt> {
         if self.function_depth == 0 {
             let return_token = self.peek();
@@ -166,24 +431,95 @@ impl Parser {
             ));
         }
         self.advance();
-        let value = (!self.check(&TokenType::Semicolon))
+        let value = (!self.check(&TokenType::Semicolon) && !self.check(&TokenType::RightBrace))
             .then(|| self.expr())
             .transpose()?;
-        self.consume_semicolon()?;
+        // the ';' may be omitted when the return closes the block
+        if !self.check(&TokenType::RightBrace) {
+            self.consume_semicolon()?;
+        }
         Ok(Stmt::Return { value })
     }
 
+    /// `switch subject { case value: ... default: ... }`; `default` is a
+    /// builtin rather than a keyword, so it is a label only when followed
+    /// by ':'
+    fn switch_stmt(&mut self) -> Result<Stmt> {
+        self.advance();
+        let subject = self.expr()?;
+        let start = self
+            .consume(TokenType::LeftBrace, "expected '{' after switch subject")?
+            .location;
+
+        let enclosing = std::mem::replace(&mut self.value_loop, false);
+        self.switch_depth += 1;
+        let mut cases: Vec<SwitchCase> = Vec::new();
+        while !self.check(&TokenType::RightBrace) {
+            if self.is_at_end() {
+                return Err(
+                    Error::syntax("unterminated switch, expected '}'".to_string(), start)
+                        .spanning(self.peek().location),
+                );
+            }
+            let value = if self.try_consume(TokenType::Case).is_some() {
+                Some(self.expr()?)
+            } else if self.at_default_label() {
+                if cases.iter().any(|case| case.value.is_none()) {
+                    return Err(self.error("a 'switch' can only have one 'default'".to_string()));
+                }
+                self.advance();
+                None
+            } else {
+                return Err(self.error("expected 'case' or 'default' in 'switch'".to_string()));
+            };
+            self.consume(TokenType::Colon, "expected ':' after case label")?;
+
+            let mut body = Vec::new();
+            while !self.check(&TokenType::Case)
+                && !self.at_default_label()
+                && !self.check(&TokenType::RightBrace)
+                && !self.is_at_end()
+            {
+                body.push(self.located_stmt()?);
+            }
+            cases.push(SwitchCase { value, body });
+        }
+        self.advance();
+        self.switch_depth -= 1;
+        self.value_loop = enclosing;
+
+        Ok(Stmt::Switch { subject, cases })
+    }
+
+    /// Whether the next tokens are `default :`
+    fn at_default_label(&self) -> bool {
+        matches!(&self.peek().token_type, TokenType::Identifier(name) if name == "default")
+            && self
+                .tokens
+                .get(self.current + 1)
+                .is_some_and(|next| next.token_type == TokenType::Colon)
+    }
+
     fn break_stmt(&mut self) -> Result<Stmt> {
-        if self.loop_depth == 0 {
+        if self.loop_depth == 0 && self.switch_depth == 0 {
             let break_token = self.peek();
             return Err(Error::syntax(
-                "'break' statement must be inside a loop".to_string(),
+                "'break' statement must be inside a loop or switch".to_string(),
                 break_token.location,
             ));
         }
-        self.advance();
+        let location = self.advance().location;
+        let value = (!self.check(&TokenType::Semicolon))
+            .then(|| self.expr())
+            .transpose()?;
+        if value.is_some() && !self.value_loop {
+            return Err(Error::syntax(
+                "only 'break' in a 'loop' can carry a value".to_string(),
+                location,
+            ));
+        }
         self.consume_semicolon()?;
-        Ok(Stmt::Break)
+        Ok(Stmt::Break { value })
     }
 
     fn continue_stmt(&mut self) -> Result<Stmt> {
@@ -199,26 +535,147 @@ impl Parser {
         Ok(Stmt::Continue)
     }
 
+    /// `loop { ... }`, allowed only to start a statement or as a `let`
+    /// initializer: the VM keeps the loop's value in a local, so there must
+    /// be nothing else above the locals on the stack
+    fn loop_expr(&mut self) -> Result<Expr> {
+        self.advance();
+        let body = self.loop_body(true, Self::block)?;
+        Ok(Expr::Loop(body))
+    }
+
+    /// Parse the body of a loop with `parse`, where `value_loop` is whether
+    /// the loop is a `loop`
+    fn loop_body<T>(&mut self, value_loop: bool, parse: fn(&mut Self) -> Result<T>) -> Result<T> {
+        let enclosing = std::mem::replace(&mut self.value_loop, value_loop);
+        self.loop_depth += 1;
+        let body = parse(self)?;
+        self.loop_depth -= 1;
+        self.value_loop = enclosing;
+        Ok(body)
+    }
+
+    /// A body without braces must start with `{`, a keyword or a name: the
+    /// header expression before it would have taken anything else, as
+    /// `if f (g)();` takes `f(g)()` for its condition and `if (x) -1;` takes
+    /// `(x) - 1`
+    fn check_unbraced_body(&self) -> Result<()> {
+        match self.peek().token_type {
+            TokenType::LeftBrace
+            | TokenType::Identifier(_)
+            | TokenType::Print
+            | TokenType::Eprint
+            | TokenType::Return
+            | TokenType::Break
+            | TokenType::Continue
+            | TokenType::If
+            | TokenType::While
+            | TokenType::For
+            | TokenType::Repeat
+            | TokenType::Switch
+            | TokenType::Loop
+            | TokenType::Let
+            | TokenType::Fn => Ok(()),
+            _ => {
+                Err(self
+                    .error("a body without braces must start with a keyword or a name".to_string()))
+            }
+        }
+    }
+
+    /// The body of `if`, `else` or a loop: a block, or a single statement
+    /// without braces
+    fn body_stmt(&mut self) -> Result<LocatedStmt> {
+        match self.peek().token_type {
+            TokenType::LeftBrace => self.located(Self::block_stmt),
+            TokenType::Let | TokenType::Fn => Err(Error::syntax(
+                "declaration must be inside a block".to_string(),
+                self.peek().location,
+            )),
+            _ => self.located_stmt(),
+        }
+    }
+
     fn block_stmt(&mut self) -> Result<Stmt> {
         let statements = self.block()?;
         Ok(Stmt::Block(statements))
     }
 
-    fn block(&mut self) -> Result<Vec<Stmt>> {
-        self.consume(TokenType::LeftBrace, "expected '{' at start of block")?;
+    fn block(&mut self) -> Result<Vec<LocatedStmt>> {
+        let start = self
+            .consume(TokenType::LeftBrace, "expected '{' at start of block")?
+            .location;
         let mut statements = Vec::new();
         while !self.check(&TokenType::RightBrace) {
-            statements.push(self.stmt()?);
+            if self.is_at_end() {
+                return Err(
+                    Error::syntax("unterminated block, expected '}'".to_string(), start)
+                        .spanning(self.peek().location),
+                );
+            }
+            statements.push(self.located_stmt()?);
         }
         self.consume(TokenType::RightBrace, "expected '}' at end of block")?;
         Ok(statements)
     }
 
     fn print_stmt(&mut self) -> Result<Stmt> {
+        Ok(Stmt::Print(self.print_arguments()?))
+    }
+
+    fn eprint_stmt(&mut self) -> Result<Stmt> {
+        Ok(Stmt::Eprint(self.print_arguments()?))
+    }
+
+    /// The values to print, optionally followed by `sep = expr` and
+    /// `end = expr`; before any value, `end = x` is an assignment to print
+    fn print_arguments(&mut self) -> Result<PrintArgs> {
         self.advance();
-        let exprs = self.arguments()?;
+        let mut args = PrintArgs::new(Vec::new());
+        loop {
+            let option = (!args.values.is_empty())
+                .then(|| self.print_option())
+                .flatten();
+            if let Some(option) = option {
+                let location = self.peek().location;
+                self.advance();
+                self.advance();
+                let value = self.expr()?;
+                let slot = if option == "sep" {
+                    &mut args.sep
+                } else {
+                    &mut args.end
+                };
+                if slot.replace(value).is_some() {
+                    return Err(Error::syntax(
+                        format!("duplicate print option '{option}'"),
+                        location,
+                    ));
+                }
+            } else if args.sep.is_some() || args.end.is_some() {
+                return Err(
+                    self.error("values to print must come before 'sep' and 'end'".to_string())
+                );
+            } else {
+                args.values.push(self.expr()?);
+            }
+            if self.try_consume(TokenType::Comma).is_none() {
+                break;
+            }
+        }
         self.consume_semicolon()?;
-        Ok(Stmt::Print(exprs))
+        Ok(args)
+    }
+
+    /// `sep` or `end` when the next tokens are `sep =` or `end =`
+    fn print_option(&self) -> Option<&'static str> {
+        let option = match &self.peek().token_type {
+            TokenType::Identifier(name) if name == "sep" => "sep",
+            TokenType::Identifier(name) if name == "end" => "end",
+            _ => return None,
+        };
+        let next = self.tokens.get(self.current + 1)?;
+        (next.token_type == TokenType::Equal).then_some(option)
     }
 
     fn expr_stmt(&mut self) -> Result<Stmt> {
@@ -228,7 +685,7 @@ impl Parser {
     }
 
     fn expr(&mut self) -> Result<Expr> {
-        self.assignment()
+        self.nested(Self::assignment)
     }
 
     fn assignment(&mut self) -> Result<Expr> {
@@ -240,102 +697,47 @@ impl Parser {
             TokenType::StarEqual,
             TokenType::SlashEqual,
         ]) {
+            let op_type = token.token_type.clone();
+            let location = token.location;
             match expr {
                 Expr::Variable(name) => {
-                    let op_type = token.token_type.clone();
-                    let value = self.assignment()?;
-                    expr = match op_type {
-                        TokenType::Equal => Expr::Assign {
-                            name,
-                            value: Box::new(value),
-                        },
-                        TokenType::PlusEqual => Expr::Assign {
-                            name: name.clone(),
-                            value: Box::new(Expr::Binary {
-                                left: Box::new(Expr::Variable(name)),
-                                operator: BinaryOp::Add,
-                                right: Box::new(value),
-                            }),
-                        },
-                        TokenType::MinusEqual => Expr::Assign {
-                            name: name.clone(),
-                            value: Box::new(Expr::Binary {
-                                left: Box::new(Expr::Variable(name)),
-                                operator: BinaryOp::Subtract,
-                                right: Box::new(value),
-                            }),
-                        },
-                        TokenType::StarEqual => Expr::Assign {
-                            name: name.clone(),
-                            value: Box::new(Expr::Binary {
-                                left: Box::new(Expr::Variable(name)),
-                                operator: BinaryOp::Multiply,
-                                right: Box::new(value),
-                            }),
-                        },
-                        TokenType::SlashEqual => Expr::Assign {
-                            name: name.clone(),
-                            value: Box::new(Expr::Binary {
-                                left: Box::new(Expr::Variable(name)),
-                                operator: BinaryOp::Divide,
-                                right: Box::new(value),
-                            }),
-                        },
-                        _ => unreachable!(),
-                    }
+                    let target = Expr::Variable(name.clone());
+                    let value = self.assigned_value(target, op_type, location)?;
+                    expr = Expr::Assign {
+                        name,
+                        value: Box::new(value),
+                    };
                 }
                 Expr::Index { array, index } => {
-                    let op_type = token.token_type.clone();
-                    let value = self.assignment()?;
-                    expr = match op_type {
-                        TokenType::Equal => Expr::IndexAssign {
-                            array,
-                            index,
-                            value: Box::new(value),
-                        },
-                        TokenType::PlusEqual => Expr::IndexAssign {
-                            array: array.clone(),
-                            index: index.clone(),
-                            value: Box::new(Expr::Binary {
-                                left: Box::new(Expr::Index { array, index }),
-                                operator: BinaryOp::Add,
-                                right: Box::new(value),
-                            }),
-                        },
-                        TokenType::MinusEqual => Expr::IndexAssign {
-                            array: array.clone(),
-                            index: index.clone(),
-                            value: Box::new(Expr::Binary {
-                                left: Box::new(Expr::Index { array, index }),
-                                operator: BinaryOp::Subtract,
-                                right: Box::new(value),
-                            }),
-                        },
-                        TokenType::StarEqual => Expr::IndexAssign {
-                            array: array.clone(),
-                            index: index.clone(),
-                            value: Box::new(Expr::Binary {
-                                left: Box::new(Expr::Index { array, index }),
-                                operator: BinaryOp::Multiply,
-                                right: Box::new(value),
-                            }),
-                        },
-                        TokenType::SlashEqual => Expr::IndexAssign {
-                            array: array.clone(),
-                            index: index.clone(),
-                            value: Box::new(Expr::Binary {
-                                left: Box::new(Expr::Index { array, index }),
-                                operator: BinaryOp::Divide,
-                                right: Box::new(value),
-                            }),
-                        },
-                        _ => unreachable!(),
-                    }
+                    let target = Expr::Index {
+                        array: array.clone(),
+                        index: index.clone(),
+                    };
+                    let value = self.assigned_value(target, op_type, location)?;
+                    expr = Expr::IndexAssign {
+                        array,
+                        index,
+                        value: Box::new(value),
+                    };
+                }
+                Expr::Slice { array, start, end } => {
+                    let target = Expr::Slice {
+                        array: array.clone(),
+                        start: start.clone(),
+                        end: end.clone(),
+                    };
+                    let value = self.assigned_value(target, op_type, location)?;
+                    expr = Expr::SliceAssign {
+                        array,
+                        start,
+                        end,
+                        value: Box::new(value),
+                    };
                 }
                 _ => {
                     return Err(Error::syntax(
                         "invalid assignment target".to_string(),
-                        token.location,
+                        location,
                     ))
                 }
             }
@@ -344,6 +746,47 @@ impl Parser {
         Ok(expr)
     }
 
+    /// Parse the right-hand side of an assignment, desugaring `x op= v`
+    /// into `x op v`
+    fn assigned_value(
+        &mut self,
+        target: Expr,
+        op_type: TokenType,
+        location: Location,
+    ) -> Result<Expr> {
+        let value = self.nested(Self::assignment)?;
+        let operator = match op_type {
+            TokenType::Equal => return Ok(value),
+            TokenType::PlusEqual => BinaryOp::Add,
+            TokenType::MinusEqual => BinaryOp::Subtract,
+            TokenType::StarEqual => BinaryOp::Multiply,
+            TokenType::SlashEqual => BinaryOp::Divide,
+            _ => unreachable!(),
+        };
+        Ok(Expr::Binary {
+            left: Box::new(target),
+            operator,
+            right: Box::new(value),
+            location,
+        })
+    }
+
+    /// Warn once when an operand of `and`, `or` or `not` is a number or
+    /// string literal, whose truthiness never changes
+    fn check_logical_operands(&mut self, operands: &[&Expr], location: Location) {
+        let Some((kind, comparison)) = operands.iter().find_map(|operand| match operand {
+            Expr::Number(_) => Some(("number", "x != 0")),
+            Expr::String(_) => Some(("string", "s != \"\"")),
+            _ => None,
+        }) else {
+            return;
+        };
+        self.warnings.push(Warning::new(
+            format!("{kind} literal used as a boolean; compare explicitly, as in '{comparison}'"),
+            location,
+        ));
+    }
+
     fn or(&mut self) -> Result<Expr> {
         self.binary(&[TokenType::Or], Self::and)
     }
@@ -360,15 +803,41 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> Result<Expr> {
-        self.binary(
-            &[
-                TokenType::LessThan,
-                TokenType::LessEqual,
-                TokenType::GreaterThan,
-                TokenType::GreaterEqual,
-            ],
-            Self::term,
-        )
+        let ops = [
+            TokenType::LessThan,
+            TokenType::LessEqual,
+            TokenType::GreaterThan,
+            TokenType::GreaterEqual,
+        ];
+
+        let mut operands = vec![self.term()?];
+        let mut operators = Vec::new();
+        let mut location = self.peek().location;
+        while let Some(token) = self.try_consume_any(&ops) {
+            location = token.location;
+            operators.push(BinaryOp::try_from(token.token_type.clone())?);
+            operands.push(self.term()?);
+        }
+
+        // a single comparison stays a plain binary expression
+        if operators.len() < 2 {
+            let mut operands = operands.into_iter();
+            let left = operands.next().unwrap();
+            return Ok(match (operators.pop(), operands.next()) {
+                (Some(operator), Some(right)) => Expr::Binary {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                    location,
+                },
+                _ => left,
+            });
+        }
+
+        Ok(Expr::ChainedComparison {
+            operands,
+            operators,
+        })
     }
 
     fn term(&mut self) -> Result<Expr> {
@@ -379,27 +848,53 @@ impl Parser {
         self.binary(&[TokenType::Slash, TokenType::Star], Self::unary)
     }
 
+    /// A chain only nests along its left spine, which the backends walk in
+    /// a loop, so its operators count towards `chain_length` rather than
+    /// the nesting depth
     fn binary<F>(&mut self, ops: &[TokenType], mut next_level: F) -> Result<Expr>
     where
         F: FnMut(&mut Self) -> Result<Expr>,
     {
+        let enclosing = self.chain_length;
         let mut expr = next_level(self)?;
         while let Some(token) = self.try_consume_any(ops) {
+            let operator = BinaryOp::try_from(token.token_type.clone())?;
+            let location = token.location;
+            // a grouped chain on the left, as in `(a + b) + c`, is on the spine too
+            let links = match expr {
+                Expr::Binary { .. } if self.chain_length == enclosing => {
+                    1 + left_spine_length(&expr)
+                }
+                _ => 1,
+            };
+            self.lengthen_chain(links)?;
+            let right = next_level(self)?;
+            if matches!(operator, BinaryOp::LogicalAnd | BinaryOp::LogicalOr) {
+                self.check_logical_operands(&[&expr, &right], location);
+            }
             expr = Expr::Binary {
                 left: Box::new(expr),
-                operator: BinaryOp::try_from(token.token_type.clone())?,
-                right: Box::new(next_level(self)?),
+                operator,
+                right: Box::new(right),
+                location,
             };
         }
 
+        self.chain_length = enclosing;
         Ok(expr)
     }
 
     fn unary(&mut self) -> Result<Expr> {
         if let Some(token) = self.try_consume_any(&[TokenType::Bang, TokenType::Minus]) {
+            let operator = UnaryOp::try_from(token.token_type.clone())?;
+            let location = token.location;
+            let operand = self.nested(Self::unary)?;
+            if operator == UnaryOp::Not {
+                self.check_logical_operands(&[&operand], location);
+            }
             return Ok(Expr::Unary {
-                operator: UnaryOp::try_from(token.token_type.clone())?,
-                operand: Box::new(self.unary()?),
+                operator,
+                operand: Box::new(operand),
             });
         }
 
@@ -407,29 +902,60 @@ impl Parser {
     }
 
     fn call(&mut self) -> Result<Expr> {
+        let location = self.peek().location;
+        let depth = self.nesting_depth;
         let mut expr = self.primary()?;
         loop {
+            if self.check(&TokenType::LeftParen) || self.check(&TokenType::LeftBracket) {
+                // each call or index nests the expression so far
+                self.deepen()?;
+            }
             if self.try_consume(TokenType::LeftParen).is_some() {
                 let arguments = self.arguments()?;
+                let message = match &expr {
+                    Expr::Variable(name) => format!("expected ')' to close call to '{name}'"),
+                    _ => "expected ')' to close call".to_string(),
+                };
+                self.consume(TokenType::RightParen, &message)?;
                 expr = Expr::Call {
                     callee: Box::new(expr),
                     arguments,
+                    location,
                 };
-                self.consume(TokenType::RightParen, "expected ')' after arguments")?;
             } else if self.try_consume(TokenType::LeftBracket).is_some() {
-                let index = self.expr()?;
-                expr = Expr::Index {
-                    array: Box::new(expr),
-                    index: Box::new(index),
-                };
-                self.consume(TokenType::RightBracket, "expected ']' after array index")?;
+                expr = self.index_or_slice(expr)?;
             } else {
                 break;
             }
         }
+        self.nesting_depth = depth;
         Ok(expr)
     }
 
+    /// Parse what follows the `[` after `array`: an index, or a slice with
+    /// either bound left out
+    fn index_or_slice(&mut self, array: Expr) -> Result<Expr> {
+        let array = Box::new(array);
+        let start = if self.check(&TokenType::Colon) {
+            None
+        } else {
+            Some(Box::new(self.expr()?))
+        };
+        if self.try_consume(TokenType::Colon).is_none() {
+            self.consume(TokenType::RightBracket, "expected ']' after array index")?;
+            let index = start.expect("an index without ':' was parsed");
+            return Ok(Expr::Index { array, index });
+        }
+
+        let end = if self.check(&TokenType::RightBracket) {
+            None
+        } else {
+            Some(Box::new(self.expr()?))
+        };
+        self.consume(TokenType::RightBracket, "expected ']' after array slice")?;
+        Ok(Expr::Slice { array, start, end })
+    }
+
     fn primary(&mut self) -> Result<Expr> {
         let token = self.advance();
 
@@ -441,7 +967,7 @@ impl Parser {
             TokenType::Nil => Ok(Expr::Nil),
             TokenType::LeftParen => {
                 let expr = self.expr()?;
-                self.consume(TokenType::RightParen, "expected ')' after expression")?;
+                self.consume(TokenType::RightParen, "expected ')' to close expression")?;
                 Ok(expr)
             }
             TokenType::LeftBracket => {
@@ -453,6 +979,10 @@ impl Parser {
                     Ok(Expr::Array(elements))
                 }
             }
+            TokenType::Loop => Err(Error::syntax(
+                "a 'loop' can only start a statement or initialize a 'let'".to_string(),
+                token.location,
+            )),
             _ => {
                 let expected = "number, string, boolean, identifier, '(' or '['";
                 Err(Error::syntax(
@@ -491,6 +1021,38 @@ impl Parser {
     }
 
     // Utility methods
+
+    /// Parse one level deeper into the tree being built
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let depth = self.nesting_depth;
+        self.deepen()?;
+        let result = parse(self);
+        self.nesting_depth = depth;
+        result
+    }
+
+    fn lengthen_chain(&mut self, links: usize) -> Result<()> {
+        self.chain_length += links;
+        if self.chain_length > MAX_CHAIN_LENGTH {
+            return Err(self.error(format!(
+                "chain of operators is too long (at most {MAX_CHAIN_LENGTH})"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fail once the tree is nested too deeply for the recursive passes
+    /// that walk it
+    fn deepen(&mut self) -> Result<()> {
+        if self.nesting_depth == MAX_NESTING_DEPTH {
+            return Err(self.error(format!(
+                "code is nested too deeply (at most {MAX_NESTING_DEPTH} levels)"
+            )));
+        }
+        self.nesting_depth += 1;
+        Ok(())
+    }
+
     fn peek(&self) -> &Token {
         &self.tokens[self.current]
     }
@@ -541,12 +1103,18 @@ impl Parser {
     }
 
     fn consume_identifier(&mut self) -> Result<String> {
-        if let TokenType::Identifier(name) = &self.peek().token_type {
-            let name = name.clone();
-            self.advance();
-            Ok(name)
-        } else {
-            Err(self.error("expected identifier".to_string()))
+        match &self.peek().token_type {
+            TokenType::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(name)
+            }
+            token_type => match token_type.keyword() {
+                Some(keyword) => Err(self.error(format!(
+                    "'{keyword}' is a reserved keyword and cannot be used as a name"
+                ))),
+                None => Err(self.error("expected identifier".to_string())),
+            },
         }
     }
 
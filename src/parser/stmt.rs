@@ -1,4 +1,7 @@
 use super::expr::Expr;
+use crate::location::Located;
+
+pub type LocatedStmt = Located<Stmt>;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
@@ -10,80 +13,201 @@ pub enum Stmt {
     FuncDecl {
         name: String,
         params: Vec<String>,
-        body: Vec<Stmt>,
+        body: Vec<LocatedStmt>,
+        /// The `///` comment lines right before the `fn`, joined by newlines
+        doc: Option<String>,
+    },
+    /// `let a = 1, b = a + 1;` declares each name in order
+    MultiVarDecl(Vec<(String, Option<Expr>)>),
+    /// `let (a, b) = expr;` binds the elements of an array in order
+    DestructureDecl {
+        names: Vec<String>,
+        initializer: Expr,
     },
 
     // Statements
     Expression(Expr),
-    Print(Vec<Expr>),
-    Block(Vec<Stmt>),
+    Print(PrintArgs),
+    /// Like `Print`, but writes to the error output
+    Eprint(PrintArgs),
+    Block(Vec<LocatedStmt>),
     If {
         condition: Expr,
-        then_branch: Box<Stmt>,
-        else_branch: Option<Box<Stmt>>,
+        then_branch: Box<LocatedStmt>,
+        else_branch: Option<Box<LocatedStmt>>,
     },
     While {
         condition: Expr,
-        body: Box<Stmt>,
+        body: Box<LocatedStmt>,
+    },
+    /// `while let name = expr { ... }` re-binds `name` each iteration and
+    /// stops once the value is nil
+    WhileLet {
+        name: String,
+        initializer: Expr,
+        body: Box<LocatedStmt>,
+    },
+    /// `repeat count { ... }` runs the body `count` times
+    Repeat {
+        count: Expr,
+        body: Box<LocatedStmt>,
     },
     For {
-        initializer: Option<Box<Stmt>>,
+        initializer: Option<Box<LocatedStmt>>,
         condition: Expr,
         increment: Option<Expr>,
-        body: Box<Stmt>,
+        body: Box<LocatedStmt>,
+    },
+    /// `for pattern in iterable`, binding each element of an array or range
+    /// to `pattern` in turn
+    ForIn {
+        pattern: Pattern,
+        iterable: Expr,
+        body: Box<LocatedStmt>,
+    },
+    /// `switch subject { case value: ... default: ... }` runs from the first
+    /// case equal to `subject`, or else from `default`, falling through the
+    /// cases after it until a `break`
+    Switch {
+        subject: Expr,
+        cases: Vec<SwitchCase>,
+    },
+    /// `break value;` is only allowed in a `loop`, which it gives that value
+    Break {
+        value: Option<Expr>,
     },
-    Break,
     Continue,
     Return {
         value: Option<Expr>,
     },
+    /// A lone `;`, such as one written after a block
+    Empty,
+}
+
+/// The values of a `print` or `eprint`, with its optional `sep = expr`
+/// and `end = expr` controls
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintArgs {
+    pub values: Vec<Expr>,
+    /// Joins the values, a space by default
+    pub sep: Option<Expr>,
+    /// Written after the values, a newline by default
+    pub end: Option<Expr>,
+}
+
+/// What a `for`-`in` binds each element to: a name, or `(a, b)` for an
+/// array of as many elements, each bound to its own pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Name(String),
+    Tuple(Vec<Pattern>),
+}
+
+/// One `case value:`, or `default:` when `value` is none, with the
+/// statements up to the next label
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchCase {
+    pub value: Option<Expr>,
+    pub body: Vec<LocatedStmt>,
+}
+
+impl PrintArgs {
+    pub fn new(values: Vec<Expr>) -> Self {
+        Self {
+            values,
+            sep: None,
+            end: None,
+        }
+    }
 }
 
 pub trait Visitor<T> {
     fn visit_expr(&mut self, expr: &Expr) -> T;
-    fn visit_print(&mut self, exprs: &[Expr]) -> T;
+    fn visit_print(&mut self, args: &PrintArgs) -> T;
+    fn visit_eprint(&mut self, args: &PrintArgs) -> T;
     fn visit_var_decl(&mut self, name: &str, initializer: Option<&Expr>) -> T;
-    fn visit_func_decl(&mut self, name: &str, params: &[String], body: &[Stmt]) -> T;
-    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> T;
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> T;
+    fn visit_multi_var_decl(&mut self, decls: &[(String, Option<Expr>)]) -> T;
+    fn visit_func_decl(
+        &mut self,
+        name: &str,
+        params: &[String],
+        body: &[LocatedStmt],
+        doc: Option<&str>,
+    ) -> T;
+    fn visit_destructure_decl(&mut self, names: &[String], initializer: &Expr) -> T;
+    fn visit_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &LocatedStmt,
+        else_branch: Option<&LocatedStmt>,
+    ) -> T;
+    fn visit_while(&mut self, condition: &Expr, body: &LocatedStmt) -> T;
+    fn visit_while_let(&mut self, name: &str, initializer: &Expr, body: &LocatedStmt) -> T;
+    fn visit_repeat(&mut self, count: &Expr, body: &LocatedStmt) -> T;
+    fn visit_switch(&mut self, subject: &Expr, cases: &[SwitchCase]) -> T;
     fn visit_return(&mut self, value: Option<&Expr>) -> T;
-    fn visit_break(&mut self) -> T;
+    fn visit_break(&mut self, value: Option<&Expr>) -> T;
     fn visit_continue(&mut self) -> T;
-    fn visit_block(&mut self, statements: &[Stmt]) -> T;
+    fn visit_block(&mut self, statements: &[LocatedStmt]) -> T;
+    fn visit_empty(&mut self) -> T;
     fn visit_for(
         &mut self,
-        initializer: Option<&Stmt>,
+        initializer: Option<&LocatedStmt>,
         condition: &Expr,
         increment: Option<&Expr>,
-        body: &Stmt,
+        body: &LocatedStmt,
     ) -> T;
+    fn visit_for_in(&mut self, pattern: &Pattern, iterable: &Expr, body: &LocatedStmt) -> T;
 }
 
 impl Stmt {
     pub fn accept<T>(&self, visitor: &mut dyn Visitor<T>) -> T {
         match self {
             Stmt::Expression(expr) => visitor.visit_expr(expr),
-            Stmt::Print(exprs) => visitor.visit_print(exprs),
+            Stmt::Print(args) => visitor.visit_print(args),
+            Stmt::Eprint(args) => visitor.visit_eprint(args),
             Stmt::Block(statements) => visitor.visit_block(statements),
             Stmt::VarDecl { name, initializer } => {
                 visitor.visit_var_decl(name, initializer.as_ref())
             }
-            Stmt::FuncDecl { name, params, body } => visitor.visit_func_decl(name, params, body),
+            Stmt::MultiVarDecl(decls) => visitor.visit_multi_var_decl(decls),
+            Stmt::FuncDecl {
+                name,
+                params,
+                body,
+                doc,
+            } => visitor.visit_func_decl(name, params, body, doc.as_deref()),
+            Stmt::DestructureDecl { names, initializer } => {
+                visitor.visit_destructure_decl(names, initializer)
+            }
             Stmt::If {
                 condition,
                 then_branch,
                 else_branch,
             } => visitor.visit_if(condition, then_branch, else_branch.as_deref()),
             Stmt::While { condition, body } => visitor.visit_while(condition, body),
+            Stmt::WhileLet {
+                name,
+                initializer,
+                body,
+            } => visitor.visit_while_let(name, initializer, body),
+            Stmt::Repeat { count, body } => visitor.visit_repeat(count, body),
+            Stmt::Switch { subject, cases } => visitor.visit_switch(subject, cases),
             Stmt::Return { value } => visitor.visit_return(value.as_ref()),
-            Stmt::Break => visitor.visit_break(),
+            Stmt::Break { value } => visitor.visit_break(value.as_ref()),
             Stmt::Continue => visitor.visit_continue(),
+            Stmt::Empty => visitor.visit_empty(),
             Stmt::For {
                 initializer,
                 condition,
                 increment,
                 body,
             } => visitor.visit_for(initializer.as_deref(), condition, increment.as_ref(), body),
+            Stmt::ForIn {
+                pattern,
+                iterable,
+                body,
+            } => visitor.visit_for_in(pattern, iterable, body),
         }
     }
 }
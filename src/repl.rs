@@ -0,0 +1,172 @@
+use crate::{
+    compiler::{self, Chunk, Compiler},
+    error::Result,
+    parse,
+    parser::{Expr, LocatedStmt, Stmt},
+    treewalk::{self, Interpreter},
+    vm::VM,
+};
+use std::io::Write;
+
+/// Which backend runs the code typed at the prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    TreeWalk,
+    Vm,
+}
+
+/// The state behind the interactive prompt. Each backend keeps its own
+/// globals, so switching backends starts from the other backend's state
+pub struct Repl {
+    backend: Backend,
+    interpreter: Interpreter,
+    vm: VM,
+    /// The last line of code entered, dumped by `:ast` and `:bytecode`
+    last_input: Option<String>,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self {
+            backend: Backend::TreeWalk,
+            interpreter: Interpreter::new(),
+            vm: VM::new(Chunk::new()),
+            last_input: None,
+        }
+    }
+
+    /// Send what either backend prints to `output` instead of stdout
+    pub fn with_output<W: Write + Clone + 'static>(output: W) -> Self {
+        Self {
+            interpreter: Interpreter::with_output(Box::new(output.clone())),
+            vm: VM::with_output(Chunk::new(), Box::new(output)),
+            ..Self::new()
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Handle one line of input: either a command such as `:vm`, or code to
+    /// run on the current backend. Returns the reply to a command, or the
+    /// value of a lone expression unless it is nil
+    pub fn handle(&mut self, line: &str) -> Result<Option<String>> {
+        match line {
+            ":vm" => {
+                self.backend = Backend::Vm;
+                Ok(Some("using the bytecode VM".to_string()))
+            }
+            ":tr" => {
+                self.backend = Backend::TreeWalk;
+                Ok(Some("using the tree-walk interpreter".to_string()))
+            }
+            ":ast" => match &self.last_input {
+                Some(input) => {
+                    let stmts = parse_input(input)?;
+                    let dump: Vec<_> = stmts
+                        .iter()
+                        .map(|stmt| format!("{:#?}", stmt.as_inner()))
+                        .collect();
+                    Ok(Some(dump.join("\n")))
+                }
+                None => Ok(Some("no input yet".to_string())),
+            },
+            ":bytecode" => match &self.last_input {
+                Some(input) => {
+                    // compiled on top of the VM's code, so it may use
+                    // globals defined by earlier lines
+                    let start = self.vm.chunk().current_ip();
+                    let chunk = self.compile(&parse_input(input)?)?;
+                    Ok(Some(chunk.disassembly_from("<stdin>", start)))
+                }
+                None => Ok(Some("no input yet".to_string())),
+            },
+            command if command.starts_with(':') => Ok(Some(format!(
+                "unknown command '{command}' (expected :vm, :tr, :ast or :bytecode)"
+            ))),
+            code => {
+                self.last_input = Some(code.to_string());
+                let stmts = parse_input(code)?;
+                match lone_expression(&stmts) {
+                    Some(stmt) => self.show(stmt),
+                    None => {
+                        self.run(&stmts)?;
+                        Ok(None)
+                    }
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, stmts: &[LocatedStmt]) -> Result<()> {
+        match self.backend {
+            Backend::TreeWalk => self.interpreter.interpret(stmts),
+            Backend::Vm => {
+                let chunk = self.compile(stmts)?;
+                self.vm.extend_chunk(chunk);
+                self.vm.run()?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Evaluate the expression statement `stmt`, returning its value as
+    /// text unless it is nil
+    fn show(&mut self, stmt: &LocatedStmt) -> Result<Option<String>> {
+        let Stmt::Expression(expr) = stmt.as_inner() else {
+            unreachable!("only expression statements are shown");
+        };
+        let location = stmt.location();
+        match self.backend {
+            Backend::TreeWalk => {
+                let value = self
+                    .interpreter
+                    .evaluate(expr)
+                    .map_err(|e| e.or_at_location(location))?;
+                Ok((!matches!(value, treewalk::Value::Nil)).then(|| value.to_string()))
+            }
+            Backend::Vm => {
+                let chunk =
+                    Compiler::with_chunk(self.vm.chunk().clone()).compile_expr(expr, location)?;
+                self.vm.extend_chunk(chunk);
+                self.vm.run()?;
+                let value = self.vm.stack().last();
+                Ok(value
+                    .filter(|value| !matches!(value, compiler::Value::Nil))
+                    .map(|value| value.to_string()))
+            }
+        }
+    }
+
+    /// Compile `stmts` onto the end of the VM's chunk
+    fn compile(&self, stmts: &[LocatedStmt]) -> Result<Chunk> {
+        Compiler::with_chunk(self.vm.chunk().clone()).compile(stmts)
+    }
+}
+
+/// Parse a line of input, which may leave out the `;` ending it
+fn parse_input(code: &str) -> Result<Vec<LocatedStmt>> {
+    parse(code).or_else(|error| parse(&format!("{code};")).map_err(|_| error))
+}
+
+/// The statement of input that is just an expression to show the value
+/// of; assignments are run for their effect, like any other statement
+fn lone_expression(stmts: &[LocatedStmt]) -> Option<&LocatedStmt> {
+    match stmts {
+        [stmt] => match stmt.as_inner() {
+            Stmt::Expression(
+                Expr::Assign { .. } | Expr::IndexAssign { .. } | Expr::SliceAssign { .. },
+            ) => None,
+            Stmt::Expression(_) => Some(stmt),
+            _ => None,
+        },
+        _ => None,
+    }
+}
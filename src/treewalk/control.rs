@@ -7,7 +7,8 @@ pub type InterpreterResult<T> = Result<T, RuntimeControl>;
 pub enum RuntimeControl {
     Error(Error),
     Return(Value),
-    Break,
+    /// Carries the value of `break value;`, which only a `loop` uses
+    Break(Option<Value>),
     Continue,
 }
 
@@ -22,7 +23,7 @@ impl From<RuntimeControl> for Error {
         match control {
             RuntimeControl::Error(error) => error,
             RuntimeControl::Return(_) => Error::runtime("'return' outside function".to_string()),
-            RuntimeControl::Break => Error::runtime("'break' outside loop".to_string()),
+            RuntimeControl::Break(_) => Error::runtime("'break' outside loop".to_string()),
             RuntimeControl::Continue => Error::runtime("'continue' outside loop".to_string()),
         }
     }
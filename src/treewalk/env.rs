@@ -1,4 +1,4 @@
-use super::{buildin::BUILTIN_FUNCTIONS, value::Value};
+use super::value::Value;
 use crate::error::{Error, Result};
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
@@ -14,7 +14,7 @@ impl Environment {
     pub fn new_global() -> EnvRef {
         let mut variables = HashMap::new();
 
-        for (name, func) in BUILTIN_FUNCTIONS {
+        for (name, func) in Value::BUILTIN_FUNCTIONS {
             let builtin_value = Value::BuiltinFunction {
                 name: name.to_string(),
                 function: *func,
@@ -35,6 +35,11 @@ impl Environment {
         }))
     }
 
+    pub fn clear(&mut self) {
+        self.variables.clear();
+        self.enclosing = None;
+    }
+
     pub fn define(&mut self, name: String, value: Value) {
         self.variables.insert(name, value);
     }
@@ -51,6 +56,15 @@ impl Environment {
         Err(Error::runtime(format!("name '{name}' is not defined")))
     }
 
+    /// Whether `name` is defined here or in an enclosing scope
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.variables.contains_key(name)
+            || self
+                .enclosing
+                .as_ref()
+                .is_some_and(|parent| parent.borrow().is_defined(name))
+    }
+
     pub fn set(&mut self, name: &str, value: Value) -> Result<()> {
         if self.variables.contains_key(name) {
             self.variables.insert(name.to_string(), value);
@@ -4,14 +4,31 @@ use super::{
     value::{Function, Value},
 };
 use crate::{
+    buildin::{file_functions, Caller},
     error::{Error, Result},
-    parser::{expr, stmt, BinaryOp, Expr, LocatedStmt, Stmt, UnaryOp},
+    location::Location,
+    parser::{
+        expr, hoist_functions, stmt, BinaryOp, Expr, LocatedStmt, Pattern, PrintArgs, SwitchCase,
+        UnaryOp,
+    },
+    value::{array_index, range_len},
 };
 use std::{cell::RefCell, io::Write, rc::Rc};
 
 pub struct Interpreter {
     env: EnvRef,
     output: Box<dyn Write>,
+    error_output: Box<dyn Write>,
+    copy_arrays: bool,
+    file_access: bool,
+    /// The value of the last expression statement or variable declaration run
+    last_value: Value,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
@@ -19,26 +36,246 @@ impl Interpreter {
         Self {
             env: Environment::new_global(),
             output: Box::new(std::io::stdout()),
+            error_output: Box::new(std::io::stderr()),
+            copy_arrays: false,
+            file_access: false,
+            last_value: Value::Nil,
         }
     }
 
     pub fn with_output(output: Box<dyn Write>) -> Self {
         Self {
-            env: Environment::new_global(),
             output,
+            ..Self::new()
         }
     }
 
+    /// Redirect `eprint` output, which goes to stderr by default
+    pub fn set_error_output(&mut self, error_output: Box<dyn Write>) {
+        self.error_output = error_output;
+    }
+
     pub fn interpret(&mut self, stmts: &[LocatedStmt]) -> Result<()> {
-        for stmt in stmts {
-            let loc = stmt.location();
-            stmt.as_inner()
-                .accept(self)
-                .map_err(|e| Error::from(e).at_location(loc))?;
+        for stmt in hoist_functions(stmts) {
+            self.execute(stmt)
+                .map_err(|e| Error::from(e).or_at_location(stmt.location()))?;
         }
         Ok(())
     }
 
+    /// Evaluate a lone expression, such as one typed at the REPL
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value> {
+        let value = expr.accept(self)?;
+        self.last_value = value.clone();
+        Ok(value)
+    }
+
+    /// The value of the most recent expression statement or variable
+    /// declaration, or nil if none has run yet
+    pub fn last_value(&self) -> &Value {
+        &self.last_value
+    }
+
+    /// Run one statement, tagging errors with its location
+    fn execute(&mut self, stmt: &LocatedStmt) -> InterpreterResult<()> {
+        stmt.as_inner()
+            .accept(self)
+            .map_err(|control| match control {
+                RuntimeControl::Error(e) => {
+                    RuntimeControl::Error(e.or_at_location(stmt.location()))
+                }
+                control => control,
+            })
+    }
+
+    /// Give arrays value semantics: binding an array to a variable or a
+    /// parameter stores a copy instead of sharing the original
+    pub fn set_copy_arrays(&mut self, enabled: bool) {
+        self.copy_arrays = enabled;
+    }
+
+    /// Let scripts read and write files with `read_file` and `write_file`,
+    /// which fail with a runtime error until enabled
+    pub fn set_file_access(&mut self, enabled: bool) {
+        self.file_access = enabled;
+        self.install_file_functions();
+    }
+
+    fn install_file_functions(&mut self) {
+        for (name, function) in file_functions(self.file_access) {
+            let builtin = Value::BuiltinFunction {
+                name: name.to_string(),
+                function,
+            };
+            self.env.borrow_mut().define(name.to_string(), builtin);
+        }
+    }
+
+    fn call_value(
+        &mut self,
+        callee: Value,
+        arguments: Vec<Value>,
+        callee_name: Option<&str>,
+    ) -> Result<Value> {
+        match callee {
+            Value::Function(func) => {
+                if func.params.len() != arguments.len() {
+                    return Err(Error::runtime(format!(
+                        "Expected {} arguments, got {}",
+                        func.params.len(),
+                        arguments.len()
+                    )));
+                }
+                let prev_env = Rc::clone(&self.env);
+                self.env = Environment::new_enclosed(Rc::clone(&func.closure));
+
+                for (param, arg) in func.params.iter().zip(arguments) {
+                    let arg = self.bind(arg);
+                    self.env.borrow_mut().define(param.clone(), arg);
+                }
+                let result = func.body.iter().try_for_each(|stmt| self.execute(stmt));
+
+                self.env = prev_env;
+
+                match result {
+                    Ok(_) => Ok(Value::Nil),
+                    Err(RuntimeControl::Return(value)) => Ok(value),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Value::BuiltinFunction { function, .. } => function(&arguments, self),
+            _ => Err(Error::not_callable(callee_name, callee.type_name())),
+        }
+    }
+
+    /// Evaluate an expression statement or `let` initializer, the only places
+    /// a `loop` may stand, so a `return` inside one can leave the function
+    fn evaluate_statement_expr(&mut self, expr: &Expr) -> InterpreterResult<Value> {
+        match expr {
+            Expr::Loop(body) => self.run_loop(body),
+            _ => Ok(expr.accept(self)?),
+        }
+    }
+
+    /// Run a `loop` until it breaks, passing any `return` on to the caller
+    fn run_loop(&mut self, body: &[LocatedStmt]) -> InterpreterResult<Value> {
+        loop {
+            match stmt::Visitor::visit_block(self, body) {
+                Ok(_) | Err(RuntimeControl::Continue) => (),
+                Err(RuntimeControl::Break(value)) => return Ok(value.unwrap_or(Value::Nil)),
+                Err(control) => return Err(control),
+            }
+        }
+    }
+
+    /// Evaluate the values of `args` and join them for printing, followed
+    /// by the terminator
+    fn format_values(&mut self, args: &PrintArgs) -> Result<String> {
+        let values = args
+            .values
+            .iter()
+            .map(|expr| expr.accept(self).map(|value| value.to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut print_option = |option: Option<&Expr>, default: &str| {
+            option.map_or(Ok(default.to_string()), |expr| {
+                expr.accept(self).map(|value| value.to_string())
+            })
+        };
+        let sep = print_option(args.sep.as_ref(), " ")?;
+        let end = print_option(args.end.as_ref(), "\n")?;
+        Ok(values.join(&sep) + &end)
+    }
+
+    /// Evaluate a slice bound, with a missing one as nil
+    fn evaluate_bound(&mut self, bound: Option<&Expr>) -> Result<Value> {
+        bound.map_or(Ok(Value::Nil), |bound| bound.accept(self))
+    }
+
+    /// Apply `op` to `left`, the value of the chain so far, and `right`
+    fn binary_step(
+        &mut self,
+        left: Value,
+        op: &BinaryOp,
+        right: &Expr,
+        location: Location,
+    ) -> Result<Value> {
+        match op {
+            BinaryOp::LogicalAnd => {
+                if !left.is_truthy() {
+                    Ok(left)
+                } else {
+                    right.accept(self)
+                }
+            }
+            BinaryOp::LogicalOr => {
+                if left.is_truthy() {
+                    Ok(left)
+                } else {
+                    right.accept(self)
+                }
+            }
+            _ => {
+                let right = right.accept(self)?;
+                let result = match op {
+                    BinaryOp::Add => left + right,
+                    BinaryOp::Subtract => left - right,
+                    BinaryOp::Multiply => left * right,
+                    BinaryOp::Divide => left / right,
+                    BinaryOp::Equal => Ok(Value::Boolean(left == right)),
+                    BinaryOp::NotEqual => Ok(Value::Boolean(left != right)),
+                    BinaryOp::LessThan
+                    | BinaryOp::LessEqual
+                    | BinaryOp::GreaterThan
+                    | BinaryOp::GreaterEqual => compare(op, &left, &right).map(Value::Boolean),
+                    _ => unreachable!(),
+                };
+                result.map_err(|e| e.or_at_location(location))
+            }
+        }
+    }
+
+    fn bind(&self, value: Value) -> Value {
+        if self.copy_arrays {
+            value.deep_copy()
+        } else {
+            value
+        }
+    }
+
+    /// Define the names of `pattern` in the current scope, unpacking
+    /// `value` as the pattern's shape demands
+    fn bind_pattern(&mut self, pattern: &Pattern, value: Value) -> Result<()> {
+        let patterns = match pattern {
+            Pattern::Name(name) => {
+                let value = self.bind(value);
+                self.env.borrow_mut().define(name.clone(), value);
+                return Ok(());
+            }
+            Pattern::Tuple(patterns) => patterns,
+        };
+        let Value::Array(arr) = value else {
+            return Err(Error::destructure_non_array(value.type_name()));
+        };
+
+        let elements = arr.borrow().clone();
+        if elements.len() != patterns.len() {
+            return Err(Error::destructure_length(elements.len(), patterns.len()));
+        }
+        for (pattern, element) in patterns.iter().zip(elements) {
+            self.bind_pattern(pattern, element)?;
+        }
+        Ok(())
+    }
+
+    /// Drop every definition from previous runs, keeping only the builtins
+    pub fn reset(&mut self) {
+        // functions hold their defining env, so clear it to break the cycle
+        self.env.borrow_mut().clear();
+        self.env = Environment::new_global();
+        self.last_value = Value::Nil;
+        self.install_file_functions();
+    }
+
     pub fn begin_scope(&mut self) {
         self.env = Environment::new_enclosed(self.env.clone());
     }
@@ -51,42 +288,65 @@ impl Interpreter {
 
 impl stmt::Visitor<InterpreterResult<()>> for Interpreter {
     fn visit_expr(&mut self, expr: &Expr) -> InterpreterResult<()> {
-        expr.accept(self)?;
+        self.last_value = self.evaluate_statement_expr(expr)?;
         Ok(())
     }
 
-    fn visit_print(&mut self, exprs: &[Expr]) -> InterpreterResult<()> {
-        let output = exprs
-            .iter()
-            .map(|expr| expr.accept(self).map(|value| value.to_string()))
-            .collect::<Result<Vec<_>>>()?
-            .join(" ");
+    fn visit_print(&mut self, args: &PrintArgs) -> InterpreterResult<()> {
+        let output = self.format_values(args)?;
+        write!(self.output, "{output}")
+            .map_err(|e| RuntimeControl::Error(Error::io(e.to_string())))?;
+        Ok(())
+    }
 
-        writeln!(self.output, "{output}")
+    fn visit_eprint(&mut self, args: &PrintArgs) -> InterpreterResult<()> {
+        let output = self.format_values(args)?;
+        write!(self.error_output, "{output}")
             .map_err(|e| RuntimeControl::Error(Error::io(e.to_string())))?;
         Ok(())
     }
 
     fn visit_var_decl(&mut self, name: &str, initializer: Option<&Expr>) -> InterpreterResult<()> {
         let value = if let Some(expr) = initializer {
-            expr.accept(self)?
+            self.evaluate_statement_expr(expr)?
         } else {
             Value::Nil
         };
 
+        let value = self.bind(value);
+        self.last_value = value.clone();
         self.env.borrow_mut().define(name.to_string(), value);
         Ok(())
     }
 
+    fn visit_multi_var_decl(&mut self, decls: &[(String, Option<Expr>)]) -> InterpreterResult<()> {
+        for (name, initializer) in decls {
+            self.visit_var_decl(name, initializer.as_ref())?;
+        }
+        Ok(())
+    }
+
+    fn visit_destructure_decl(
+        &mut self,
+        names: &[String],
+        initializer: &Expr,
+    ) -> InterpreterResult<()> {
+        let value = initializer.accept(self)?;
+        let pattern = Pattern::Tuple(names.iter().cloned().map(Pattern::Name).collect());
+        Ok(self.bind_pattern(&pattern, value)?)
+    }
+
     fn visit_func_decl(
         &mut self,
         name: &str,
         params: &[String],
-        body: &[Stmt],
+        body: &[LocatedStmt],
+        doc: Option<&str>,
     ) -> InterpreterResult<()> {
         let func = Value::Function(Rc::new(Function {
             name: name.to_string(),
             params: params.to_vec(),
+            doc: doc.map(str::to_string),
             body: body.to_vec(),
             closure: Rc::clone(&self.env),
         }));
@@ -96,22 +356,22 @@ impl stmt::Visitor<InterpreterResult<()>> for Interpreter {
 
     fn visit_for(
         &mut self,
-        initializer: Option<&Stmt>,
+        initializer: Option<&LocatedStmt>,
         condition: &Expr,
         increment: Option<&Expr>,
-        body: &Stmt,
+        body: &LocatedStmt,
     ) -> InterpreterResult<()> {
         self.begin_scope();
 
         if let Some(init) = initializer {
-            init.accept(self)?;
+            self.execute(init)?;
         }
         while condition.accept(self)?.is_truthy() {
-            match body.accept(self) {
+            match self.execute(body) {
                 Ok(_) => (),
-                Err(RuntimeControl::Break) => break,
+                Err(RuntimeControl::Break(_)) => break,
                 Err(RuntimeControl::Continue) => (),
-                Err(e) => return Err(e.into()),
+                Err(e) => return Err(e),
             }
             if let Some(inc) = increment {
                 inc.accept(self)?;
@@ -125,31 +385,138 @@ impl stmt::Visitor<InterpreterResult<()>> for Interpreter {
     fn visit_if(
         &mut self,
         condition: &Expr,
-        then_branch: &Stmt,
-        else_branch: Option<&Stmt>,
+        then_branch: &LocatedStmt,
+        else_branch: Option<&LocatedStmt>,
     ) -> InterpreterResult<()> {
         if condition.accept(self)?.is_truthy() {
-            then_branch.accept(self)?;
+            self.execute(then_branch)?;
         } else if let Some(else_branch) = else_branch {
-            else_branch.accept(self)?;
+            self.execute(else_branch)?;
         }
         Ok(())
     }
 
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> InterpreterResult<()> {
+    fn visit_while(&mut self, condition: &Expr, body: &LocatedStmt) -> InterpreterResult<()> {
         while condition.accept(self)?.is_truthy() {
-            match body.accept(self) {
+            match self.execute(body) {
                 Ok(_) => (),
-                Err(RuntimeControl::Break) => break,
+                Err(RuntimeControl::Break(_)) => break,
                 Err(RuntimeControl::Continue) => (),
-                Err(e) => return Err(e.into()),
+                Err(e) => return Err(e),
             }
         }
         Ok(())
     }
 
-    fn visit_break(&mut self) -> InterpreterResult<()> {
-        Err(RuntimeControl::Break)
+    fn visit_while_let(
+        &mut self,
+        name: &str,
+        initializer: &Expr,
+        body: &LocatedStmt,
+    ) -> InterpreterResult<()> {
+        loop {
+            let value = initializer.accept(self)?;
+            if matches!(value, Value::Nil) {
+                break;
+            }
+
+            self.begin_scope();
+            let value = self.bind(value);
+            self.env.borrow_mut().define(name.to_string(), value);
+            let result = self.execute(body);
+            self.end_scope();
+
+            match result {
+                Ok(_) => (),
+                Err(RuntimeControl::Break(_)) => break,
+                Err(RuntimeControl::Continue) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_repeat(&mut self, count: &Expr, body: &LocatedStmt) -> InterpreterResult<()> {
+        let count = match count.accept(self)? {
+            Value::Number(n) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+            value => {
+                return Err(Error::runtime(format!(
+                    "repeat count must be a non-negative integer, got {value}"
+                ))
+                .into())
+            }
+        };
+
+        for _ in 0..count {
+            match self.execute(body) {
+                Ok(_) => (),
+                Err(RuntimeControl::Break(_)) => break,
+                Err(RuntimeControl::Continue) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_for_in(
+        &mut self,
+        pattern: &Pattern,
+        iterable: &Expr,
+        body: &LocatedStmt,
+    ) -> InterpreterResult<()> {
+        let iterable = iterable.accept(self)?;
+        for index in 0.. {
+            let Some(element) = iterable.iteration_element(index)? else {
+                break;
+            };
+
+            self.begin_scope();
+            let result = self
+                .bind_pattern(pattern, element)
+                .map_err(RuntimeControl::from)
+                .and_then(|()| self.execute(body));
+            self.end_scope();
+
+            match result {
+                Ok(_) => (),
+                Err(RuntimeControl::Break(_)) => break,
+                Err(RuntimeControl::Continue) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_switch(&mut self, subject: &Expr, cases: &[SwitchCase]) -> InterpreterResult<()> {
+        let subject = subject.accept(self)?;
+        let mut start = None;
+        for (i, case) in cases.iter().enumerate() {
+            if let Some(value) = &case.value {
+                if value.accept(self)? == subject {
+                    start = Some(i);
+                    break;
+                }
+            }
+        }
+        let Some(start) = start.or_else(|| cases.iter().position(|case| case.value.is_none()))
+        else {
+            return Ok(());
+        };
+
+        // fall through every case after the one matched, until a break
+        for case in &cases[start..] {
+            match stmt::Visitor::visit_block(self, &case.body) {
+                Ok(_) => (),
+                Err(RuntimeControl::Break(_)) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_break(&mut self, value: Option<&Expr>) -> InterpreterResult<()> {
+        let value = value.map(|expr| expr.accept(self)).transpose()?;
+        Err(RuntimeControl::Break(value))
     }
 
     fn visit_continue(&mut self) -> InterpreterResult<()> {
@@ -161,15 +528,18 @@ impl stmt::Visitor<InterpreterResult<()>> for Interpreter {
             .map(|expr| expr.accept(self))
             .transpose()?
             .unwrap_or(Value::Nil);
-        return Err(RuntimeControl::Return(value));
+        Err(RuntimeControl::Return(value))
     }
 
-    fn visit_block(&mut self, statements: &[Stmt]) -> InterpreterResult<()> {
+    fn visit_block(&mut self, statements: &[LocatedStmt]) -> InterpreterResult<()> {
         self.begin_scope();
-        for stmt in statements {
-            stmt.accept(self)?;
-        }
+        // close the scope even when break/continue/return leaves early
+        let result = statements.iter().try_for_each(|stmt| self.execute(stmt));
         self.end_scope();
+        result
+    }
+
+    fn visit_empty(&mut self) -> InterpreterResult<()> {
         Ok(())
     }
 }
@@ -203,46 +573,38 @@ impl expr::Visitor<Result<Value>> for Interpreter {
         Ok(Value::Array(Rc::new(RefCell::new(values))))
     }
 
-    fn visit_binary(&mut self, left: &Expr, op: &BinaryOp, right: &Expr) -> Result<Value> {
-        match op {
-            BinaryOp::LogicalAnd => {
-                let left = left.accept(self)?;
-                if !left.is_truthy() {
-                    Ok(left)
-                } else {
-                    right.accept(self)
-                }
-            }
-            BinaryOp::LogicalOr => {
-                let left = left.accept(self)?;
-                if left.is_truthy() {
-                    Ok(left)
-                } else {
-                    right.accept(self)
-                }
-            }
-            _ => {
-                let left = left.accept(self)?;
-                let right = right.accept(self)?;
-                match op {
-                    BinaryOp::Add => left + right,
-                    BinaryOp::Subtract => left - right,
-                    BinaryOp::Multiply => left * right,
-                    BinaryOp::Divide => left / right,
-                    BinaryOp::Equal => Ok(Value::Boolean(left == right)),
-                    BinaryOp::NotEqual => Ok(Value::Boolean(left != right)),
-                    BinaryOp::LessThan => Ok(Value::Boolean(left < right)),
-                    BinaryOp::LessEqual => Ok(Value::Boolean(left <= right)),
-                    BinaryOp::GreaterThan => Ok(Value::Boolean(left > right)),
-                    BinaryOp::GreaterEqual => Ok(Value::Boolean(left >= right)),
-                    _ => unreachable!(),
-                }
-            }
+    fn visit_binary(
+        &mut self,
+        left: &Expr,
+        op: &BinaryOp,
+        right: &Expr,
+        location: Location,
+    ) -> Result<Value> {
+        // evaluate the left spine of a chain such as `a + b + c` in a loop
+        // rather than recursing once per operator, so long chains are fine
+        let mut steps = vec![(op, right, location)];
+        let mut leftmost = left;
+        while let Expr::Binary {
+            left,
+            operator,
+            right,
+            location,
+        } = leftmost
+        {
+            steps.push((operator, right, *location));
+            leftmost = left;
         }
+
+        let mut value = leftmost.accept(self)?;
+        for (op, right, location) in steps.into_iter().rev() {
+            value = self.binary_step(value, op, right, location)?;
+        }
+        Ok(value)
     }
 
     fn visit_assign(&mut self, name: &str, value: &Expr) -> Result<Value> {
         let value = value.accept(self)?;
+        let value = self.bind(value);
         self.env.borrow_mut().set(name, value.clone())?;
         Ok(value)
     }
@@ -253,97 +615,116 @@ impl expr::Visitor<Result<Value>> for Interpreter {
 
         match (array_value, index_value) {
             (Value::Array(arr), Value::Number(idx)) => {
-                let idx = idx as usize;
-                if idx < arr.borrow().len() {
+                let idx = array_index(idx)?;
+                let length = arr.borrow().len();
+                if idx < length {
                     Ok(arr.borrow()[idx].clone())
                 } else {
-                    Err(Error::runtime(format!(
-                        "Array index {} out of bounds (length: {})",
+                    Err(Error::index_out_of_bounds(
+                        idx,
+                        length,
+                        variable_name(array),
+                    ))
+                }
+            }
+            (Value::Range { start, end, step }, Value::Number(idx)) => {
+                let idx = array_index(idx)?;
+                let length = range_len(start, end, step);
+                if idx < length {
+                    Ok(Value::Number(start + idx as f64 * step))
+                } else {
+                    Err(Error::index_out_of_bounds(
                         idx,
-                        arr.borrow().len()
-                    )))
+                        length,
+                        variable_name(array),
+                    ))
                 }
             }
-            (Value::Array(_), _) => Err(Error::runtime("array index must be a number".to_string())),
-            _ => Err(Error::runtime("cannot index non-array value".to_string())),
+            (Value::Array(_) | Value::Range { .. }, index) => {
+                Err(Error::non_number_index(index.type_name()))
+            }
+            (array, _) => Err(Error::not_indexable(array.type_name())),
         }
     }
 
+    /// Arrays are shared references, so assigning through any expression
+    /// that evaluates to one, such as `matrix[1][0] = x`, mutates it in place
     fn visit_index_assign(&mut self, array: &Expr, index: &Expr, value: &Expr) -> Result<Value> {
+        let array_value = array.accept(self)?;
         let index_value = index.accept(self)?;
         let new_value = value.accept(self)?;
 
-        match index_value {
-            Value::Number(idx) => {
-                let idx = idx as usize;
-                match array {
-                    Expr::Variable(name) => {
-                        let mut array_value = self.env.borrow().get(name)?;
-                        match &mut array_value {
-                            Value::Array(ref mut arr) => {
-                                if idx < arr.borrow().len() {
-                                    arr.borrow_mut()[idx] = new_value.clone();
-                                    self.env.borrow_mut().set(name, array_value)?;
-                                    Ok(new_value)
-                                } else {
-                                    Err(Error::runtime(format!(
-                                        "Array index {} out of bounds (length: {})",
-                                        idx,
-                                        arr.borrow().len()
-                                    )))
-                                }
-                            }
-                            _ => Err(Error::runtime(
-                                "Cannot index assign to non-array value".to_string(),
-                            )),
-                        }
+        match (array_value, index_value) {
+            (Value::Array(arr), Value::Number(idx)) => {
+                let idx = array_index(idx)?;
+                let length = arr.borrow().len();
+                match arr.borrow_mut().get_mut(idx) {
+                    Some(target) => *target = new_value.clone(),
+                    None => {
+                        return Err(Error::index_out_of_bounds(
+                            idx,
+                            length,
+                            variable_name(array),
+                        ))
                     }
-                    _ => Err(Error::runtime(
-                        "Can only assign to array variables".to_string(),
-                    )),
                 }
+                Ok(new_value)
             }
-            _ => Err(Error::runtime("Array index must be a number".to_string())),
+            (Value::Range { .. }, _) => Err(Error::range_unsupported("assigned to")),
+            (Value::Array(_), index) => Err(Error::non_number_index(index.type_name())),
+            (array, _) => Err(Error::not_indexable(array.type_name())),
         }
     }
 
-    fn visit_call(&mut self, callee: &Expr, arguments: &[Expr]) -> Result<Value> {
+    fn visit_slice(
+        &mut self,
+        array: &Expr,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+    ) -> Result<Value> {
+        let array = array.accept(self)?;
+        let start = self.evaluate_bound(start)?;
+        let end = self.evaluate_bound(end)?;
+        array.slice(&start, &end)
+    }
+
+    fn visit_slice_assign(
+        &mut self,
+        array: &Expr,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+        value: &Expr,
+    ) -> Result<Value> {
+        let array = array.accept(self)?;
+        let start = self.evaluate_bound(start)?;
+        let end = self.evaluate_bound(end)?;
+        let value = value.accept(self)?;
+        array.splice(&start, &end, &value)?;
+        Ok(value)
+    }
+
+    fn visit_call(
+        &mut self,
+        callee: &Expr,
+        arguments: &[Expr],
+        location: Location,
+    ) -> Result<Value> {
+        let callee_name = variable_name(callee);
         let callee = callee.accept(self)?;
         let arguments = arguments
             .iter()
             .map(|arg| arg.accept(self))
             .collect::<Result<Vec<Value>>>()?;
 
-        match callee {
-            Value::Function(func) => {
-                if func.params.len() != arguments.len() {
-                    return Err(Error::runtime(format!(
-                        "Expected {} arguments, got {}",
-                        func.params.len(),
-                        arguments.len()
-                    )));
-                }
-                let prev_env = Rc::clone(&self.env);
-                self.env = Environment::new_enclosed(Rc::clone(&func.closure));
-
-                for (param, arg) in func.params.iter().zip(arguments.iter()) {
-                    self.env.borrow_mut().define(param.clone(), arg.clone());
-                }
-                let result = func.body.iter().try_for_each(|stmt| stmt.accept(self));
-
-                self.env = prev_env;
+        // errors from inside a user function already carry their own location
+        self.call_value(callee, arguments, callee_name)
+            .map_err(|e| e.or_at_location(location))
+    }
 
-                match result {
-                    Ok(_) => Ok(Value::Nil),
-                    Err(RuntimeControl::Return(value)) => Ok(value),
-                    Err(e) => Err(e.into()),
-                }
-            }
-            Value::BuiltinFunction { function, .. } => function(&arguments),
-            _ => Err(Error::runtime(format!(
-                "can only call functions. Got {callee}"
-            ))),
-        }
+    fn visit_loop(&mut self, body: &[LocatedStmt]) -> Result<Value> {
+        // the parser only lets a `loop` start a statement or initialize a
+        // `let`, which go through `evaluate_statement_expr` instead
+        self.run_loop(body).map_err(Error::from)
     }
 
     fn visit_unary(&mut self, op: &UnaryOp, operand: &Expr) -> Result<Value> {
@@ -353,4 +734,50 @@ impl expr::Visitor<Result<Value>> for Interpreter {
             UnaryOp::Not => Ok(Value::Boolean(!operand.is_truthy())),
         }
     }
+
+    fn visit_chained_comparison(
+        &mut self,
+        operands: &[Expr],
+        operators: &[BinaryOp],
+    ) -> Result<Value> {
+        let mut left = operands[0].accept(self)?;
+        for (op, operand) in operators.iter().zip(&operands[1..]) {
+            let right = operand.accept(self)?;
+            if !compare(op, &left, &right)? {
+                return Ok(Value::Boolean(false));
+            }
+            left = right;
+        }
+        Ok(Value::Boolean(true))
+    }
+}
+
+impl Caller<Function> for Interpreter {
+    fn call(&mut self, function: &Value, args: Vec<Value>) -> Result<Value> {
+        self.call_value(function.clone(), args, None)
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.env.borrow().is_defined(name)
+    }
+}
+
+/// The name of the variable `expr` reads, for naming it in errors like the VM does
+fn variable_name(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Variable(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// Evaluate an ordering operator, rejecting operands of different types
+fn compare(op: &BinaryOp, left: &Value, right: &Value) -> Result<bool> {
+    left.check_ordered(right, &op.to_string())?;
+    Ok(match op {
+        BinaryOp::LessThan => left < right,
+        BinaryOp::LessEqual => left <= right,
+        BinaryOp::GreaterThan => left > right,
+        BinaryOp::GreaterEqual => left >= right,
+        _ => unreachable!(),
+    })
 }
@@ -1,4 +1,3 @@
-mod buildin;
 mod control;
 mod env;
 pub mod interpreter;
@@ -0,0 +1,519 @@
+use crate::{
+    buildin::BuiltinFn,
+    error::{Error, Result},
+};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::{Add, Div, Mul, Neg, Range, Sub},
+    rc::Rc,
+};
+
+/// Shared, mutable storage of an array value
+pub type Array<F> = Rc<RefCell<Vec<Value<F>>>>;
+
+/// Identifies an array by its storage, for walks that may reach it twice
+type ArrayPtr<F> = *const RefCell<Vec<Value<F>>>;
+
+/// A runtime value shared by both backends, which differ only in how they
+/// represent user functions: `F` is the tree-walker's or the VM's `Function`
+#[derive(Debug)]
+pub enum Value<F> {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    Array(Array<F>),
+    Function(Rc<F>),
+    BuiltinFunction {
+        name: String,
+        function: BuiltinFn<F>,
+    },
+    /// The numbers from `start` up to but not including `end`, `step` apart,
+    /// worked out on demand rather than stored
+    Range {
+        start: f64,
+        end: f64,
+        step: f64,
+    },
+    Nil,
+}
+
+// not derived, which would needlessly require `F: Clone`
+impl<F> Clone for Value<F> {
+    fn clone(&self) -> Self {
+        match self {
+            Value::Number(n) => Value::Number(*n),
+            Value::String(s) => Value::String(s.clone()),
+            Value::Boolean(b) => Value::Boolean(*b),
+            Value::Array(arr) => Value::Array(Rc::clone(arr)),
+            Value::Function(function) => Value::Function(Rc::clone(function)),
+            Value::BuiltinFunction { name, function } => Value::BuiltinFunction {
+                name: name.clone(),
+                function: *function,
+            },
+            Value::Range { start, end, step } => Value::Range {
+                start: *start,
+                end: *end,
+                step: *step,
+            },
+            Value::Nil => Value::Nil,
+        }
+    }
+}
+
+impl<F> Value<F> {
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Boolean(b) => *b,
+            Value::Nil => false,
+            Value::Number(n) => *n != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::Array(arr) => !arr.borrow().is_empty(),
+            Value::Function(_) => true,
+            Value::BuiltinFunction { .. } => true,
+            Value::Range { start, end, step } => range_len(*start, *end, *step) > 0,
+        }
+    }
+
+    /// Copy arrays recursively so the result shares no storage with `self`
+    pub fn deep_copy(&self) -> Self {
+        self.deep_copy_with(&mut HashMap::new())
+    }
+
+    /// Equality without the tolerance `==` allows numbers: they must match
+    /// exactly, so `nan` never equals itself, and arrays compare their
+    /// elements the same way
+    pub fn strict_eq(&self, other: &Self) -> bool {
+        self.strict_eq_with(other, &mut HashSet::new())
+    }
+
+    /// `copies` maps each array already copied to its copy, so an array
+    /// reached twice, or through itself, keeps that shape in the copy
+    fn deep_copy_with(&self, copies: &mut HashMap<ArrayPtr<F>, Array<F>>) -> Self {
+        let Value::Array(arr) = self else {
+            return self.clone();
+        };
+        if let Some(copy) = copies.get(&Rc::as_ptr(arr)) {
+            return Value::Array(Rc::clone(copy));
+        }
+
+        let copy = Rc::new(RefCell::new(Vec::new()));
+        copies.insert(Rc::as_ptr(arr), Rc::clone(&copy));
+        let elements = arr
+            .borrow()
+            .iter()
+            .map(|value| value.deep_copy_with(copies))
+            .collect();
+        *copy.borrow_mut() = elements;
+        Value::Array(copy)
+    }
+
+    /// `compared` holds the pairs of arrays met so far, so a pair reached
+    /// again, as through an array holding itself, is taken to match rather
+    /// than compared forever: a difference anywhere ends the comparison
+    /// where it first shows up
+    fn strict_eq_with(
+        &self,
+        other: &Self,
+        compared: &mut HashSet<(ArrayPtr<F>, ArrayPtr<F>)>,
+    ) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => {
+                if !compared.insert((Rc::as_ptr(a), Rc::as_ptr(b))) {
+                    return true;
+                }
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|(x, y)| x.strict_eq_with(y, compared))
+            }
+            _ => self == other,
+        }
+    }
+
+    /// The array `self` and the range of it `self[start:end]` covers, where
+    /// a nil bound stands for the start or end of the array
+    fn slice_range(&self, start: &Self, end: &Self) -> Result<(&Array<F>, Range<usize>)> {
+        let array = match self {
+            Value::Array(array) => array,
+            Value::Range { .. } => return Err(Error::range_unsupported("sliced")),
+            _ => return Err(Error::not_indexable(self.type_name())),
+        };
+        let length = array.borrow().len();
+        let bound = |bound: &Self, default| match bound {
+            Value::Nil => Ok(default),
+            Value::Number(n) => array_index(*n),
+            bound => Err(Error::non_number_index(bound.type_name())),
+        };
+        let (start, end) = (bound(start, 0)?, bound(end, length)?);
+        if start > end || end > length {
+            return Err(Error::slice_out_of_bounds(start, end, length));
+        }
+        Ok((array, start..end))
+    }
+
+    /// `self[start:end]`, copied into a new array
+    pub fn slice(&self, start: &Self, end: &Self) -> Result<Self> {
+        let (array, range) = self.slice_range(start, end)?;
+        let elements = array.borrow()[range].to_vec();
+        Ok(Value::from(elements))
+    }
+
+    /// Replace `self[start:end]` with the elements of the array `value`,
+    /// growing or shrinking `self` when their lengths differ
+    pub fn splice(&self, start: &Self, end: &Self, value: &Self) -> Result<()> {
+        if let Value::Range { .. } = self {
+            return Err(Error::range_unsupported("assigned to"));
+        }
+        let (array, range) = self.slice_range(start, end)?;
+        let Value::Array(replacement) = value else {
+            return Err(Error::type_error(
+                "slice assignment",
+                "array",
+                value.type_name(),
+            ));
+        };
+        // copied first, since the replacement may be the array itself
+        let elements = replacement.borrow().clone();
+        array.borrow_mut().splice(range, elements);
+        Ok(())
+    }
+
+    /// Element `index` of the array or range a `for`-`in` loops over, or
+    /// none past its end; the length is checked each time, as the loop body
+    /// may change an array
+    pub fn iteration_element(&self, index: usize) -> Result<Option<Self>> {
+        match self {
+            Value::Array(array) => Ok(array.borrow().get(index).cloned()),
+            Value::Range { start, end, step } => Ok((index < range_len(*start, *end, *step))
+                .then(|| Value::Number(start + index as f64 * step))),
+            _ => Err(Error::not_iterable(self.type_name())),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Array(_) => "array",
+            Value::Function(_) => "function",
+            Value::BuiltinFunction { .. } => "builtin_function",
+            Value::Range { .. } => "range",
+            Value::Nil => "nil",
+        }
+    }
+
+    /// Ordering with `<`, `<=`, `>` or `>=` is only defined between values of
+    /// the same type; anything else is an error rather than a silent `false`.
+    /// Arrays order by their elements, so each pair of elements compared, up
+    /// to the first that differ, is checked the same way
+    pub fn check_ordered(&self, other: &Self, op: &str) -> Result<()> {
+        match (self, other) {
+            (Value::Number(_), Value::Number(_))
+            | (Value::String(_), Value::String(_))
+            | (Value::Boolean(_), Value::Boolean(_)) => Ok(()),
+            (Value::Array(a), Value::Array(b)) => {
+                for (x, y) in a.borrow().iter().zip(b.borrow().iter()) {
+                    x.check_ordered(y, op)?;
+                    if x.partial_cmp(y) != Some(Ordering::Equal) {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            _ => Err(Error::runtime(format!(
+                "'{op}' not supported between '{}' and '{}'",
+                self.type_name(),
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+impl<F> Add for Value<F> {
+    type Output = Result<Self>;
+
+    fn add(self, other: Self) -> Self::Output {
+        let self_type = self.type_name();
+        let other_type = other.type_name();
+
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+            (Value::String(a), Value::String(b)) => Ok(Value::String(a + &b)),
+            (Value::Array(a), Value::Array(b)) => Ok(Value::Array(Rc::new(RefCell::new(
+                a.borrow()
+                    .iter()
+                    .chain(b.borrow().iter())
+                    .cloned()
+                    .collect(),
+            )))),
+            _ => Err(Error::runtime(format!(
+                "unsupported operand type(s) for +: '{self_type}' and '{other_type}'"
+            ))),
+        }
+    }
+}
+
+impl<F> Sub for Value<F> {
+    type Output = Result<Self>;
+
+    fn sub(self, other: Self) -> Self::Output {
+        let self_type = self.type_name();
+        let other_type = other.type_name();
+
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a - b)),
+            _ => Err(Error::runtime(format!(
+                "unsupported operand type(s) for -: '{self_type}' and '{other_type}'"
+            ))),
+        }
+    }
+}
+
+impl<F> Mul for Value<F> {
+    type Output = Result<Self>;
+
+    fn mul(self, other: Self) -> Self::Output {
+        let self_type = self.type_name();
+        let other_type = other.type_name();
+
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)),
+            _ => Err(Error::runtime(format!(
+                "unsupported operand type(s) for *: '{self_type}' and '{other_type}'"
+            ))),
+        }
+    }
+}
+
+impl<F> Div for Value<F> {
+    type Output = Result<Self>;
+
+    fn div(self, other: Self) -> Self::Output {
+        let self_type = self.type_name();
+        let other_type = other.type_name();
+
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a / b)),
+            _ => Err(Error::runtime(format!(
+                "unsupported operand type(s) for /: '{self_type}' and '{other_type}'"
+            ))),
+        }
+    }
+}
+
+/// How many numbers `range(start, end, step)` holds
+pub fn range_len(start: f64, end: f64, step: f64) -> usize {
+    let steps = ((end - start) / step).ceil();
+    if steps > 0.0 {
+        steps as usize
+    } else {
+        0
+    }
+}
+
+/// Convert a number to an array index, rejecting values `as usize` would
+/// silently truncate or saturate
+pub fn array_index(index: f64) -> Result<usize> {
+    if index < 0.0 || index.fract() != 0.0 {
+        Err(Error::runtime(format!(
+            "array index must be a non-negative integer, got {index}"
+        )))
+    } else if index >= usize::MAX as f64 {
+        Err(Error::runtime(format!(
+            "array index {index} is out of range"
+        )))
+    } else {
+        Ok(index as usize)
+    }
+}
+
+/// Whether `a == b` holds for numbers: within one `f64::EPSILON` of the
+/// larger magnitude, so rounding error such as `0.1 + 0.2` against `0.3`
+/// is forgiven at any scale, while near zero only equal numbers match
+fn numbers_equal(a: f64, b: f64) -> bool {
+    a == b
+        || (a.is_finite() && b.is_finite() && (a - b).abs() <= f64::EPSILON * a.abs().max(b.abs()))
+}
+
+/// Arrays and ranges compare by contents, functions by identity
+impl<F> PartialEq for Value<F> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => numbers_equal(*a, *b),
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => *a.borrow() == *b.borrow(),
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::BuiltinFunction { name: a, .. }, Value::BuiltinFunction { name: b, .. }) => {
+                a == b
+            }
+            (
+                Value::Range {
+                    start: a_start,
+                    end: a_end,
+                    step: a_step,
+                },
+                Value::Range {
+                    start: b_start,
+                    end: b_end,
+                    step: b_step,
+                },
+            ) => {
+                let length = range_len(*a_start, *a_end, *a_step);
+                length == range_len(*b_start, *b_end, *b_step)
+                    && (length == 0 || a_start == b_start)
+                    && (length <= 1 || a_step == b_step)
+            }
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<F> PartialOrd for Value<F> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
+            (Value::Array(a), Value::Array(b)) => a.borrow().partial_cmp(&b.borrow()),
+            _ => None,
+        }
+    }
+}
+
+impl<F> Neg for Value<F> {
+    type Output = Result<Self>;
+
+    fn neg(self) -> Self::Output {
+        let self_type = self.type_name();
+        match self {
+            Value::Number(n) => Ok(Value::Number(-n)),
+            _ => Err(Error::runtime(format!(
+                "bad operand type for unary -: '{self_type}'"
+            ))),
+        }
+    }
+}
+
+/// Conversions for embedders passing Rust values in and out
+impl<F> From<f64> for Value<F> {
+    fn from(value: f64) -> Self {
+        Value::Number(value)
+    }
+}
+
+impl<F> From<bool> for Value<F> {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl<F> From<&str> for Value<F> {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl<F> From<String> for Value<F> {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl<F> From<Vec<Value<F>>> for Value<F> {
+    fn from(values: Vec<Value<F>>) -> Self {
+        Value::Array(Rc::new(RefCell::new(values)))
+    }
+}
+
+fn conversion_error<F>(expected: &str, value: &Value<F>) -> Error {
+    Error::runtime(format!(
+        "expected {expected}, found '{}'",
+        value.type_name()
+    ))
+}
+
+impl<F> TryFrom<Value<F>> for f64 {
+    type Error = Error;
+
+    fn try_from(value: Value<F>) -> Result<Self> {
+        match value {
+            Value::Number(n) => Ok(n),
+            _ => Err(conversion_error("number", &value)),
+        }
+    }
+}
+
+impl<F> TryFrom<Value<F>> for bool {
+    type Error = Error;
+
+    fn try_from(value: Value<F>) -> Result<Self> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(conversion_error("boolean", &value)),
+        }
+    }
+}
+
+impl<F> TryFrom<Value<F>> for String {
+    type Error = Error;
+
+    fn try_from(value: Value<F>) -> Result<Self> {
+        match value {
+            Value::String(s) => Ok(s),
+            _ => Err(conversion_error("string", &value)),
+        }
+    }
+}
+
+/// Copies the elements out; the array itself stays shared with other holders
+impl<F> TryFrom<Value<F>> for Vec<Value<F>> {
+    type Error = Error;
+
+    fn try_from(value: Value<F>) -> Result<Self> {
+        match value {
+            Value::Array(arr) => Ok(arr.borrow().clone()),
+            _ => Err(conversion_error("array", &value)),
+        }
+    }
+}
+
+/// Numbers print without a trailing `.0`; `-0` prints as `0`, and the
+/// non-finite values as `inf`, `-inf` and `nan`
+impl<F: fmt::Display> fmt::Display for Value<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) if *n == 0.0 => write!(f, "0"),
+            Value::Number(n) if n.is_nan() => write!(f, "nan"),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Array(arr) => {
+                write!(f, "[")?;
+                for (i, val) in arr.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", val)?;
+                }
+                write!(f, "]")
+            }
+            Value::Function(function) => write!(f, "{}", function),
+            Value::BuiltinFunction { name, .. } => {
+                write!(f, "<builtin function {}>", name)
+            }
+            Value::Range { start, end, step } if *step == 1.0 => {
+                write!(f, "range({start}, {end})")
+            }
+            Value::Range { start, end, step } => write!(f, "range({start}, {end}, {step})"),
+            Value::Nil => write!(f, "nil"),
+        }
+    }
+}
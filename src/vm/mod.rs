@@ -1,4 +1,5 @@
 mod stack;
+#[allow(clippy::module_inception)]
 pub mod vm;
 
-pub use vm::VM;
+pub use vm::{StepResult, VM};
@@ -30,6 +30,11 @@ impl CallStack {
         self.frames.pop()
     }
 
+    /// Number of active calls
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
     pub fn offset(&self) -> usize {
         self.frames
             .last()
@@ -1,23 +1,57 @@
 use super::stack::{CallFrame, CallStack};
 use crate::{
-    compiler::{Chunk, Function, OpCode, Value, BUILTIN_FUNCTIONS},
+    buildin::{file_functions, Caller},
+    compiler::{
+        value::{Capture, Upvalue},
+        Chunk, Function, OpCode, Value,
+    },
     constant::STACK_SIZE,
-    error::{Error, Result},
+    error::{Error, ErrorType, Result},
+    value::{array_index, range_len},
 };
-use std::{cell::RefCell, collections::HashMap, io::Write, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io::Write,
+    rc::Rc,
+};
+
+/// Outcome of executing a single instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// More instructions remain to be executed
+    Continue,
+    /// The end of the program has been reached
+    Halted,
+    /// Execution stopped before an instruction on a breakpoint line
+    Breakpoint,
+}
 
 pub struct VM {
     chunk: Chunk,
     ip: usize,
     stack: Vec<Value>,
     globals: HashMap<String, Value>,
+    /// Globals that bytecode may read but never assign or redefine
+    constants: HashSet<String>,
     call_stack: CallStack,
+    /// Captures still pointing into the stack, by ascending slot
+    open_upvalues: Vec<Upvalue>,
     output: Box<dyn Write>,
+    error_output: Box<dyn Write>,
+    breakpoints: HashSet<usize>,
+    last_line: Option<usize>,
+    paused_at: Option<usize>,
+    instruction_limit: Option<usize>,
+    instruction_count: usize,
+    /// Whether the chunk has passed `Chunk::validate`, which it must before
+    /// any of it runs
+    validated: bool,
 }
 
 impl VM {
     pub fn new(chunk: Chunk) -> Self {
-        let globals = BUILTIN_FUNCTIONS
+        let globals = Value::BUILTIN_FUNCTIONS
             .iter()
             .map(|(name, func)| {
                 (
@@ -34,8 +68,17 @@ impl VM {
             ip: 0,
             stack: Vec::with_capacity(STACK_SIZE),
             globals,
+            constants: HashSet::new(),
             call_stack: CallStack::new(),
+            open_upvalues: Vec::new(),
             output: Box::new(std::io::stdout()),
+            error_output: Box::new(std::io::stderr()),
+            breakpoints: HashSet::new(),
+            last_line: None,
+            paused_at: None,
+            instruction_limit: None,
+            instruction_count: 0,
+            validated: false,
         }
     }
 
@@ -46,23 +89,155 @@ impl VM {
         }
     }
 
-    pub fn run(&mut self) -> Result<()> {
+    /// Redirect `eprint` output, which goes to stderr by default
+    pub fn set_error_output(&mut self, error_output: Box<dyn Write>) {
+        self.error_output = error_output;
+    }
+
+    /// Let scripts read and write files with `read_file` and `write_file`,
+    /// which fail with a runtime error until enabled
+    pub fn set_file_access(&mut self, enabled: bool) {
+        for (name, function) in file_functions(enabled) {
+            let builtin = Value::BuiltinFunction {
+                name: name.to_string(),
+                function,
+            };
+            self.globals.insert(name.to_string(), builtin);
+        }
+    }
+
+    /// Define a global the program can read but not change; assigning or
+    /// redefining it is a runtime error, even from hand-written bytecode the
+    /// compiler never checked
+    pub fn define_constant(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.to_string(), value);
+        self.constants.insert(name.to_string());
+    }
+
+    pub fn chunk(&self) -> &Chunk {
+        &self.chunk
+    }
+
+    /// Swap in `chunk`, which extends the current one with more code, and
+    /// carry on from where the old code ended. Globals and functions defined
+    /// so far are kept, while whatever a failed run left on the stack is not
+    pub fn extend_chunk(&mut self, chunk: Chunk) {
+        self.ip = self.chunk.current_ip();
+        self.close_upvalues(0);
+        self.stack.clear();
+        self.call_stack = CallStack::new();
+        self.paused_at = None;
+        self.instruction_count = 0;
+        self.chunk = chunk;
+        self.validated = false;
+    }
+
+    /// Run until the program halts or reaches a breakpoint; calling `run` again resumes
+    pub fn run(&mut self) -> Result<StepResult> {
         loop {
-            if self.ip >= self.chunk.current_ip() {
-                break;
+            if self.should_pause() {
+                return Ok(StepResult::Breakpoint);
+            }
+            if self.step()? == StepResult::Halted {
+                return Ok(StepResult::Halted);
             }
+        }
+    }
+
+    /// Pause whenever execution enters `line` from a different line
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    pub fn clear_breakpoint(&mut self, line: usize) {
+        self.breakpoints.remove(&line);
+    }
+
+    /// Abort with a runtime error once more than `limit` instructions have
+    /// run from now on, so untrusted scripts cannot loop forever; unlimited
+    /// by default, and again after setting `None`
+    pub fn set_instruction_limit(&mut self, limit: Option<usize>) {
+        self.instruction_limit = limit;
+        self.instruction_count = 0;
+    }
 
-            let location = self.chunk.location_at(self.ip);
-            let instruction = OpCode::try_from(self.read_byte()?)?;
-            self.run_instruction(instruction)
-                .map_err(|e| e.at_location(location))?;
+    /// Execute exactly one instruction
+    pub fn step(&mut self) -> Result<StepResult> {
+        if !self.validated {
+            self.chunk.validate()?;
+            self.validated = true;
         }
-        Ok(())
+        if self.is_at_end() {
+            return Ok(StepResult::Halted);
+        }
+
+        let location = self.chunk.location_at(self.ip);
+        self.instruction_count += 1;
+        if let Some(limit) = self.instruction_limit {
+            if self.instruction_count > limit {
+                return Err(
+                    Error::vm_runtime(format!("instruction limit of {limit} exceeded"))
+                        .at_location(location),
+                );
+            }
+        }
+        self.last_line = Some(location.line);
+        let ip = self.ip;
+        let instruction = OpCode::try_from(self.read_byte()?)?;
+        self.run_instruction(instruction).map_err(|e| {
+            // an error with a location came from a nested step, such as one
+            // running a callback a builtin called, which already described it
+            if e.location.is_some() {
+                return e;
+            }
+            // well-formed bytecode never underflows, so point at the culprit
+            let e = if e.error_type == ErrorType::StackUnderflow {
+                e.with_opcode(instruction).with_ip(ip)
+            } else {
+                e
+            };
+            e.or_at_location(location)
+        })?;
+
+        if self.is_at_end() {
+            Ok(StepResult::Halted)
+        } else {
+            Ok(StepResult::Continue)
+        }
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
     }
 }
 
 /// Utility functions
 impl VM {
+    fn is_at_end(&self) -> bool {
+        self.ip >= self.chunk.current_ip()
+    }
+
+    fn should_pause(&mut self) -> bool {
+        if self.is_at_end() || self.breakpoints.is_empty() {
+            return false;
+        }
+        // resuming from this exact instruction must not pause again
+        if self.paused_at.take() == Some(self.ip) {
+            return false;
+        }
+
+        let line = self.chunk.location_at(self.ip).line;
+        if self.last_line != Some(line) && self.breakpoints.contains(&line) {
+            self.paused_at = Some(self.ip);
+            return true;
+        }
+        false
+    }
+
     fn read_byte(&mut self) -> Result<u8> {
         let byte = self
             .chunk
@@ -102,6 +277,13 @@ impl VM {
     }
 
     fn pop(&mut self) -> Result<Value> {
+        // a popped local outlives its slot in any closure that captured it
+        let top = self
+            .stack
+            .len()
+            .checked_sub(1)
+            .ok_or(Error::stack_underflow())?;
+        self.close_upvalues(top);
         self.stack.pop().ok_or(Error::stack_underflow())
     }
 
@@ -143,7 +325,7 @@ impl VM {
             OpCode::DefineGlobal => {
                 let name = self.read_global_name()?;
                 let value = self.pop()?;
-                self.define_global(name, value);
+                self.define_global(name, value)?;
             }
             OpCode::GetGlobal => {
                 let name = self.read_global_name()?;
@@ -189,17 +371,49 @@ impl VM {
                 let offset = self.read_short()? as usize;
                 self.ip -= offset;
             }
+            OpCode::ForIter => {
+                // the iterable and the index of its next element are the
+                // two locals on top of the stack
+                let offset = self.read_short()? as usize;
+                let top = self.stack.len();
+                let (Some(iterable), Some(Value::Number(index))) = (
+                    top.checked_sub(2).map(|i| &self.stack[i]),
+                    self.stack.last(),
+                ) else {
+                    return Err(Error::stack_underflow());
+                };
+                let index = *index as usize;
+                match iterable.iteration_element(index)? {
+                    Some(element) => {
+                        self.stack[top - 1] = Value::Number((index + 1) as f64);
+                        self.push(element);
+                    }
+                    None => self.ip += offset,
+                }
+            }
+            OpCode::CheckCount => {
+                let count = self.peek()?;
+                if !matches!(count, Value::Number(n) if n >= 0.0 && n.fract() == 0.0) {
+                    return Err(Error::vm_runtime(format!(
+                        "repeat count must be a non-negative integer, got {count}"
+                    )));
+                }
+            }
 
             // Functions
             OpCode::Call => {
+                let call_ip = self.ip - 1;
                 let arg_count = self.read_byte()? as usize;
                 let callee = self.pop()?;
-                self.call_value(callee, arg_count)?;
+                self.call_value(callee, arg_count, Some(call_ip))?;
             }
             OpCode::Return => {
                 let result = self.pop()?;
                 if let Some(frame) = self.call_stack.pop() {
                     self.ip = frame.ip;
+                    // coming back to the caller's line is not entering it
+                    self.last_line = Some(self.chunk.location_at(frame.ip).line);
+                    self.close_upvalues(frame.slots_offset);
                     self.stack.truncate(frame.slots_offset);
                     self.push(result);
                 } else {
@@ -213,12 +427,45 @@ impl VM {
             }
             OpCode::Print => {
                 let count = self.read_byte()? as usize;
-                self.print_values(count)?;
+                let line = self.format_values(count)?;
+                writeln!(self.output, "{line}")?;
+            }
+            OpCode::EPrint => {
+                let count = self.read_byte()? as usize;
+                let line = self.format_values(count)?;
+                writeln!(self.error_output, "{line}")?;
+            }
+            OpCode::PrintWith => {
+                let count = self.read_byte()? as usize;
+                let text = self.format_values_with(count)?;
+                write!(self.output, "{text}")?;
             }
+            OpCode::EPrintWith => {
+                let count = self.read_byte()? as usize;
+                let text = self.format_values_with(count)?;
+                write!(self.error_output, "{text}")?;
+            }
+            OpCode::Nop => {}
             OpCode::Dup => {
                 let value = self.peek()?;
                 self.push(value);
             }
+            OpCode::Swap => {
+                let top = self.pop()?;
+                let below = self.pop()?;
+                self.push(top);
+                self.push(below);
+            }
+            OpCode::Over => {
+                let below = self
+                    .stack
+                    .len()
+                    .checked_sub(2)
+                    .and_then(|index| self.stack.get(index))
+                    .cloned()
+                    .ok_or(Error::stack_underflow())?;
+                self.push(below);
+            }
 
             // Arrays
             OpCode::Array => {
@@ -227,22 +474,44 @@ impl VM {
                 self.push(array);
             }
             OpCode::Index => {
+                let index_ip = self.ip - 1;
                 let index = self.pop()?;
                 let array = self.pop()?;
-                self.index_array(index, array)?;
+                self.index_array(index, array, index_ip)?;
             }
             OpCode::IndexSet => {
+                let index_ip = self.ip - 1;
                 let value = self.pop()?;
                 let index = self.pop()?;
                 let array = self.pop()?;
-                self.set_array_element(value, index, array)?;
+                self.set_array_element(value, index, array, index_ip)?;
+            }
+            OpCode::Slice => {
+                let end = self.pop()?;
+                let start = self.pop()?;
+                let array = self.pop()?;
+                let slice = array.slice(&start, &end)?;
+                self.push(slice);
+            }
+            OpCode::SliceSet => {
+                let value = self.pop()?;
+                let end = self.pop()?;
+                let start = self.pop()?;
+                let array = self.pop()?;
+                array.splice(&start, &end, &value)?;
+                self.push(value);
+            }
+            OpCode::Unpack => {
+                let count = self.read_byte()? as usize;
+                let array = self.pop()?;
+                self.unpack_array(array, count)?;
             }
 
             // Closures and Upvalues
             OpCode::Closure => {
-                let proto = self.read_constant()?;
+                let proto_index = self.read_byte()? as usize;
                 let upvalue_count = self.read_byte()? as usize;
-                self.create_closure(proto, upvalue_count)?;
+                self.create_closure(proto_index, upvalue_count)?;
             }
             OpCode::GetUpvalue => {
                 let upvalue_index = self.read_byte()? as usize;
@@ -265,10 +534,22 @@ impl VM {
             OpCode::Divide => self.push((left / right)?),
             OpCode::Equal => self.push(Value::Boolean(left == right)),
             OpCode::NotEqual => self.push(Value::Boolean(left != right)),
-            OpCode::LessThan => self.push(Value::Boolean(left < right)),
-            OpCode::LessEqual => self.push(Value::Boolean(left <= right)),
-            OpCode::GreaterThan => self.push(Value::Boolean(left > right)),
-            OpCode::GreaterEqual => self.push(Value::Boolean(left >= right)),
+            OpCode::LessThan => {
+                left.check_ordered(&right, "<")?;
+                self.push(Value::Boolean(left < right))
+            }
+            OpCode::LessEqual => {
+                left.check_ordered(&right, "<=")?;
+                self.push(Value::Boolean(left <= right))
+            }
+            OpCode::GreaterThan => {
+                left.check_ordered(&right, ">")?;
+                self.push(Value::Boolean(left > right))
+            }
+            OpCode::GreaterEqual => {
+                left.check_ordered(&right, ">=")?;
+                self.push(Value::Boolean(left >= right))
+            }
             _ => return Err(Error::invalid_opcode(op as u8)),
         }
         Ok(())
@@ -284,7 +565,13 @@ impl VM {
         Ok(())
     }
 
-    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<()> {
+    /// `call_ip` is the ip of the `Call` op, if the call comes from one
+    fn call_value(
+        &mut self,
+        callee: Value,
+        arg_count: usize,
+        call_ip: Option<usize>,
+    ) -> Result<()> {
         match callee {
             Value::Function(function) => {
                 if function.arity() != arg_count {
@@ -312,12 +599,13 @@ impl VM {
                     .rev()
                     .collect();
 
-                let result = function(&args)?;
+                let result = function(&args, self)?;
                 self.push(result);
                 Ok(())
             }
-            _ => Err(Error::runtime(
-                "Can only call functions and closures".to_string(),
+            _ => Err(Error::not_callable(
+                call_ip.and_then(|ip| self.chunk.operand_name(ip)),
+                callee.type_name(),
             )),
         }
     }
@@ -330,16 +618,24 @@ impl VM {
     }
 
     fn set_global(&mut self, name: String, value: Value) -> Result<()> {
-        if self.globals.contains_key(&name) {
-            self.globals.insert(name, value);
-            Ok(())
-        } else {
-            Err(Error::undefined_variable(&name))
+        if self.constants.contains(&name) {
+            return Err(Error::assign_to_constant(&name));
+        }
+        match self.globals.get_mut(&name) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err(Error::undefined_variable(&name)),
         }
     }
 
-    fn define_global(&mut self, name: String, value: Value) {
+    fn define_global(&mut self, name: String, value: Value) -> Result<()> {
+        if self.constants.contains(&name) {
+            return Err(Error::assign_to_constant(&name));
+        }
         self.globals.insert(name, value);
+        Ok(())
     }
 
     fn get_local(&self, slot: usize) -> Result<Value> {
@@ -369,71 +665,127 @@ impl VM {
         Ok(Value::Array(Rc::new(RefCell::new(elements))))
     }
 
-    fn index_array(&mut self, index: Value, array: Value) -> Result<()> {
+    fn index_array(&mut self, index: Value, array: Value, index_ip: usize) -> Result<()> {
         match (&array, &index) {
             (Value::Array(arr), Value::Number(idx)) => {
-                let idx = *idx as usize;
+                let idx = array_index(*idx)?;
                 let value = arr
                     .borrow()
                     .get(idx)
-                    .ok_or(Error::index_out_of_bounds(idx, arr.borrow().len()))?
+                    .ok_or_else(|| {
+                        Error::index_out_of_bounds(
+                            idx,
+                            arr.borrow().len(),
+                            self.chunk.operand_name(index_ip),
+                        )
+                    })?
                     .clone();
                 self.push(value);
                 Ok(())
             }
-            (Value::Array(_), _) => Err(Error::runtime("array index must be a number".to_string())),
-            _ => Err(Error::runtime("can only index arrays".to_string())),
+            (Value::Range { start, end, step }, Value::Number(idx)) => {
+                let idx = array_index(*idx)?;
+                let length = range_len(*start, *end, *step);
+                if idx >= length {
+                    return Err(Error::index_out_of_bounds(
+                        idx,
+                        length,
+                        self.chunk.operand_name(index_ip),
+                    ));
+                }
+                self.push(Value::Number(start + idx as f64 * step));
+                Ok(())
+            }
+            (Value::Array(_) | Value::Range { .. }, index) => {
+                Err(Error::non_number_index(index.type_name()))
+            }
+            (array, _) => Err(Error::not_indexable(array.type_name())),
         }
     }
 
-    fn set_array_element(&mut self, value: Value, index: Value, array: Value) -> Result<()> {
+    fn set_array_element(
+        &mut self,
+        value: Value,
+        index: Value,
+        array: Value,
+        index_ip: usize,
+    ) -> Result<()> {
         match (&array, &index) {
             (Value::Array(arr), Value::Number(idx)) => {
-                let idx = *idx as usize;
-                if let Some(target) = arr.borrow_mut().get_mut(idx) {
-                    *target = value.clone();
-                    self.push(value);
-                } else {
-                    return Err(Error::index_out_of_bounds(idx, arr.borrow().len()));
+                let idx = array_index(*idx)?;
+                let length = arr.borrow().len();
+                match arr.borrow_mut().get_mut(idx) {
+                    Some(target) => *target = value.clone(),
+                    None => {
+                        return Err(Error::index_out_of_bounds(
+                            idx,
+                            length,
+                            self.chunk.operand_name(index_ip),
+                        ))
+                    }
                 }
+                self.push(value);
                 Ok(())
             }
-            (Value::Array(_), _) => Err(Error::runtime("array index must be a number".to_string())),
-            _ => Err(Error::runtime("can only index arrays".to_string())),
+            (Value::Range { .. }, _) => Err(Error::range_unsupported("assigned to")),
+            (Value::Array(_), index) => Err(Error::non_number_index(index.type_name())),
+            (array, _) => Err(Error::not_indexable(array.type_name())),
         }
     }
 
-    fn print_values(&mut self, count: usize) -> Result<()> {
-        let output = (0..count)
+    fn unpack_array(&mut self, array: Value, count: usize) -> Result<()> {
+        let Value::Array(arr) = array else {
+            return Err(Error::destructure_non_array(array.type_name()));
+        };
+
+        let elements = arr.borrow();
+        if elements.len() != count {
+            return Err(Error::destructure_length(elements.len(), count));
+        }
+
+        for element in elements.iter() {
+            self.stack.push(element.clone());
+        }
+        Ok(())
+    }
+
+    /// Pop `count` values and join them for printing
+    fn format_values(&mut self, count: usize) -> Result<String> {
+        self.join_values(count, " ")
+    }
+
+    /// Pop the terminator, the separator and then `count` values, and join
+    /// the values with the separator, followed by the terminator
+    fn format_values_with(&mut self, count: usize) -> Result<String> {
+        let end = self.pop()?.to_string();
+        let sep = self.pop()?.to_string();
+        Ok(self.join_values(count, &sep)? + &end)
+    }
+
+    fn join_values(&mut self, count: usize, sep: &str) -> Result<String> {
+        Ok((0..count)
             .map(|_| self.pop())
             .collect::<Result<Vec<_>>>()?
             .into_iter()
             .rev()
             .map(|value| value.to_string())
             .collect::<Vec<_>>()
-            .join(" ");
-
-        writeln!(self.output, "{output}")?;
-        Ok(())
+            .join(sep))
     }
 
-    fn create_closure(&mut self, proto: Value, upvalue_count: usize) -> Result<()> {
-        let proto = match proto {
-            Value::Proto(proto) => proto,
-            _ => {
-                return Err(Error::runtime(
-                    "Expected function in closure creation".to_string(),
-                ))
-            }
-        };
+    fn create_closure(&mut self, proto_index: usize, upvalue_count: usize) -> Result<()> {
+        let proto = self
+            .chunk
+            .proto(proto_index)
+            .cloned()
+            .ok_or_else(|| Error::vm_runtime(format!("invalid proto index: {proto_index}")))?;
 
         let upvalues = (0..upvalue_count)
             .map(|_| {
                 let is_local = self.read_byte()? == 1;
                 let index = self.read_byte()? as usize;
                 if is_local {
-                    let value = self.get_local(index)?;
-                    Ok(Value::new_upvalue(value.clone()))
+                    Ok(self.capture_local(self.call_stack.offset() + index))
                 } else {
                     self.call_stack.get_upvalue(index).cloned()
                 }
@@ -446,16 +798,88 @@ impl VM {
         Ok(())
     }
 
+    /// The capture of the local in `slot`, shared with earlier closures
+    /// capturing the same local
+    fn capture_local(&mut self, slot: usize) -> Upvalue {
+        let position = self
+            .open_upvalues
+            .iter()
+            .position(|upvalue| matches!(*upvalue.borrow(), Capture::Open(open) if open >= slot));
+        let position = match position {
+            Some(position) => position,
+            None => self.open_upvalues.len(),
+        };
+        if let Some(upvalue) = self.open_upvalues.get(position) {
+            if matches!(*upvalue.borrow(), Capture::Open(open) if open == slot) {
+                return Rc::clone(upvalue);
+            }
+        }
+
+        let upvalue = Rc::new(RefCell::new(Capture::Open(slot)));
+        self.open_upvalues.insert(position, Rc::clone(&upvalue));
+        upvalue
+    }
+
+    /// Close every capture of a slot at or above `slot`, before those slots
+    /// are discarded
+    fn close_upvalues(&mut self, slot: usize) {
+        while let Some(upvalue) = self.open_upvalues.last() {
+            let Capture::Open(open) = *upvalue.borrow() else {
+                unreachable!("closed upvalues are never left open");
+            };
+            if open < slot {
+                break;
+            }
+            let value = self.stack.get(open).cloned().unwrap_or(Value::Nil);
+            *upvalue.borrow_mut() = Capture::Closed(value);
+            self.open_upvalues.pop();
+        }
+    }
+
     fn get_upvalue(&mut self, upvalue_index: usize) -> Result<()> {
         let upvalue = self.call_stack.get_upvalue(upvalue_index)?;
-        let value = upvalue.borrow().clone();
+        let value = match &*upvalue.borrow() {
+            Capture::Open(slot) => self.stack[*slot].clone(),
+            Capture::Closed(value) => value.clone(),
+        };
         self.push(value);
         Ok(())
     }
 
     fn set_upvalue(&mut self, upvalue_index: usize, value: Value) -> Result<()> {
         let upvalue = self.call_stack.get_upvalue(upvalue_index)?;
-        *upvalue.borrow_mut() = value;
+        let mut capture = upvalue.borrow_mut();
+        match &mut *capture {
+            Capture::Open(slot) => self.stack[*slot] = value,
+            Capture::Closed(captured) => *captured = value,
+        }
         Ok(())
     }
 }
+
+/// Runs a called function to completion before the calling builtin resumes
+impl Caller<Function> for VM {
+    fn call(&mut self, function: &Value, args: Vec<Value>) -> Result<Value> {
+        let depth = self.call_stack.depth();
+        let arg_count = args.len();
+        for arg in args {
+            self.push(arg);
+        }
+        self.call_value(function.clone(), arg_count, None)?;
+        while self.call_stack.depth() > depth {
+            self.step()?;
+        }
+        self.pop()
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        // a builtin runs within the two-byte `Call` right before the ip,
+        // whether called directly or handed to another, such as `find`
+        let call_ip = self.ip.saturating_sub(2);
+        self.chunk
+            .local_names(call_ip)
+            .iter()
+            .any(|local| local == name)
+            || self.globals.contains_key(name)
+    }
+}
@@ -0,0 +1,228 @@
+use mylang::{
+    compile, compiler::Chunk, error::ErrorType, lexer::TokenType, parse, parser::Stmt,
+    run_reader_with_vm, run_with_tr, run_with_vm, run_with_vm_timed, tokenize,
+    treewalk::Interpreter, vm::VM,
+};
+use std::{
+    io::{self, Cursor},
+    rc::Rc,
+    time::Duration,
+};
+
+#[test]
+fn test_tokenize() {
+    let tokens = tokenize("let x = 1;").unwrap();
+    assert_eq!(tokens.len(), 6);
+    assert_eq!(tokens[0].token_type, TokenType::Let);
+    assert_eq!(tokens.last().unwrap().token_type, TokenType::Eof);
+}
+
+#[test]
+fn test_parse() {
+    let stmts = parse("let x = 1;\nprint x;").unwrap();
+    assert_eq!(stmts.len(), 2);
+    assert!(matches!(stmts[1].as_inner(), Stmt::Print(_)));
+    assert_eq!(stmts[1].location().line, 2);
+}
+
+#[test]
+fn test_compile() {
+    let chunk = compile("print 1 + 2;").unwrap();
+    assert!(chunk.current_ip() > 0);
+    assert!(chunk.decode_at(0).is_some());
+}
+
+#[test]
+fn test_stage_errors() {
+    let lexical = tokenize("let x = @;").unwrap_err();
+    assert_eq!(lexical.error_type, ErrorType::Lexical);
+    let syntax = parse("let = 1;").unwrap_err();
+    assert_eq!(syntax.error_type, ErrorType::Syntax);
+    assert!(compile("1(2);").unwrap_err().is_compilation());
+}
+
+#[test]
+fn test_value_conversions_round_trip() {
+    use mylang::compiler::Value;
+
+    assert_eq!(f64::try_from(Value::from(5.0)).unwrap(), 5.0);
+    assert!(bool::try_from(Value::from(true)).unwrap());
+    assert_eq!(String::try_from(Value::from("s")).unwrap(), "s");
+    assert_eq!(String::try_from(Value::from("t".to_string())).unwrap(), "t");
+
+    let array = Value::from(vec![Value::from(1.0), Value::from("two")]);
+    assert_eq!(array.to_string(), "[1, two]");
+    let elements = Vec::<Value>::try_from(array).unwrap();
+    assert_eq!(
+        elements,
+        vec![Value::Number(1.0), Value::String("two".to_string())]
+    );
+
+    let error = f64::try_from(Value::from("5")).unwrap_err();
+    assert_eq!(error.message, "expected number, found 'string'");
+}
+
+#[test]
+fn test_treewalk_value_conversions_round_trip() {
+    use mylang::treewalk::Value;
+
+    assert_eq!(f64::try_from(Value::from(-1.5)).unwrap(), -1.5);
+    assert!(!bool::try_from(Value::from(false)).unwrap());
+    assert_eq!(String::try_from(Value::from("s")).unwrap(), "s");
+    let elements = Vec::<Value>::try_from(Value::from(vec![Value::Nil])).unwrap();
+    assert_eq!(elements, vec![Value::Nil]);
+
+    let error = bool::try_from(Value::Nil).unwrap_err();
+    assert_eq!(error.message, "expected boolean, found 'nil'");
+}
+
+#[test]
+fn test_deep_copy_keeps_cycles_and_sharing() {
+    use mylang::treewalk::Value;
+
+    let Value::Array(shared) = Value::from(vec![Value::from(1.0)]) else {
+        unreachable!()
+    };
+    let Value::Array(outer) = Value::from(vec![]) else {
+        unreachable!()
+    };
+    outer.borrow_mut().extend([
+        Value::Array(shared.clone()),
+        Value::Array(shared.clone()),
+        Value::Array(outer.clone()),
+    ]);
+
+    let Value::Array(copy) = Value::Array(outer.clone()).deep_copy() else {
+        panic!("expected an array");
+    };
+    let elements = copy.borrow();
+    let [Value::Array(first), Value::Array(second), Value::Array(this)] = &elements[..] else {
+        panic!("expected three arrays");
+    };
+    assert!(Rc::ptr_eq(first, second), "shared arrays stay shared");
+    assert!(!Rc::ptr_eq(first, &shared), "but are copies");
+    assert!(Rc::ptr_eq(this, &copy), "the cycle points at the copy");
+
+    // break the cycles so the arrays can be freed
+    outer.borrow_mut().clear();
+    drop(elements);
+    copy.borrow_mut().clear();
+}
+
+#[test]
+fn test_truncated_programs_are_errors_not_panics() {
+    let source = include_str!("../examples/complex_closures.myl");
+    for (end, _) in source.char_indices() {
+        // every prefix either compiles or fails with an error
+        let _ = compile(&source[..end]);
+    }
+}
+
+#[test]
+fn test_oversized_programs_are_errors() {
+    let constants: String = (0..300).map(|i| format!("print {i};\n")).collect();
+    assert_eq!(
+        compile(&constants).unwrap_err().message,
+        "too many constants in one script (at most 256)"
+    );
+
+    let globals: String = (0..300).map(|i| format!("let g{i};\n")).collect();
+    assert_eq!(
+        compile(&globals).unwrap_err().message,
+        "too many global variables (at most 256)"
+    );
+
+    let values = vec!["nil"; 300].join(", ");
+    assert_eq!(
+        compile(&format!("print {values};")).unwrap_err().message,
+        "too many values to print (at most 255)"
+    );
+}
+
+#[test]
+fn test_deeply_nested_programs_are_errors() {
+    let nested = |depth: usize| format!("print {}1{};", "(".repeat(depth), ")".repeat(depth));
+    assert!(compile(&nested(60)).is_ok());
+    let error = parse(&nested(5000)).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::Syntax);
+    assert_eq!(
+        error.message,
+        "code is nested too deeply (at most 64 levels)"
+    );
+
+    // chains are walked in a loop, so they do not count as nesting, but
+    // their length is still capped
+    let chain = vec!["1"; 5000].join(" + ");
+    let error = parse(&format!("print {chain};")).unwrap_err();
+    assert_eq!(
+        error.message,
+        "chain of operators is too long (at most 512)"
+    );
+    let grouped = vec!["1"; 300].join(" + ");
+    assert!(parse(&format!("print ({grouped}) + {grouped};")).is_err());
+    assert!(parse(&format!("print {}1;", "-".repeat(5000))).is_err());
+    assert!(parse(&format!("print f{};", "()".repeat(5000))).is_err());
+
+    // every `elif` or `else if` nests the rest of the chain one level deeper
+    let arms = |count: usize, link: &str| {
+        let arm = format!(" {link} x == 1 {{ print 1; }}");
+        format!("let x = 0;\nif x == 0 {{ print 0; }}{}", arm.repeat(count))
+    };
+    for link in ["elif", "else if"] {
+        assert!(compile(&arms(40, link)).is_ok());
+        let error = parse(&arms(2000, link)).unwrap_err();
+        assert_eq!(
+            error.message,
+            "code is nested too deeply (at most 64 levels)"
+        );
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        assert!(run_with_tr(arms(2000, link), &mut interpreter).is_err());
+        assert!(run_with_vm(arms(2000, link)).is_err());
+    }
+}
+
+#[test]
+fn test_timed_run_reports_every_phase() {
+    let mut vm = VM::with_output(Chunk::new(), Box::new(io::sink()));
+    let source = "fn main() { let total = 0; repeat 100 { total += 1; } print total; }";
+    let times = run_with_vm_timed(source, true, &mut vm).unwrap();
+    for (phase, time) in [
+        ("lex", times.lex),
+        ("parse", times.parse),
+        ("compile", times.compile),
+        ("run", times.run),
+    ] {
+        assert!(time > Duration::ZERO, "{phase} took no time");
+    }
+    assert_eq!(
+        times.total(),
+        times.lex + times.parse + times.compile + times.run
+    );
+    let summary = times.to_string();
+    assert_eq!(summary.lines().count(), 5, "{summary}");
+    assert!(summary.starts_with("lex "), "{summary}");
+
+    // warnings stay on the chunk, and errors stop the pipeline
+    let mut vm = VM::with_output(Chunk::new(), Box::new(io::sink()));
+    run_with_vm_timed("if false { print 1; }", false, &mut vm).unwrap();
+    assert_eq!(vm.chunk().warnings().len(), 1);
+    let error = run_with_vm_timed("print nil + 1;", false, &mut vm).unwrap_err();
+    assert!(
+        error.message.contains("unsupported operand"),
+        "{}",
+        error.message
+    );
+}
+
+#[test]
+fn test_run_from_a_reader() {
+    let source = "fn double(x) { return x * 2; }\nassert(double(21) == 42);\n";
+    run_reader_with_vm(Cursor::new(source)).unwrap();
+
+    let error = run_reader_with_vm(Cursor::new("let x = 1;\nassert(x == 2);")).unwrap_err();
+    assert_eq!(error.message, "assertion failed");
+    assert_eq!(error.location.map(|l| l.line), Some(2));
+
+    let error = run_reader_with_vm(Cursor::new([0xff, 0xfe])).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::Io);
+}
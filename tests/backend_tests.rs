@@ -0,0 +1,1025 @@
+use mylang::{
+    compile, compile_with_main,
+    compiler::Compiler,
+    error::{Error, ErrorType},
+    location::{Located, Location},
+    parse_with_main,
+    parser::{Expr, Stmt},
+    run_with_tr, run_with_vm,
+    treewalk::Interpreter,
+    vm::VM,
+};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// Writer handle whose contents remain readable after the backend takes ownership
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+/// Standard and error output of running `source` with the tree-walker
+fn streams_with_tr(source: &str) -> (String, String) {
+    let (output, error_output) = (SharedBuffer::default(), SharedBuffer::default());
+    let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+    interpreter.set_error_output(Box::new(error_output.clone()));
+    run_with_tr(source.to_string(), &mut interpreter).unwrap();
+    (output.contents(), error_output.contents())
+}
+
+/// Standard and error output of running `source` with the VM
+fn streams_with_vm(source: &str) -> (String, String) {
+    let chunk = compile(source).unwrap();
+
+    let (output, error_output) = (SharedBuffer::default(), SharedBuffer::default());
+    let mut vm = VM::with_output(chunk, Box::new(output.clone()));
+    vm.set_error_output(Box::new(error_output.clone()));
+    vm.run().unwrap();
+    (output.contents(), error_output.contents())
+}
+
+/// Run `source` on both backends and check they print `expected`
+fn assert_output(source: &str, expected: &str) {
+    assert_eq!(streams_with_tr(source).0, expected, "tree-walk output");
+    assert_eq!(streams_with_vm(source).0, expected, "vm output");
+}
+
+/// The errors running `source` fails with on the tree-walker and the VM
+fn errors(source: &str) -> (Error, Error) {
+    let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+    let tr_error = run_with_tr(source.to_string(), &mut interpreter).unwrap_err();
+    let vm_error = run_with_vm(source.to_string()).unwrap_err();
+    (tr_error, vm_error)
+}
+
+/// Run `source` on both backends and check they fail with `expected`
+fn assert_error(source: &str, expected: &str) {
+    let (tr_error, vm_error) = errors(source);
+    assert_eq!(tr_error.message, expected, "tree-walk error");
+    assert_eq!(vm_error.message, expected, "vm error");
+}
+
+#[test]
+fn test_errors_in_callbacks_point_into_the_callee() {
+    let source = "fn p(x) {\n  return x + \"a\";\n}\nlet r = find([1], p);";
+    let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+    let error = run_with_tr(source.to_string(), &mut interpreter).unwrap_err();
+    assert_eq!(
+        (error.line(), error.column()),
+        (Some(2), Some(12)),
+        "tree-walk error"
+    );
+    let error = run_with_vm(source.to_string()).unwrap_err();
+    assert_eq!(
+        (error.line(), error.column()),
+        (Some(2), Some(12)),
+        "vm error"
+    );
+}
+
+#[test]
+fn test_defined_sees_locals_in_scope() {
+    assert_output(
+        r#"
+        {
+            let inner = 1;
+            print defined("inner");
+        }
+        print defined("inner");
+        fn f(p) {
+            let q = 1;
+            fn g() {
+                return defined("p");
+            }
+            print defined("p"), defined("q"), g();
+        }
+        f(1);
+        print defined("p");
+        "#,
+        "true\nfalse\ntrue true true\nfalse\n",
+    );
+    // the name need not be a literal, nor the call a direct one
+    assert_output(
+        r#"
+        fn h() {
+            let z = 1;
+            let n = "z";
+            let d = defined;
+            let found = loop { break find(["y", "z"], defined); };
+            print defined(n), d("z"), d("y"), found, defined("loop value");
+        }
+        h();
+        print defined("z");
+        "#,
+        "true true false z false\nfalse\n",
+    );
+}
+
+#[test]
+fn test_indexing_errors_are_runtime_errors_on_both_backends() {
+    for source in [
+        "print [1][1];",
+        "print [1][-1];",
+        "print [1][0.5];",
+        "print [1][1000000 * 1000000 * 1000000 * 1000000];",
+        "print [1][\"0\"];",
+        "print 1[0];",
+        "let a = [1]; a[2] = 0;",
+        "print range(3)[3];",
+        "print [1, 2][1:3];",
+    ] {
+        let (tr_error, vm_error) = errors(source);
+        assert_eq!(
+            tr_error.error_type,
+            ErrorType::Runtime,
+            "tree-walk: {source}"
+        );
+        assert_eq!(vm_error.error_type, ErrorType::Runtime, "vm: {source}");
+        assert_eq!(tr_error.message, vm_error.message, "for {source}");
+    }
+}
+
+#[test]
+fn test_index_out_of_bounds_names_the_array() {
+    assert_error(
+        "let arr = [1, 2, 3];\nprint arr[10];",
+        "array index 10 out of bounds for 'arr' (length: 3)",
+    );
+    assert_error(
+        "fn f() { let arr = [1]; arr[1] = 2; }\nf();",
+        "array index 1 out of bounds for 'arr' (length: 1)",
+    );
+    assert_error(
+        "print [1, 2][5];",
+        "array index 5 out of bounds (length: 2)",
+    );
+    assert_error(
+        "let r = range(3);\nprint r[3];",
+        "array index 3 out of bounds for 'r' (length: 3)",
+    );
+}
+
+#[test]
+fn test_print_single_array() {
+    assert_output("print [1, 2, 3];", "[1, 2, 3]\n");
+    assert_output("print [[1, 2], [], \"a\"];", "[[1, 2], [], a]\n");
+}
+
+#[test]
+fn test_print_multiple_values() {
+    assert_output("print [1, 2], 3, \"x\";", "[1, 2] 3 x\n");
+}
+
+#[test]
+fn test_print_special_numbers() {
+    assert_output("print 0 * -1, -0, 5.0, 2.5;", "0 0 5 2.5\n");
+    assert_output("let a = 0;\nlet b = -0;\nprint 1 / a, 1 / b;", "inf -inf\n");
+    assert_output("print 1 / 0, -1 / 0, 0 / 0;", "inf -inf nan\n");
+    assert_output("print [0 * -1];", "[0]\n");
+}
+
+#[test]
+fn test_logical_operators_short_circuit_side_effects() {
+    let source = r#"
+        fn hit(label, value) {
+            print label;
+            return value;
+        }
+        print false and hit("and-false", true);
+        print true and hit("and-true", false);
+        print true or hit("or-true", false);
+        print false or hit("or-false", true);
+        print hit("left", nil) or hit("right", 1) and hit("last", 2);
+    "#;
+    assert_output(
+        source,
+        "false\nand-true\nfalse\ntrue\nor-false\ntrue\nleft\nright\nlast\n2\n",
+    );
+}
+
+#[test]
+fn test_semicolon_after_block() {
+    assert_output("if true { print 1; };\n;;\nprint 2;", "1\n2\n");
+}
+
+#[test]
+fn test_braceless_bodies() {
+    assert_output(
+        "for let i = 0; i < 3; i += 1 if i == 1 print i; else print -i;",
+        "0\n1\n-2\n",
+    );
+    assert_output("let a = true; if a if false print 1; else print 2;", "2\n");
+    assert_output("let n = 2; while n > 0 n -= 1; print n;", "0\n");
+}
+
+#[test]
+fn test_eprint_writes_to_error_output() {
+    let source = "print \"out\";\neprint \"warning:\", 1 + 1;\neprint([1]);\nprint \"done\";";
+    let expected = ("out\ndone\n".to_string(), "warning: 2\n[1]\n".to_string());
+    assert_eq!(streams_with_tr(source), expected, "tree-walk streams");
+    assert_eq!(streams_with_vm(source), expected, "vm streams");
+}
+
+#[test]
+fn test_negation() {
+    assert_output(
+        "let x = 3;\nprint -42, --42, -x, --x, -(-x);",
+        "-42 42 -3 3 3\n",
+    );
+}
+
+#[test]
+fn test_ordering_arrays_and_strings() {
+    assert_output(
+        "print [1, 2] < [1, 3], [1, 2] < [1], [] <= [], [2] > [1, 9];",
+        "true false true true\n",
+    );
+    assert_output(
+        r#"print "ab" < "b", "b" <= "ab", "abc" > "ab", "a" >= "a";"#,
+        "true false true true\n",
+    );
+    assert_output("print 1 < 2 < 3, [1] < [2] < [2];", "true false\n");
+}
+
+#[test]
+fn test_ordering_mismatched_types_is_an_error() {
+    assert_error(
+        r#"print 1 < "a";"#,
+        "'<' not supported between 'number' and 'string'",
+    );
+    assert_error(
+        "print [1] >= nil;",
+        "'>=' not supported between 'array' and 'nil'",
+    );
+    assert_error(
+        r#"print 0 < 1 <= "b";"#,
+        "'<=' not supported between 'number' and 'string'",
+    );
+    assert_error(
+        r#"print [1] < ["a"];"#,
+        "'<' not supported between 'number' and 'string'",
+    );
+    assert_error(
+        r#"print [1, [true]] > [1, [nil]];"#,
+        "'>' not supported between 'boolean' and 'nil'",
+    );
+    // elements past the first pair that differ are never compared
+    assert_output(
+        r#"print [1, "a"] < [2, nil], [] < ["a"];"#,
+        "true true
+",
+    );
+}
+
+#[test]
+fn test_zip_and_enumerate_reject_non_arrays() {
+    assert_error(
+        "zip([1], 2);",
+        "zip() expects two arrays, found 'array' and 'number'",
+    );
+    assert_error(
+        r#"enumerate("ab");"#,
+        "enumerate() expects an array, found 'string'",
+    );
+}
+
+#[test]
+fn test_print_function_values() {
+    assert_output(
+        "fn make(n) { fn add(x) { return x + n; } return add; }\nfn main() {}\nprint make, make(1), main, type(main);",
+        "<function make(n)> <function add(x)> <function main()> function\n",
+    );
+}
+
+#[test]
+fn test_print_self_referential_closure() {
+    assert_output(
+        "fn outer() {\n  let f = nil;\n  fn g(n) { if n > 0 { return f(n - 1); } return n; }\n  f = g;\n  return g;\n}\nlet h = outer();\nprint h, [h, h], h(3);",
+        "<function g(n)> [<function g(n)>, <function g(n)>] 0\n",
+    );
+}
+
+#[test]
+fn test_values_behave_identically() {
+    assert_output(
+        r#"print 1 + 2, "a" + "b", [1] + [2, 3], 7 / 2, -(1 - 3);"#,
+        "3 ab [1, 2, 3] 3.5 2\n",
+    );
+    assert_output(
+        "fn add(a, b) { return a + b; } print add, len, [add];",
+        "<function add(a, b)> <builtin function len> [<function add(a, b)>]\n",
+    );
+    assert_output(
+        "fn f() {} let g = f; print f == g, len == len, [1, [2]] == [1, [2]];",
+        "true true true\n",
+    );
+    assert_output(
+        "let a = [1]; let b = a; b[0] = 2; print a, type(a), type(nil);",
+        "[2] array nil\n",
+    );
+}
+
+#[test]
+fn test_calling_a_non_function_names_the_callee() {
+    assert_error("let foo; foo();", "cannot call 'foo' (nil)");
+    assert_error(
+        "fn f() { let n = 1; return n(2); } f();",
+        "cannot call 'n' (number)",
+    );
+    assert_error(
+        "let a = [1]; a[0]();",
+        "cannot call a value of type 'number'",
+    );
+}
+
+/// There is a single number type, so integer and float literals mix freely;
+/// an integer type would have to keep these results
+#[test]
+fn test_integer_and_float_literals_mix() {
+    assert_output(
+        "print 1 + 2.0, 1 + 2, 3 - 0.5, 2 * 1.5, 1 / 2;",
+        "3 3 2.5 3 0.5\n",
+    );
+    assert_output(
+        "print 1 == 1.0, 2.0 != 2, 1 < 1.5, 2.0 >= 2, [1] == [1.0];",
+        "true false true true true\n",
+    );
+}
+
+#[test]
+fn test_chained_index_assignment() {
+    assert_output(
+        "let matrix = [[1, 2], [3, 4]];\nmatrix[1][0] = 99;\nmatrix[0][1] += 10;\nprint matrix;",
+        "[[1, 12], [99, 4]]\n",
+    );
+    assert_output(
+        "fn rows() { return [[0]]; }\nlet grid = rows();\ngrid[0][0] = 1;\nprint grid;",
+        "[[1]]\n",
+    );
+}
+
+/// Output of running `source` on both backends with `main` called at the end
+fn main_output(source: &str) -> (String, String) {
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+    interpreter
+        .interpret(&parse_with_main(source).unwrap())
+        .unwrap();
+    let tr_output = output.contents();
+
+    let output = SharedBuffer::default();
+    let chunk = compile_with_main(source).unwrap();
+    VM::with_output(chunk, Box::new(output.clone()))
+        .run()
+        .unwrap();
+    (tr_output, output.contents())
+}
+
+#[test]
+fn test_hoisted_functions_keep_the_source_order_of_redefinitions() {
+    // a later `let` still replaces a function declared before it
+    assert_output(
+        "print f();\nfn f() { return 1; }\nlet f = 2;\nprint f;",
+        "1\n2\n",
+    );
+    // hoisting would let the earlier declaration win, so these are errors
+    for source in [
+        "fn f() { return 1; }\nfn f() { return 2; }\nprint f();",
+        "let f = 1;\nfn f() { return 2; }\nprint f;",
+        "let (a, f) = [1, 2];\nfn f() {}",
+    ] {
+        assert_error(
+            source,
+            "'f' is already declared; top-level functions cannot be redefined",
+        );
+    }
+    // functions in a block are not hoisted, so they may shadow each other
+    assert_output(
+        "{ fn g() { return 1; } fn g() { return 2; } print g(); }",
+        "2\n",
+    );
+}
+
+#[test]
+fn test_main_is_called_after_the_script() {
+    let source = "fn main() { print greeting; }\nlet greeting = \"hi\";\nprint \"top\";";
+    let expected = "top\nhi\n".to_string();
+    assert_eq!(main_output(source), (expected.clone(), expected));
+    // without the mode, main is just another function
+    assert_output(source, "top\n");
+
+    let no_main = "fn main(x) { print x; }\nprint 1;";
+    let expected = "1\n".to_string();
+    assert_eq!(main_output(no_main), (expected.clone(), expected));
+}
+
+#[test]
+fn test_find_calls_back_into_the_backend() {
+    // the predicate is a closure that prints, so both backends must run it
+    // in order and stop at the first match
+    assert_output(
+        "let limit = 2;\nfn over(n) { print n; return n > limit; }\nprint find([1, 2, 3, 4], over), find_index([5], over);",
+        "1\n2\n3\n5\n3 0\n",
+    );
+    assert_error(
+        "find([1], 2);",
+        "find() expects an array and a function, found 'array' and 'number'",
+    );
+    assert_error(
+        "find_index([1]);",
+        "find_index() takes exactly 2 arguments (1 given)",
+    );
+    assert_error(
+        "fn bad(n) { return n + \"a\"; }\nfind([1], bad);",
+        "unsupported operand type(s) for +: 'number' and 'string'",
+    );
+}
+
+#[test]
+fn test_innermost_closure_assigns_outer_variable() {
+    assert_output(
+        "fn outer() {\n    let count = 0;\n    fn middle() {\n        fn inner() { count += 1; }\n        inner();\n        print count;\n        return inner;\n    }\n    let inner = middle();\n    inner();\n    print count;\n    return inner;\n}\nlet inner = outer();\ninner();",
+        "1\n2\n",
+    );
+}
+
+#[test]
+fn test_starts_with_and_ends_with_reject_non_strings() {
+    assert_error(
+        "starts_with(\"abc\", 1);",
+        "starts_with() expects two strings, found 'string' and 'number'",
+    );
+    assert_error(
+        "ends_with([\"a\"], \"a\");",
+        "ends_with() expects two strings, found 'array' and 'string'",
+    );
+    assert_error(
+        "ends_with(\"a\");",
+        "ends_with() takes exactly 2 arguments (1 given)",
+    );
+}
+
+#[test]
+fn test_ranges_are_lazy() {
+    // a trillion numbers would not fit in memory if range() stored them
+    assert_output(
+        "let r = range(1000000000000);\nprint len(r), r[999999999999], r;",
+        "1000000000000 999999999999 range(0, 1000000000000)\n",
+    );
+    assert_output(
+        r#"
+        let r = range(1000000);
+        let total = 0;
+        for let i = 0; i < len(r); i += 1000 { total += r[i]; }
+        print total;
+        "#,
+        "499500000\n",
+    );
+    assert_output(
+        "print to_array(range(4)), to_array(range(2, 11, 3)), to_array(range(5, 0, -2));",
+        "[0, 1, 2, 3] [2, 5, 8] [5, 3, 1]\n",
+    );
+    assert_output(
+        "print range(3) == range(0, 3, 1), range(0) == range(5, 2), range(3) == range(4), type(range(1));",
+        "true true false range\n",
+    );
+    assert_output("if range(0) print 1; else print 2;", "2\n");
+
+    assert_error("range();", "range() takes 1 to 3 arguments (0 given)");
+    assert_error("range(1.5);", "range() arguments must be integers, got 1.5");
+    assert_error("range(0, 10, 0);", "range() step must not be zero");
+    assert_error(
+        "let r = range(3);\nr[0] = 1;",
+        "ranges cannot be assigned to; convert one with to_array() first",
+    );
+    assert_error(
+        "range(3)[0:1];",
+        "ranges cannot be sliced; convert one with to_array() first",
+    );
+    assert_error(
+        "to_array(1);",
+        "to_array() expects a range or an array, found 'number'",
+    );
+    assert_error(
+        "to_array(range(1000000 * 1000000 * 1000000));",
+        "to_array() would make an array of 1000000000000000000 elements (at most 16777216)",
+    );
+}
+
+#[test]
+fn test_ord_and_chr_convert_character_codes() {
+    assert_output(
+        "print ord(\"A\"), ord(\"a\"), chr(65), chr(97) + chr(98);",
+        "65 97 A ab\n",
+    );
+    assert_output("print chr(ord(\"中\")), ord(\"🚀\");", "中 128640\n");
+    assert_error(
+        "ord(\"ab\");",
+        "ord() expects a single character, got a string of length 2",
+    );
+    assert_error(
+        "ord(\"\");",
+        "ord() expects a single character, got a string of length 0",
+    );
+    assert_error("ord(65);", "ord() expects a string, found 'number'");
+    assert_error("chr(\"A\");", "chr() expects a number, found 'string'");
+    assert_error("chr(-1);", "chr() expects a non-negative integer, got -1");
+    assert_error("chr(6.5);", "chr() expects a non-negative integer, got 6.5");
+    assert_error(
+        "chr(1114112);",
+        "chr() expects a Unicode code point, got 1114112",
+    );
+    assert_error(
+        "chr(55296);",
+        "chr() expects a Unicode code point, got 55296",
+    );
+}
+
+#[test]
+fn test_empty_functions_and_blocks() {
+    assert_output(
+        "fn f() {}\nfn g(a) { {} }\n{}\n{ {} }\nprint f(), g(1), is_nil(f());",
+        "nil nil true\n",
+    );
+    assert_output(
+        "fn f(x) { if x { return 1; } }\nprint f(false), f(true);",
+        "nil 1\n",
+    );
+}
+
+#[test]
+fn test_string_compound_assignment() {
+    assert_output("let s = \"a\";\ns += \"b\";\ns += s;\nprint s;", "abab\n");
+    // strings and numbers do not mix until concatenation coerces numbers
+    assert_error(
+        "let s = \"n = \";\ns += 1;",
+        "unsupported operand type(s) for +: 'string' and 'number'",
+    );
+}
+
+#[test]
+fn test_indexing_a_non_array_names_its_type() {
+    for (source, type_name) in [
+        ("let n = 5;\nprint n[0];", "number"),
+        ("print true[0];", "boolean"),
+        ("let x = nil;\nx[0] = 1;", "nil"),
+        ("print \"abc\"[0];", "string"),
+    ] {
+        assert_error(
+            source,
+            &format!(
+                "cannot index a value of type '{type_name}'; only arrays and ranges can be indexed"
+            ),
+        );
+    }
+    assert_error(
+        "let a = [1];\nprint a[\"0\"];",
+        "array index must be a number, found 'string'",
+    );
+    assert_error(
+        "let a = [1];\na[nil] = 2;",
+        "array index must be a number, found 'nil'",
+    );
+}
+
+#[test]
+fn test_let_declares_several_variables() {
+    assert_output(
+        "let a = 1, b = a + 1, c = a + b;\nprint a, b, c;",
+        "1 2 3\n",
+    );
+    assert_output(
+        "fn f() {\n  let x = 2, y, z = x * 10;\n  return [x, y, z];\n}\nprint f();",
+        "[2, nil, 20]\n",
+    );
+    assert_output(
+        "let total = 0;\nfor let i = 0, j = 3; i < j; i += 1 { total += i; }\nprint total;",
+        "3\n",
+    );
+}
+
+#[test]
+fn test_other_quote_needs_no_escape() {
+    assert_output(
+        "print 'he said \"hi\"';\nprint \"it's\", len(\"it's\");",
+        "he said \"hi\"\nit's 4\n",
+    );
+}
+
+#[test]
+fn test_print_separator_and_terminator() {
+    assert_output(
+        "let a = 1, b = [2];\nprint a, b, \"c\", sep = \", \";\nprint a, end = \"\";\nprint b, sep = \"-\", end = \"!\";\nprint \"\";",
+        "1, [2], c\n1[2]!\n",
+    );
+    assert_output(
+        "let sep = \"\";\nfor let i = 0; i < 3; i += 1 { print i, end = sep; }\nprint \"\";",
+        "012\n",
+    );
+    // before any value, `end =` and `sep =` are plain assignments
+    assert_output(
+        "let end = 1, sep = 2;\nprint end = 5;\nprint sep = end, end;\nprint end, sep;",
+        "5\n5 5\n5 5\n",
+    );
+}
+
+#[test]
+fn test_count_values_and_predicate_matches() {
+    assert_output(
+        "fn is_even(n) { return divmod(n, 2)[1] == 0; }\nlet xs = [2, 5, 2, 8, 3];\nprint count(xs, 2), count(xs, is_even), count(xs, \"2\");",
+        "2 3 0\n",
+    );
+    assert_error(
+        "count(\"aab\", \"a\");",
+        "count() expects an array, found 'string'",
+    );
+}
+
+#[test]
+fn test_for_without_condition_runs_until_break() {
+    assert_output(
+        "let i = 0;\nfor ;; {\n  i += 1;\n  if i == 3 { break; }\n}\nprint i;\nfor ;; i += 1 { if i > 5 { break; } }\nprint i;",
+        "3\n6\n",
+    );
+    assert_output("for ;; { break; }\nprint \"done\";", "done\n");
+}
+
+#[test]
+fn test_arity_and_name_reflect_functions() {
+    assert_output(
+        "fn add(a, b) { return a + b; }\nfn make() { fn inner(x, y, z) {} return inner; }\nlet f = make();\nprint arity(add), name(add), arity(f), name(f), arity(make);",
+        "2 add 3 inner 0\n",
+    );
+    assert_error("arity(1);", "arity() expects a function, found 'number'");
+    assert_error(
+        "name(\"add\");",
+        "name() expects a function, found 'string'",
+    );
+}
+
+#[test]
+fn test_doc_returns_the_function_doc_comment() {
+    assert_output(
+        "/// Adds two numbers.\n/// Both must be numbers.\nfn add(a, b) { return a + b; }\nfn outer() {\n  /// Inner doc.\n  fn inner() {}\n  return inner;\n}\n// a plain comment\nfn plain() {}\nprint doc(add);\nprint doc(outer()), doc(plain), doc(len);",
+        "Adds two numbers.\nBoth must be numbers.\nInner doc. nil nil\n",
+    );
+    assert_error("doc(1);", "doc() expects a function, found 'number'");
+}
+
+#[test]
+fn test_constant_conditions_run_the_live_branch() {
+    assert_output(
+        "if false { print 1; } else { print 2; }\nif true { print 3; } else { print 4; }",
+        "2\n3\n",
+    );
+    assert_output(
+        "let i = 0;\nwhile true { i += 1; if i == 3 { break; } }\nfor ;; i += 1 { if i > 4 { break; } continue; }\nfor let j = 0; false; j += 1 { print j; }\nprint i;",
+        "5\n",
+    );
+}
+
+#[test]
+fn test_slice_assignment_splices() {
+    assert_output(
+        "let a = [1, 2, 3, 4];\na[1:3] = [\"x\", \"y\", \"z\"];\nprint len(a), a;\nprint a[:2], a[3:];",
+        "5 [1, x, y, z, 4]\n[1, x] [z, 4]\n",
+    );
+    assert_output(
+        "let a = [1, 2];\na[0:0] = a;\nprint a;\na[1:] = [];\nprint a;\nlet m = [[1, 2]];\nprint m[0][1:] = [3, 4], m;",
+        "[1, 2, 1, 2]\n[1]\n[3, 4] [[1, 3, 4]]\n",
+    );
+    assert_error(
+        "let a = [1, 2];\na[1:3] = [0];",
+        "array slice [1:3] out of bounds (length: 2)",
+    );
+    assert_error(
+        "let a = [1, 2];\nprint a[2:1];",
+        "array slice [2:1] out of bounds (length: 2)",
+    );
+    assert_error(
+        "let a = [1, 2];\na[0:1] = 5;",
+        "type error in slice assignment: expected array, found number",
+    );
+}
+
+#[test]
+fn test_top_level_return_is_rejected() {
+    assert_error(
+        "print 1;\nreturn 5;",
+        "'return' statement must be inside a function",
+    );
+    assert_error(
+        "if true { return; }",
+        "'return' statement must be inside a function",
+    );
+
+    // statements built without the parser fail the same way on both backends
+    let stmts = [Located::new(
+        Stmt::Return {
+            value: Some(Expr::Number(5.0)),
+        },
+        Location::new(),
+    )];
+    let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+    let error = interpreter.interpret(&stmts).unwrap_err();
+    assert_eq!(error.message, "'return' outside function");
+    let error = Compiler::new().compile(&stmts).unwrap_err();
+    assert_eq!(error.message, "'return' outside function");
+}
+
+#[test]
+fn test_break_gives_a_loop_its_value() {
+    assert_output("let x = loop { break 42; };\nprint x;", "42\n");
+    assert_output(
+        r#"
+        fn sum_odd_squares(n) {
+            let total = 0;
+            let i = 0;
+            let sum = loop {
+                i += 1;
+                if i > n { break total; }
+                let square = i * i;
+                if square == 4 or square == 16 { continue; }
+                total += square;
+            };
+            // loops nest, and each keeps its value apart from the locals
+            let result = loop { let extra = loop { break 3; }; break sum + extra; };
+            return result;
+        }
+        {
+            let a = 1;
+            let b = loop { let c = a + 1; break c * 10; };
+            print sum_odd_squares(5), a, b;
+        }
+        let empty = loop { break; };
+        loop { print empty; break; }
+        "#,
+        "38 1 20\nnil\n",
+    );
+}
+
+#[test]
+fn test_return_leaves_a_loop() {
+    assert_output(
+        r#"
+        fn first_over(xs, limit) {
+            let i = 0;
+            let found = loop {
+                if i == len(xs) { break nil; }
+                if xs[i] > limit { return xs[i]; }
+                i += 1;
+            };
+            return found;
+        }
+        fn countdown(n) {
+            loop {
+                if n == 0 { return "done"; }
+                n -= 1;
+            }
+        }
+        print first_over([1, 5, 9], 4), first_over([1], 4), countdown(3);
+        "#,
+        "5 nil done\n",
+    );
+}
+
+#[test]
+fn test_unused_constants_do_not_move_jumps() {
+    assert_output(
+        r#"
+        fn f(n) {
+            1;
+            if n { 2; return n; }
+            3;
+            return "none";
+        }
+        let i = 0;
+        while i < 3 { 4; i += 1; }
+        let x = true and 5;
+        false or 6;
+        for let j = 0; j < 2; j += 1 { "skip"; continue; }
+        print i, f(7), f(false), x;
+        "#,
+        "3 7 none 5\n",
+    );
+}
+
+#[test]
+fn test_switch_falls_through_until_break() {
+    assert_output(
+        r#"
+        fn describe(n) {
+            switch n {
+                case 1:
+                    print "one";
+                case 2:
+                    print "up to two";
+                    break;
+                case 3:
+                    let three = "three";
+                    print three;
+                default:
+                    print "other";
+            }
+        }
+        describe(1);
+        describe(2);
+        describe(3);
+        describe(4);
+        "#,
+        "one\nup to two\nup to two\nthree\nother\nother\n",
+    );
+    // `default` may come first, and only runs when nothing matches
+    assert_output(
+        r#"
+        let x = "b";
+        switch x { default: print "none"; break; case "a": print "a"; }
+        switch x { case "a": print "a"; case "b": print "b"; }
+        switch 5 { case 1: print 1; }
+        "#,
+        "none\nb\n",
+    );
+}
+
+#[test]
+fn test_break_and_continue_in_switch_inside_loop() {
+    assert_output(
+        r#"
+        for let i = 0; i < 4; i += 1 {
+            let label = "i";
+            switch i {
+                case 0:
+                    continue;
+                case 2:
+                    let skipped = true;
+                    break;
+                default:
+                    print label, i;
+            }
+            print "after", i;
+        }
+        "#,
+        "i 1\nafter 1\nafter 2\ni 3\nafter 3\n",
+    );
+}
+
+#[test]
+fn test_for_in_destructures_pairs() {
+    assert_output(
+        r#"
+        let keys = ["a", "b", "c"];
+        let vals = [1, 2, 3];
+        for (k, v) in zip(keys, vals) {
+            let line = k + "=";
+            print line, v;
+        }
+        for (i, (k, v)) in enumerate(zip(keys, vals)) {
+            if i == 1 { continue; }
+            print i, k, v;
+        }
+        let total = 0;
+        for x in range(10, 0, -2) {
+            if x < 5 { break; }
+            total += x;
+        }
+        print total;
+        for in in [[]] print in;
+        "#,
+        "a= 1\nb= 2\nc= 3\n0 a 1\n2 c 3\n24\n[]\n",
+    );
+    assert_error(
+        "for (k, v) in [[1, 2], [3]] { print k, v; }",
+        "cannot destructure array of length 1 into 2 names",
+    );
+    assert_error(
+        "for (k, v) in [1] { print k, v; }",
+        "cannot destructure value of type 'number', expected array",
+    );
+    assert_error(
+        "let (a, b) = [1, 2, 3];",
+        "cannot destructure array of length 3 into 2 names",
+    );
+    assert_error(
+        "let (a, b) = nil;",
+        "cannot destructure value of type 'nil', expected array",
+    );
+    assert_error(
+        "for x in 3 { print x; }",
+        "cannot loop over a value of type 'number'; only arrays and ranges can be",
+    );
+}
+
+#[test]
+fn test_long_operator_chains_are_not_nesting() {
+    let sum = vec!["1"; 200].join(" + ");
+    let text = vec!["\"a\""; 70].join(" + ");
+    let all = vec!["true"; 70].join(" and ");
+    assert_output(
+        &format!("let x = 0;\nx = x + {sum};\nlet s = {text};\nprint x, len(s), {all};"),
+        "200 70 true\n",
+    );
+}
+
+#[test]
+fn test_raw_identifiers_can_be_keywords() {
+    assert_output(
+        "let r#let = 1;\nfn r#fn(r#if) { return r#if + r#let; }\nprint r#fn(2), r#let;",
+        "3 1\n",
+    );
+}
+
+#[test]
+fn test_defined_checks_globals() {
+    assert_output(
+        r#"
+        print defined("x"), defined("len");
+        let x = 1;
+        fn f() { return defined("x") and defined("f"); }
+        print defined("x"), f(), defined("later");
+        let later = nil;
+        print defined("later");
+        "#,
+        "false true\ntrue true false\ntrue\n",
+    );
+    assert_error("defined(1);", "defined() expects a string, found 'number'");
+    assert_error(
+        "defined(\"a\", \"b\");",
+        "defined() takes exactly 1 argument (2 given)",
+    );
+}
+
+#[test]
+fn test_deepequal_compares_numbers_exactly() {
+    assert_output(
+        "print 0.1 + 0.2 == 0.3, deepequal(0.1 + 0.2, 0.3), deepequal(0.5 + 0.25, 0.75);",
+        "true false true\n",
+    );
+    assert_output(
+        r#"
+        let nan = 0 / 0;
+        print deepequal(nan, nan), deepequal([nan], [nan]);
+        print [0.1 + 0.2] == [0.3], deepequal([1, [0.1 + 0.2]], [1, [0.3]]);
+        print deepequal([1, ["a", nil]], [1, ["a", nil]]), deepequal(1, "1"), deepequal(len, len);
+        "#,
+        "false false\ntrue false\ntrue false true\n",
+    );
+    assert_error(
+        "deepequal(1);",
+        "deepequal() takes exactly 2 arguments (1 given)",
+    );
+}
+
+#[test]
+fn test_deepequal_handles_arrays_holding_themselves() {
+    assert_output(
+        r#"
+        let a = [0];
+        a[0] = a;
+        let b = [0];
+        b[0] = b;
+        let c = [1, 2];
+        c[1] = c;
+        let d = [3, 2];
+        d[1] = d;
+        print deepequal(a, a), deepequal(a, b), deepequal(c, d), deepequal(a, [a]);
+        "#,
+        "true true false true
+",
+    );
+}
+
+#[test]
+fn test_number_equality_is_relative_to_magnitude() {
+    let prelude = "let big = 1000000000000000;\nlet tiny = 1 / (big * big);\n";
+    // rounding error is forgiven at any scale
+    assert_output(
+        &format!("{prelude}print 0.1 + 0.2 == 0.3, big * (0.1 + 0.2) == big * 0.3, tiny * (0.1 + 0.2) == tiny * 0.3;"),
+        "true true true\n",
+    );
+    // but numbers that really differ are not equal, however small or large
+    assert_output(
+        &format!("{prelude}print tiny == 2 * tiny, 0 == tiny, big == big + 1, big + 1 != big;"),
+        "false false false true\n",
+    );
+    assert_output(
+        &format!("{prelude}let inf = 1 / 0;\nprint inf == inf, inf == big * big, -inf == -inf, 0 / 0 == 0 / 0;"),
+        "true false true false\n",
+    );
+}
@@ -78,7 +78,8 @@ mod lexer_tests {
 
     #[test]
     fn test_keywords() {
-        let input = "let fn if else while for return true false and or";
+        let input =
+            "let fn if else elif while repeat loop switch case for return true false and or";
         let tokens = get_tokens(input);
 
         let expected_types = vec![
@@ -86,7 +87,12 @@ mod lexer_tests {
             TokenType::Fn,
             TokenType::If,
             TokenType::Else,
+            TokenType::Elif,
             TokenType::While,
+            TokenType::Repeat,
+            TokenType::Loop,
+            TokenType::Switch,
+            TokenType::Case,
             TokenType::For,
             TokenType::Return,
             TokenType::Boolean(true),
@@ -99,6 +105,21 @@ mod lexer_tests {
         assert_eq!(token_types(&tokens), expected_types);
     }
 
+    #[test]
+    fn test_not_is_a_bang() {
+        let tokens = get_tokens("not x != nothing");
+
+        let expected_types = vec![
+            TokenType::Bang,
+            TokenType::Identifier("x".to_string()),
+            TokenType::BangEqual,
+            TokenType::Identifier("nothing".to_string()),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(token_types(&tokens), expected_types);
+    }
+
     #[test]
     fn test_identifiers() {
         let input = "variable_name camelCase _underscore var123";
@@ -116,6 +137,7 @@ mod lexer_tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_numbers() {
         let input = "123 3.14 0 999.999";
         let tokens = get_tokens(input);
@@ -131,6 +153,55 @@ mod lexer_tests {
         assert_eq!(token_types(&tokens), expected_types);
     }
 
+    #[test]
+    fn test_dot_edge_numbers() {
+        let tokens = get_tokens(".5 5. 0.25");
+
+        let expected_types = vec![
+            TokenType::Number(0.5),
+            TokenType::Number(5.0),
+            TokenType::Number(0.25),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(token_types(&tokens), expected_types);
+    }
+
+    #[test]
+    fn test_octal_literals() {
+        let tokens = get_tokens("0o17 0O7 0o0 017");
+        let expected_types = vec![
+            TokenType::Number(15.0),
+            TokenType::Number(7.0),
+            TokenType::Number(0.0),
+            TokenType::Number(17.0),
+            TokenType::Eof,
+        ];
+        assert_eq!(token_types(&tokens), expected_types);
+
+        for (input, expected) in [
+            ("0o8", "invalid digit '8' in octal literal"),
+            ("0o19", "invalid digit '9' in octal literal"),
+            ("0o", "octal literal has no digits"),
+        ] {
+            let error = Lexer::new(input.to_string()).tokenize().unwrap_err();
+            assert_eq!(error.message, expected);
+        }
+    }
+
+    #[test]
+    fn test_lone_dot_error() {
+        for input in [".", "let x = . ;", "x.y"] {
+            let mut lexer = Lexer::new(input.to_string());
+            let error = lexer.tokenize().unwrap_err();
+            assert!(
+                error.message.contains("unexpected character: ."),
+                "unexpected message for '{input}': {}",
+                error.message
+            );
+        }
+    }
+
     #[test]
     fn test_strings() {
         let input = r#""hello" "world with spaces" 'one "double" quote'"#;
@@ -146,6 +217,37 @@ mod lexer_tests {
         assert_eq!(token_types(&tokens), expected_types);
     }
 
+    #[test]
+    fn test_doc_comments() {
+        let input = "/// Adds one.\r\n///  indented\n////not a doc\nfn";
+        let tokens = get_tokens(input);
+
+        let expected_types = vec![
+            TokenType::DocComment("Adds one.".to_string()),
+            TokenType::DocComment(" indented".to_string()),
+            TokenType::Fn,
+            TokenType::Eof,
+        ];
+
+        assert_eq!(token_types(&tokens), expected_types);
+    }
+
+    #[test]
+    fn test_other_quote_is_literal() {
+        let input = r#"'he said "hi"' "it's" '"' "'""#;
+        let tokens = get_tokens(input);
+
+        let expected_types = vec![
+            TokenType::String(r#"he said "hi""#.to_string()),
+            TokenType::String("it's".to_string()),
+            TokenType::String("\"".to_string()),
+            TokenType::String("'".to_string()),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(token_types(&tokens), expected_types);
+    }
+
     #[test]
     fn test_comments() {
         let input = "// this is a comment\nlet x = 42; // another comment";
@@ -269,6 +371,21 @@ mod lexer_tests {
         }
     }
 
+    #[test]
+    fn test_non_ascii_strings() {
+        let tokens = get_tokens("\"中文\" \"é\" \"🚀\" x");
+        let expected_types = vec![
+            TokenType::String("中文".to_string()),
+            TokenType::String("é".to_string()),
+            TokenType::String("🚀".to_string()),
+            TokenType::Identifier("x".to_string()),
+            TokenType::Eof,
+        ];
+        assert_eq!(token_types(&tokens), expected_types);
+        // columns count characters, whatever their width in bytes
+        assert_eq!(tokens[3].location.column, 14);
+    }
+
     #[test]
     fn test_unicode_and_special_characters() {
         let test_cases = vec![
@@ -360,6 +477,37 @@ mod lexer_tests {
         assert_eq!(token_types(&tokens), expected_types);
     }
 
+    #[test]
+    fn test_line_continuation() {
+        let input = "let x = 1 + \\\n    2;\nx;";
+        let tokens = get_tokens(input);
+
+        let expected_types = vec![
+            TokenType::Let,
+            TokenType::Identifier("x".to_string()),
+            TokenType::Equal,
+            TokenType::Number(1.0),
+            TokenType::Plus,
+            TokenType::Number(2.0),
+            TokenType::Semicolon,
+            TokenType::Identifier("x".to_string()),
+            TokenType::Semicolon,
+            TokenType::Eof,
+        ];
+
+        assert_eq!(token_types(&tokens), expected_types);
+        // the continued line still counts towards line numbers
+        assert_eq!(tokens[5].location.line, 2);
+        assert_eq!(tokens[7].location.line, 3);
+    }
+
+    #[test]
+    fn test_backslash_without_newline_errors() {
+        let mut lexer = Lexer::new("let x = 1 \\ 2;".to_string());
+        let error = lexer.tokenize().unwrap_err();
+        assert!(error.message.contains("unexpected character: \\"));
+    }
+
     // Block comment tests
     #[test]
     fn test_simple_block_comment() {
@@ -473,4 +621,44 @@ let y = 2; /* End of line block */
 
         assert_eq!(token_types(&tokens), expected_types);
     }
+
+    #[test]
+    fn test_raw_identifiers() {
+        let tokens = get_tokens("let r#let = r#fn + r#x + r + r2;");
+
+        let expected_types = vec![
+            TokenType::Let,
+            TokenType::Identifier("let".to_string()),
+            TokenType::Equal,
+            TokenType::Identifier("fn".to_string()),
+            TokenType::Plus,
+            TokenType::Identifier("x".to_string()),
+            TokenType::Plus,
+            TokenType::Identifier("r".to_string()),
+            TokenType::Plus,
+            TokenType::Identifier("r2".to_string()),
+            TokenType::Semicolon,
+            TokenType::Eof,
+        ];
+        assert_eq!(token_types(&tokens), expected_types);
+        assert_eq!(tokens[3].location.column, 13);
+
+        for input in ["r#1", "r#", "r# x"] {
+            let error = Lexer::new(input.to_string()).tokenize().unwrap_err();
+            assert_eq!(
+                error.message, "expected an identifier after 'r#'",
+                "for {input:?}"
+            );
+            assert_eq!(error.location.map(|l| l.column), Some(3));
+        }
+    }
+
+    #[test]
+    fn test_token_formatting_includes_location() {
+        let tokens = get_tokens("let\n  x");
+
+        assert_eq!(format!("{:?}", tokens[1]), "Identifier(\"x\") @ 2:3");
+        assert_eq!(tokens[1].to_string(), "Identifier(\"x\") at 2:3");
+        assert_eq!(format!("{:?}", tokens[0]), "Let @ 1:1");
+    }
 }
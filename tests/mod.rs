@@ -1,4 +1,9 @@
+#![allow(clippy::module_inception)]
+
+mod api_tests;
+mod backend_tests;
 mod lexer_tests;
 mod parser_tests;
+mod repl_tests;
 mod treewalk_tests;
 mod vm_tests;
@@ -1,6 +1,7 @@
 use mylang::{
     lexer::lexer::Lexer,
-    parser::{BinaryOp, Expr, Parser, Stmt, UnaryOp},
+    location::{Located, Location},
+    parser::{BinaryOp, Expr, LocatedStmt, Parser, Pattern, PrintArgs, Stmt, SwitchCase, UnaryOp},
 };
 
 #[cfg(test)]
@@ -15,10 +16,205 @@ mod parser_tests {
             .parse()
             .unwrap()
             .into_iter()
-            .map(|stmt| stmt.into_inner())
+            .map(|stmt| erase_locations(stmt.into_inner()))
             .collect::<Vec<_>>()
     }
 
+    /// Nested statement at the default location, to compare structure only
+    fn located(stmt: Stmt) -> LocatedStmt {
+        Located::new(stmt, Location::new())
+    }
+
+    fn erase_locations(stmt: Stmt) -> Stmt {
+        let erase_all = |stmts: Vec<LocatedStmt>| {
+            stmts
+                .into_iter()
+                .map(|stmt| located(erase_locations(stmt.into_inner())))
+                .collect()
+        };
+        let erase_boxed =
+            |stmt: Box<LocatedStmt>| Box::new(located(erase_locations(stmt.into_inner())));
+        match stmt {
+            Stmt::VarDecl { name, initializer } => Stmt::VarDecl {
+                name,
+                initializer: initializer.map(erase_expr),
+            },
+            Stmt::MultiVarDecl(decls) => Stmt::MultiVarDecl(
+                decls
+                    .into_iter()
+                    .map(|(name, initializer)| (name, initializer.map(erase_expr)))
+                    .collect(),
+            ),
+            Stmt::DestructureDecl { names, initializer } => Stmt::DestructureDecl {
+                names,
+                initializer: erase_expr(initializer),
+            },
+            Stmt::Expression(expr) => Stmt::Expression(erase_expr(expr)),
+            Stmt::Print(args) => Stmt::Print(erase_print_args(args)),
+            Stmt::Eprint(args) => Stmt::Eprint(erase_print_args(args)),
+            Stmt::Block(stmts) => Stmt::Block(erase_all(stmts)),
+            Stmt::FuncDecl {
+                name,
+                params,
+                body,
+                doc,
+            } => Stmt::FuncDecl {
+                name,
+                params,
+                body: erase_all(body),
+                doc,
+            },
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => Stmt::If {
+                condition: erase_expr(condition),
+                then_branch: erase_boxed(then_branch),
+                else_branch: else_branch.map(erase_boxed),
+            },
+            Stmt::While { condition, body } => Stmt::While {
+                condition: erase_expr(condition),
+                body: erase_boxed(body),
+            },
+            Stmt::WhileLet {
+                name,
+                initializer,
+                body,
+            } => Stmt::WhileLet {
+                name,
+                initializer: erase_expr(initializer),
+                body: erase_boxed(body),
+            },
+            Stmt::Repeat { count, body } => Stmt::Repeat {
+                count: erase_expr(count),
+                body: erase_boxed(body),
+            },
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => Stmt::For {
+                initializer: initializer.map(erase_boxed),
+                condition: erase_expr(condition),
+                increment: increment.map(erase_expr),
+                body: erase_boxed(body),
+            },
+            Stmt::Return { value } => Stmt::Return {
+                value: value.map(erase_expr),
+            },
+            Stmt::Break { value } => Stmt::Break {
+                value: value.map(erase_expr),
+            },
+            Stmt::ForIn {
+                pattern,
+                iterable,
+                body,
+            } => Stmt::ForIn {
+                pattern,
+                iterable: erase_expr(iterable),
+                body: erase_boxed(body),
+            },
+            Stmt::Switch { subject, cases } => Stmt::Switch {
+                subject: erase_expr(subject),
+                cases: cases
+                    .into_iter()
+                    .map(|case| SwitchCase {
+                        value: case.value.map(erase_expr),
+                        body: erase_all(case.body),
+                    })
+                    .collect(),
+            },
+            stmt => stmt,
+        }
+    }
+
+    fn erase_print_args(args: PrintArgs) -> PrintArgs {
+        PrintArgs {
+            values: args.values.into_iter().map(erase_expr).collect(),
+            sep: args.sep.map(erase_expr),
+            end: args.end.map(erase_expr),
+        }
+    }
+
+    /// Reset operator locations so expressions compare by structure only
+    fn erase_expr(expr: Expr) -> Expr {
+        let erase_boxed = |expr: Box<Expr>| Box::new(erase_expr(*expr));
+        let erase_all = |exprs: Vec<Expr>| exprs.into_iter().map(erase_expr).collect();
+        match expr {
+            Expr::Array(elements) => Expr::Array(erase_all(elements)),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => Expr::Binary {
+                left: erase_boxed(left),
+                operator,
+                right: erase_boxed(right),
+                location: Location::new(),
+            },
+            Expr::Unary { operator, operand } => Expr::Unary {
+                operator,
+                operand: erase_boxed(operand),
+            },
+            Expr::ChainedComparison {
+                operands,
+                operators,
+            } => Expr::ChainedComparison {
+                operands: erase_all(operands),
+                operators,
+            },
+            Expr::Assign { name, value } => Expr::Assign {
+                name,
+                value: erase_boxed(value),
+            },
+            Expr::IndexAssign {
+                array,
+                index,
+                value,
+            } => Expr::IndexAssign {
+                array: erase_boxed(array),
+                index: erase_boxed(index),
+                value: erase_boxed(value),
+            },
+            Expr::Index { array, index } => Expr::Index {
+                array: erase_boxed(array),
+                index: erase_boxed(index),
+            },
+            Expr::Slice { array, start, end } => Expr::Slice {
+                array: erase_boxed(array),
+                start: start.map(erase_boxed),
+                end: end.map(erase_boxed),
+            },
+            Expr::SliceAssign {
+                array,
+                start,
+                end,
+                value,
+            } => Expr::SliceAssign {
+                array: erase_boxed(array),
+                start: start.map(erase_boxed),
+                end: end.map(erase_boxed),
+                value: erase_boxed(value),
+            },
+            Expr::Call {
+                callee, arguments, ..
+            } => Expr::Call {
+                callee: erase_boxed(callee),
+                arguments: erase_all(arguments),
+                location: Location::new(),
+            },
+            Expr::Loop(body) => Expr::Loop(
+                body.into_iter()
+                    .map(|stmt| located(erase_locations(stmt.into_inner())))
+                    .collect(),
+            ),
+            expr => expr,
+        }
+    }
+
     #[test]
     fn test_number_literal() {
         let program = parse_program("42;");
@@ -59,6 +255,7 @@ mod parser_tests {
             left: Box::new(Expr::Number(1.0)),
             operator: BinaryOp::Add,
             right: Box::new(Expr::Number(2.0)),
+            location: Location::new(),
         })];
         assert_eq!(program, expected);
 
@@ -68,6 +265,7 @@ mod parser_tests {
             left: Box::new(Expr::Number(3.0)),
             operator: BinaryOp::Multiply,
             right: Box::new(Expr::Number(4.0)),
+            location: Location::new(),
         })];
         assert_eq!(program, expected);
     }
@@ -83,7 +281,9 @@ mod parser_tests {
                 left: Box::new(Expr::Number(2.0)),
                 operator: BinaryOp::Multiply,
                 right: Box::new(Expr::Number(3.0)),
+                location: Location::new(),
             }),
+            location: Location::new(),
         })];
         assert_eq!(program, expected);
     }
@@ -97,13 +297,16 @@ mod parser_tests {
                 left: Box::new(Expr::Variable("a".to_string())),
                 operator: BinaryOp::LessThan,
                 right: Box::new(Expr::Variable("b".to_string())),
+                location: Location::new(),
             }),
             operator: BinaryOp::LogicalAnd,
             right: Box::new(Expr::Binary {
                 left: Box::new(Expr::Variable("c".to_string())),
                 operator: BinaryOp::GreaterThan,
                 right: Box::new(Expr::Variable("d".to_string())),
+                location: Location::new(),
             }),
+            location: Location::new(),
         })];
         assert_eq!(program, expected);
     }
@@ -121,6 +324,7 @@ mod parser_tests {
                 left: Box::new(Expr::Variable(left_name.to_string())),
                 operator: expected_op,
                 right: Box::new(Expr::Variable(right_name.to_string())),
+                location: Location::new(),
             })];
             assert_eq!(program, expected);
         }
@@ -143,6 +347,20 @@ mod parser_tests {
             operand: Box::new(Expr::Boolean(true)),
         })];
         assert_eq!(program, expected);
+
+        // Test the keyword spelling of not
+        assert_eq!(parse_program("not true;"), expected);
+        let program = parse_program("not a and b;");
+        let expected = vec![Stmt::Expression(Expr::Binary {
+            left: Box::new(Expr::Unary {
+                operator: UnaryOp::Not,
+                operand: Box::new(Expr::Variable("a".to_string())),
+            }),
+            operator: BinaryOp::LogicalAnd,
+            right: Box::new(Expr::Variable("b".to_string())),
+            location: Location::new(),
+        })];
+        assert_eq!(program, expected);
     }
 
     #[test]
@@ -153,9 +371,11 @@ mod parser_tests {
                 left: Box::new(Expr::Number(1.0)),
                 operator: BinaryOp::Add,
                 right: Box::new(Expr::Number(2.0)),
+                location: Location::new(),
             }),
             operator: BinaryOp::Multiply,
             right: Box::new(Expr::Number(3.0)),
+            location: Location::new(),
         })];
         assert_eq!(program, expected);
     }
@@ -177,6 +397,7 @@ mod parser_tests {
                 left: Box::new(Expr::Variable(left_name.to_string())),
                 operator: expected_op,
                 right: Box::new(Expr::Variable(right_name.to_string())),
+                location: Location::new(),
             })];
             assert_eq!(program, expected);
         }
@@ -192,6 +413,7 @@ mod parser_tests {
                 Expr::Number(2.0),
                 Expr::Variable("x".to_string()),
             ],
+            location: Location::new(),
         })];
         assert_eq!(program, expected);
     }
@@ -206,19 +428,47 @@ mod parser_tests {
         assert_eq!(program, expected);
     }
 
+    #[test]
+    fn test_let_declares_several_names() {
+        let program = parse_program("let a = 1, b, c = a;");
+        let expected = vec![Stmt::MultiVarDecl(vec![
+            ("a".to_string(), Some(Expr::Number(1.0))),
+            ("b".to_string(), None),
+            ("c".to_string(), Some(Expr::Variable("a".to_string()))),
+        ])];
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_doc_comments_attach_to_the_next_function() {
+        let program = parse_program(
+            "/// Greets.\n/// Twice.\nfn hi() {}\n/// stray\nlet x = [1, /// inside\n 2];\nfn bare() {}",
+        );
+        let docs = program
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::FuncDecl { name, doc, .. } => Some((name.as_str(), doc.as_deref())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(docs, vec![("hi", Some("Greets.\nTwice.")), ("bare", None)]);
+    }
+
     #[test]
     fn test_function_declaration_structure() {
         let program = parse_program("fn add(a, b) { return a + b; }");
         let expected = vec![Stmt::FuncDecl {
             name: "add".to_string(),
             params: vec!["a".to_string(), "b".to_string()],
-            body: vec![Stmt::Return {
+            body: vec![located(Stmt::Return {
                 value: Some(Expr::Binary {
                     left: Box::new(Expr::Variable("a".to_string())),
                     operator: BinaryOp::Add,
                     right: Box::new(Expr::Variable("b".to_string())),
+                    location: Location::new(),
                 }),
-            }],
+            })],
+            doc: None,
         }];
         assert_eq!(program, expected);
     }
@@ -231,13 +481,14 @@ mod parser_tests {
                 left: Box::new(Expr::Variable("x".to_string())),
                 operator: BinaryOp::GreaterThan,
                 right: Box::new(Expr::Number(0.0)),
+                location: Location::new(),
             },
-            then_branch: Box::new(Stmt::Block(vec![Stmt::Expression(Expr::Variable(
-                "x".to_string(),
-            ))])),
-            else_branch: Some(Box::new(Stmt::Block(vec![Stmt::Expression(
-                Expr::Variable("y".to_string()),
-            )]))),
+            then_branch: Box::new(located(Stmt::Block(vec![located(Stmt::Expression(
+                Expr::Variable("x".to_string()),
+            ))]))),
+            else_branch: Some(Box::new(located(Stmt::Block(vec![located(
+                Stmt::Expression(Expr::Variable("y".to_string())),
+            )])))),
         }];
         assert_eq!(program, expected);
     }
@@ -250,15 +501,46 @@ mod parser_tests {
                 left: Box::new(Expr::Variable("x".to_string())),
                 operator: BinaryOp::GreaterThan,
                 right: Box::new(Expr::Number(0.0)),
+                location: Location::new(),
             },
-            body: Box::new(Stmt::Block(vec![Stmt::Expression(Expr::Assign {
-                name: "x".to_string(),
-                value: Box::new(Expr::Binary {
-                    left: Box::new(Expr::Variable("x".to_string())),
-                    operator: BinaryOp::Subtract,
-                    right: Box::new(Expr::Number(1.0)),
-                }),
-            })])),
+            body: Box::new(located(Stmt::Block(vec![located(Stmt::Expression(
+                Expr::Assign {
+                    name: "x".to_string(),
+                    value: Box::new(Expr::Binary {
+                        left: Box::new(Expr::Variable("x".to_string())),
+                        operator: BinaryOp::Subtract,
+                        right: Box::new(Expr::Number(1.0)),
+                        location: Location::new(),
+                    }),
+                },
+            ))]))),
+        }];
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_while_let_statement_structure() {
+        let program = parse_program("while let x = next() { }");
+        let expected = vec![Stmt::WhileLet {
+            name: "x".to_string(),
+            initializer: Expr::Call {
+                callee: Box::new(Expr::Variable("next".to_string())),
+                arguments: vec![],
+                location: Location::new(),
+            },
+            body: Box::new(located(Stmt::Block(vec![]))),
+        }];
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_repeat_statement_structure() {
+        let program = parse_program("repeat(3) { print x; }");
+        let expected = vec![Stmt::Repeat {
+            count: Expr::Number(3.0),
+            body: Box::new(located(Stmt::Block(vec![located(Stmt::Print(
+                PrintArgs::new(vec![Expr::Variable("x".to_string())]),
+            ))]))),
         }];
         assert_eq!(program, expected);
     }
@@ -275,7 +557,9 @@ mod parser_tests {
                         left: Box::new(Expr::Number(2.0)),
                         operator: BinaryOp::Multiply,
                         right: Box::new(Expr::Number(3.0)),
+                        location: Location::new(),
                     }),
+                    location: Location::new(),
                 })],
             ),
             (
@@ -285,9 +569,11 @@ mod parser_tests {
                         left: Box::new(Expr::Variable("a".to_string())),
                         operator: BinaryOp::LogicalAnd,
                         right: Box::new(Expr::Variable("b".to_string())),
+                        location: Location::new(),
                     }),
                     operator: BinaryOp::LogicalOr,
                     right: Box::new(Expr::Variable("c".to_string())),
+                    location: Location::new(),
                 })],
             ), // and before or
             (
@@ -299,6 +585,7 @@ mod parser_tests {
                     }),
                     operator: BinaryOp::LogicalAnd,
                     right: Box::new(Expr::Variable("b".to_string())),
+                    location: Location::new(),
                 })],
             ), // not before and
             (
@@ -308,13 +595,16 @@ mod parser_tests {
                         left: Box::new(Expr::Variable("a".to_string())),
                         operator: BinaryOp::LessThan,
                         right: Box::new(Expr::Variable("b".to_string())),
+                        location: Location::new(),
                     }),
                     operator: BinaryOp::LogicalAnd,
                     right: Box::new(Expr::Binary {
                         left: Box::new(Expr::Variable("c".to_string())),
                         operator: BinaryOp::GreaterThan,
                         right: Box::new(Expr::Variable("d".to_string())),
+                        location: Location::new(),
                     }),
+                    location: Location::new(),
                 })],
             ), // comparison before logical
         ];
@@ -338,14 +628,18 @@ mod parser_tests {
                             left: Box::new(Expr::Variable("b".to_string())),
                             operator: BinaryOp::Multiply,
                             right: Box::new(Expr::Variable("c".to_string())),
+                            location: Location::new(),
                         }),
+                        location: Location::new(),
                     }),
                     operator: BinaryOp::Subtract,
                     right: Box::new(Expr::Binary {
                         left: Box::new(Expr::Variable("d".to_string())),
                         operator: BinaryOp::Divide,
                         right: Box::new(Expr::Variable("e".to_string())),
+                        location: Location::new(),
                     }),
+                    location: Location::new(),
                 })],
             ),
             (
@@ -357,13 +651,16 @@ mod parser_tests {
                             left: Box::new(Expr::Variable("a".to_string())),
                             operator: BinaryOp::Add,
                             right: Box::new(Expr::Variable("b".to_string())),
+                            location: Location::new(),
                         },
                         Expr::Binary {
                             left: Box::new(Expr::Variable("c".to_string())),
                             operator: BinaryOp::Multiply,
                             right: Box::new(Expr::Variable("d".to_string())),
+                            location: Location::new(),
                         },
                     ],
+                    location: Location::new(),
                 })],
             ),
             (
@@ -373,13 +670,16 @@ mod parser_tests {
                         left: Box::new(Expr::Variable("a".to_string())),
                         operator: BinaryOp::Add,
                         right: Box::new(Expr::Variable("b".to_string())),
+                        location: Location::new(),
                     }),
                     operator: BinaryOp::Multiply,
                     right: Box::new(Expr::Binary {
                         left: Box::new(Expr::Variable("c".to_string())),
                         operator: BinaryOp::Subtract,
                         right: Box::new(Expr::Variable("d".to_string())),
+                        location: Location::new(),
                     }),
+                    location: Location::new(),
                 })],
             ),
             (
@@ -394,7 +694,9 @@ mod parser_tests {
                         left: Box::new(Expr::Variable("count".to_string())),
                         operator: BinaryOp::GreaterThan,
                         right: Box::new(Expr::Number(0.0)),
+                        location: Location::new(),
                     }),
+                    location: Location::new(),
                 })],
             ),
             (
@@ -404,6 +706,7 @@ mod parser_tests {
                         left: Box::new(Expr::Variable("x".to_string())),
                         operator: BinaryOp::Equal,
                         right: Box::new(Expr::Variable("y".to_string())),
+                        location: Location::new(),
                     }),
                     operator: BinaryOp::LogicalOr,
                     right: Box::new(Expr::Binary {
@@ -411,14 +714,18 @@ mod parser_tests {
                             left: Box::new(Expr::Variable("z".to_string())),
                             operator: BinaryOp::NotEqual,
                             right: Box::new(Expr::Variable("w".to_string())),
+                            location: Location::new(),
                         }),
                         operator: BinaryOp::LogicalAnd,
                         right: Box::new(Expr::Binary {
                             left: Box::new(Expr::Variable("a".to_string())),
                             operator: BinaryOp::LessThan,
                             right: Box::new(Expr::Variable("b".to_string())),
+                            location: Location::new(),
                         }),
+                        location: Location::new(),
                     }),
+                    location: Location::new(),
                 })],
             ),
         ];
@@ -439,6 +746,7 @@ mod parser_tests {
                 Expr::Number(2.0),
                 Expr::Variable("x".to_string()),
             ],
+            location: Location::new(),
         })];
         assert_eq!(result, expected);
     }
@@ -449,6 +757,7 @@ mod parser_tests {
         let expected = vec![Stmt::Expression(Expr::Call {
             callee: Box::new(Expr::Variable("func".to_string())),
             arguments: vec![],
+            location: Location::new(),
         })];
         assert_eq!(program, expected);
     }
@@ -494,6 +803,7 @@ mod parser_tests {
                         left: Box::new(Expr::Variable("b".to_string())),
                         operator: BinaryOp::Add,
                         right: Box::new(Expr::Variable("c".to_string())),
+                        location: Location::new(),
                     }),
                 })],
             ),
@@ -505,6 +815,41 @@ mod parser_tests {
         }
     }
 
+    #[test]
+    fn test_slices() {
+        let variable = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        let number = |n: f64| Box::new(Expr::Number(n));
+
+        let result = parse_program("a[1:3] = [x, y, z];");
+        let expected = vec![Stmt::Expression(Expr::SliceAssign {
+            array: variable("a"),
+            start: Some(number(1.0)),
+            end: Some(number(3.0)),
+            value: Box::new(Expr::Array(vec![
+                Expr::Variable("x".to_string()),
+                Expr::Variable("y".to_string()),
+                Expr::Variable("z".to_string()),
+            ])),
+        })];
+        assert_eq!(result, expected);
+
+        let result = parse_program("a[:i][j:];");
+        let expected = vec![Stmt::Expression(Expr::Slice {
+            array: Box::new(Expr::Slice {
+                array: variable("a"),
+                start: None,
+                end: Some(variable("i")),
+            }),
+            start: Some(variable("j")),
+            end: None,
+        })];
+        assert_eq!(result, expected);
+
+        let tokens = Lexer::new("a[1:2;".to_string()).tokenize().unwrap();
+        let error = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(error.message, "expected ']' after array slice");
+    }
+
     #[test]
     fn test_if_statement() {
         let result = parse_program("if x > 0 { x; }");
@@ -513,10 +858,11 @@ mod parser_tests {
                 left: Box::new(Expr::Variable("x".to_string())),
                 operator: BinaryOp::GreaterThan,
                 right: Box::new(Expr::Number(0.0)),
+                location: Location::new(),
             },
-            then_branch: Box::new(Stmt::Block(vec![Stmt::Expression(Expr::Variable(
-                "x".to_string(),
-            ))])),
+            then_branch: Box::new(located(Stmt::Block(vec![located(Stmt::Expression(
+                Expr::Variable("x".to_string()),
+            ))]))),
             else_branch: None,
         }];
         assert_eq!(result, expected);
@@ -530,15 +876,340 @@ mod parser_tests {
                 left: Box::new(Expr::Variable("x".to_string())),
                 operator: BinaryOp::GreaterThan,
                 right: Box::new(Expr::Number(0.0)),
+                location: Location::new(),
             },
-            then_branch: Box::new(Stmt::Block(vec![Stmt::Expression(Expr::Variable(
+            then_branch: Box::new(located(Stmt::Block(vec![located(Stmt::Expression(
+                Expr::Variable("x".to_string()),
+            ))]))),
+            else_branch: Some(Box::new(located(Stmt::Block(vec![located(
+                Stmt::Expression(Expr::Variable("y".to_string())),
+            )])))),
+        }];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_elif_ladder_nests_like_else_if() {
+        let result = parse_program("if a { x; } elif b { y; } elif c z; else { w; }");
+        let block = |name: &str| {
+            Box::new(located(Stmt::Block(vec![located(Stmt::Expression(
+                Expr::Variable(name.to_string()),
+            ))])))
+        };
+        let expected = vec![Stmt::If {
+            condition: Expr::Variable("a".to_string()),
+            then_branch: block("x"),
+            else_branch: Some(Box::new(located(Stmt::If {
+                condition: Expr::Variable("b".to_string()),
+                then_branch: block("y"),
+                else_branch: Some(Box::new(located(Stmt::If {
+                    condition: Expr::Variable("c".to_string()),
+                    then_branch: Box::new(located(Stmt::Expression(Expr::Variable(
+                        "z".to_string(),
+                    )))),
+                    else_branch: Some(block("w")),
+                }))),
+            }))),
+        }];
+        assert_eq!(result, expected);
+
+        let else_if = parse_program("if a { x; } else if b { y; } else { w; }");
+        let elif = parse_program("if a { x; } elif b { y; } else { w; }");
+        assert_eq!(elif, else_if);
+    }
+
+    #[test]
+    fn test_braceless_if_statement() {
+        let result = parse_program("if x print x; else print y;");
+        let expected = vec![Stmt::If {
+            condition: Expr::Variable("x".to_string()),
+            then_branch: Box::new(located(Stmt::Print(PrintArgs::new(vec![Expr::Variable(
                 "x".to_string(),
-            ))])),
-            else_branch: Some(Box::new(Stmt::Block(vec![Stmt::Expression(
+            )])))),
+            else_branch: Some(Box::new(located(Stmt::Print(PrintArgs::new(vec![
                 Expr::Variable("y".to_string()),
-            )]))),
+            ]))))),
+        }];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_dangling_else_binds_to_nearest_if() {
+        let result = parse_program("if a if b x; else y;");
+        let expected = vec![Stmt::If {
+            condition: Expr::Variable("a".to_string()),
+            then_branch: Box::new(located(Stmt::If {
+                condition: Expr::Variable("b".to_string()),
+                then_branch: Box::new(located(Stmt::Expression(Expr::Variable("x".to_string())))),
+                else_branch: Some(Box::new(located(Stmt::Expression(Expr::Variable(
+                    "y".to_string(),
+                ))))),
+            })),
+            else_branch: None,
+        }];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_braceless_loop_bodies() {
+        let result = parse_program("while x x = 0; repeat 2 break;");
+        let expected = vec![
+            Stmt::While {
+                condition: Expr::Variable("x".to_string()),
+                body: Box::new(located(Stmt::Expression(Expr::Assign {
+                    name: "x".to_string(),
+                    value: Box::new(Expr::Number(0.0)),
+                }))),
+            },
+            Stmt::Repeat {
+                count: Expr::Number(2.0),
+                body: Box::new(located(Stmt::Break { value: None })),
+            },
+        ];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_literal_logical_operands_warn() {
+        let warnings = |source: &str| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let mut parser = Parser::new(tokens);
+            parser.parse().unwrap();
+            parser
+                .warnings()
+                .iter()
+                .map(|warning| warning.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            warnings("5 and 3;"),
+            vec!["1:3: Warning: number literal used as a boolean; compare explicitly, as in 'x != 0'"]
+        );
+        assert_eq!(
+            warnings("let s = \"\";\nprint not \"x\", s or \"y\";"),
+            vec![
+                "2:7: Warning: string literal used as a boolean; compare explicitly, as in 's != \"\"'",
+                "2:18: Warning: string literal used as a boolean; compare explicitly, as in 's != \"\"'",
+            ]
+        );
+
+        for source in [
+            "a > 0 and b > 0;",
+            "a or b;",
+            "not done;",
+            "true and !nil;",
+            "5 + 3;",
+        ] {
+            assert!(warnings(source).is_empty(), "for {source:?}");
+        }
+    }
+
+    #[test]
+    fn test_raw_identifiers_name_keywords() {
+        let result = parse_program("let r#let = 1; print r#let; fn r#fn(r#if) { return r#if; }");
+        let expected = vec![
+            Stmt::VarDecl {
+                name: "let".to_string(),
+                initializer: Some(Expr::Number(1.0)),
+            },
+            Stmt::Print(PrintArgs {
+                values: vec![Expr::Variable("let".to_string())],
+                sep: None,
+                end: None,
+            }),
+            Stmt::FuncDecl {
+                name: "fn".to_string(),
+                params: vec!["if".to_string()],
+                body: vec![located(Stmt::Return {
+                    value: Some(Expr::Variable("if".to_string())),
+                })],
+                doc: None,
+            },
+        ];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_loop_expression() {
+        let result = parse_program("let x = loop { break 42; }; loop { break; }");
+        let expected = vec![
+            Stmt::VarDecl {
+                name: "x".to_string(),
+                initializer: Some(Expr::Loop(vec![located(Stmt::Break {
+                    value: Some(Expr::Number(42.0)),
+                })])),
+            },
+            Stmt::Expression(Expr::Loop(vec![located(Stmt::Break { value: None })])),
+        ];
+        assert_eq!(result, expected);
+
+        // a function declared in a loop returns from itself
+        parse_program("loop { fn f() { while true { break; } return 1; } break f(); }");
+    }
+
+    #[test]
+    fn test_loop_expression_errors() {
+        for (source, message) in [
+            (
+                "print loop { break; };",
+                "a 'loop' can only start a statement or initialize a 'let'",
+            ),
+            (
+                "x = loop { break 1; };",
+                "a 'loop' can only start a statement or initialize a 'let'",
+            ),
+            (
+                "while true { break 1; }",
+                "only 'break' in a 'loop' can carry a value",
+            ),
+            (
+                "loop { repeat 2 { break 1; } }",
+                "only 'break' in a 'loop' can carry a value",
+            ),
+        ] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let error = Parser::new(tokens).parse().unwrap_err();
+            assert_eq!(error.message, message, "for {source:?}");
+        }
+    }
+
+    #[test]
+    fn test_for_in_patterns() {
+        let name = |name: &str| Pattern::Name(name.to_string());
+        let result = parse_program("for (i, (k, v)) in pairs {} for x in xs {}");
+        let expected = vec![
+            Stmt::ForIn {
+                pattern: Pattern::Tuple(vec![
+                    name("i"),
+                    Pattern::Tuple(vec![name("k"), name("v")]),
+                ]),
+                iterable: Expr::Variable("pairs".to_string()),
+                body: Box::new(located(Stmt::Block(vec![]))),
+            },
+            Stmt::ForIn {
+                pattern: name("x"),
+                iterable: Expr::Variable("xs".to_string()),
+                body: Box::new(located(Stmt::Block(vec![]))),
+            },
+        ];
+        assert_eq!(result, expected);
+
+        // `in` is only special after a pattern, so a C-style `for` may use it
+        let result = parse_program("for in = 0; in < 1; in += 1 {}");
+        assert!(matches!(result[0], Stmt::For { .. }));
+    }
+
+    #[test]
+    fn test_switch_statement_structure() {
+        let result = parse_program("switch x { case 1: case 2: print 2; break; default: }");
+        let expected = vec![Stmt::Switch {
+            subject: Expr::Variable("x".to_string()),
+            cases: vec![
+                SwitchCase {
+                    value: Some(Expr::Number(1.0)),
+                    body: vec![],
+                },
+                SwitchCase {
+                    value: Some(Expr::Number(2.0)),
+                    body: vec![
+                        located(Stmt::Print(PrintArgs::new(vec![Expr::Number(2.0)]))),
+                        located(Stmt::Break { value: None }),
+                    ],
+                },
+                SwitchCase {
+                    value: None,
+                    body: vec![],
+                },
+            ],
         }];
         assert_eq!(result, expected);
+
+        // `default` is still a name everywhere but a label
+        parse_program("switch x { default: print default(nil, 1); }");
+    }
+
+    #[test]
+    fn test_switch_errors() {
+        for (source, message) in [
+            (
+                "switch x { print 1; }",
+                "expected 'case' or 'default' in 'switch'",
+            ),
+            (
+                "switch x { default: default: }",
+                "a 'switch' can only have one 'default'",
+            ),
+            (
+                "switch x { case 1 print 1; }",
+                "expected ':' after case label",
+            ),
+            (
+                "switch x { case 1: continue; }",
+                "'continue' statement must be inside a loop",
+            ),
+            (
+                "loop { switch x { case 1: break 2; } }",
+                "only 'break' in a 'loop' can carry a value",
+            ),
+        ] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let error = Parser::new(tokens).parse().unwrap_err();
+            assert_eq!(error.message, message, "for {source:?}");
+        }
+    }
+
+    #[test]
+    fn test_braceless_body_rejects_declaration() {
+        let tokens = Lexer::new("if x let y = 1;".to_string())
+            .tokenize()
+            .unwrap();
+        let error = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(error.message, "declaration must be inside a block");
+        assert_eq!(error.location.map(|l| l.column), Some(6));
+    }
+
+    #[test]
+    fn test_braceless_body_is_not_taken_into_the_condition() {
+        // each body would continue the condition, which takes it first
+        for (source, column) in [
+            ("if f (g)();", 11),
+            ("if x -1;", 8),
+            ("if (f) (g)();", 13),
+            ("if (x) -1;", 10),
+            ("while x -1;", 11),
+            ("repeat n (g)();", 15),
+            ("for x in xs [0] = 1;", 20),
+            ("for let i = 0; i < 2; i += 1 (g)();", 35),
+        ] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let error = Parser::new(tokens).parse().unwrap_err();
+            assert_eq!(
+                error.message, "a body without braces must start with a keyword or a name",
+                "for {source:?}"
+            );
+            assert_eq!(
+                error.location.map(|l| l.column),
+                Some(column),
+                "for {source:?}"
+            );
+        }
+
+        // braces say where the body starts
+        let body = located(Stmt::Expression(Expr::Call {
+            callee: Box::new(Expr::Variable("g".to_string())),
+            arguments: vec![],
+            location: Location::new(),
+        }));
+        assert_eq!(
+            parse_program("if f { (g)(); }"),
+            vec![Stmt::If {
+                condition: Expr::Variable("f".to_string()),
+                then_branch: Box::new(located(Stmt::Block(vec![body]))),
+                else_branch: None,
+            }]
+        );
+        parse_program("if x print -1; if (a) + (b) { print 1; } if x y = 1; while (x) f();");
     }
 
     #[test]
@@ -549,15 +1220,19 @@ mod parser_tests {
                 left: Box::new(Expr::Variable("x".to_string())),
                 operator: BinaryOp::GreaterThan,
                 right: Box::new(Expr::Number(0.0)),
+                location: Location::new(),
             },
-            body: Box::new(Stmt::Block(vec![Stmt::Expression(Expr::Assign {
-                name: "x".to_string(),
-                value: Box::new(Expr::Binary {
-                    left: Box::new(Expr::Variable("x".to_string())),
-                    operator: BinaryOp::Subtract,
-                    right: Box::new(Expr::Number(1.0)),
-                }),
-            })])),
+            body: Box::new(located(Stmt::Block(vec![located(Stmt::Expression(
+                Expr::Assign {
+                    name: "x".to_string(),
+                    value: Box::new(Expr::Binary {
+                        left: Box::new(Expr::Variable("x".to_string())),
+                        operator: BinaryOp::Subtract,
+                        right: Box::new(Expr::Number(1.0)),
+                        location: Location::new(),
+                    }),
+                },
+            ))]))),
         }];
         assert_eq!(result, expected);
     }
@@ -567,14 +1242,15 @@ mod parser_tests {
         let result = parse_program("for let i = 0; i < 10; i += 1 { print i; }");
 
         let expected = vec![Stmt::For {
-            initializer: Some(Box::new(Stmt::VarDecl {
+            initializer: Some(Box::new(located(Stmt::VarDecl {
                 name: "i".to_string(),
                 initializer: Some(Expr::Number(0.0)),
-            })),
+            }))),
             condition: Expr::Binary {
                 left: Box::new(Expr::Variable("i".to_string())),
                 operator: BinaryOp::LessThan,
                 right: Box::new(Expr::Number(10.0)),
+                location: Location::new(),
             },
             increment: Some(Expr::Assign {
                 name: "i".to_string(),
@@ -582,29 +1258,60 @@ mod parser_tests {
                     left: Box::new(Expr::Variable("i".to_string())),
                     operator: BinaryOp::Add,
                     right: Box::new(Expr::Number(1.0)),
+                    location: Location::new(),
                 }),
             }),
-            body: Box::new(Stmt::Block(vec![Stmt::Print(vec![Expr::Variable(
-                "i".to_string(),
-            )])])),
+            body: Box::new(located(Stmt::Block(vec![located(Stmt::Print(
+                PrintArgs::new(vec![Expr::Variable("i".to_string())]),
+            ))]))),
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_for_with_every_clause_omitted() {
+        let result = parse_program("for ;; {}");
+
+        let expected = vec![Stmt::For {
+            initializer: None,
+            condition: Expr::Boolean(true),
+            increment: None,
+            body: Box::new(located(Stmt::Block(vec![]))),
         }];
 
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_destructure_declaration() {
+        let result = parse_program("let (q, r) = divmod(7, 2);");
+        let expected = vec![Stmt::DestructureDecl {
+            names: vec!["q".to_string(), "r".to_string()],
+            initializer: Expr::Call {
+                callee: Box::new(Expr::Variable("divmod".to_string())),
+                arguments: vec![Expr::Number(7.0), Expr::Number(2.0)],
+                location: Location::new(),
+            },
+        }];
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn test_function_declaration() {
         let result = parse_program("fn add(a, b) { return a + b; }");
         let expected = vec![Stmt::FuncDecl {
             name: "add".to_string(),
             params: vec!["a".to_string(), "b".to_string()],
-            body: vec![Stmt::Return {
+            body: vec![located(Stmt::Return {
                 value: Some(Expr::Binary {
                     left: Box::new(Expr::Variable("a".to_string())),
                     operator: BinaryOp::Add,
                     right: Box::new(Expr::Variable("b".to_string())),
+                    location: Location::new(),
                 }),
-            }],
+            })],
+            doc: None,
         }];
         assert_eq!(result, expected);
     }
@@ -615,26 +1322,277 @@ mod parser_tests {
         let expected = vec![Stmt::FuncDecl {
             name: "hello".to_string(),
             params: vec![],
-            body: vec![Stmt::Return {
+            body: vec![located(Stmt::Return {
                 value: Some(Expr::String("world".to_string())),
-            }],
+            })],
+            doc: None,
         }];
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_return_without_semicolon_before_brace() {
+        let result = parse_program("fn one() { return 1 } fn none() { return }");
+        let expected = vec![
+            Stmt::FuncDecl {
+                name: "one".to_string(),
+                params: vec![],
+                body: vec![located(Stmt::Return {
+                    value: Some(Expr::Number(1.0)),
+                })],
+                doc: None,
+            },
+            Stmt::FuncDecl {
+                name: "none".to_string(),
+                params: vec![],
+                body: vec![located(Stmt::Return { value: None })],
+                doc: None,
+            },
+        ];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_return_without_semicolon_mid_block() {
+        let mut lexer = Lexer::new("fn f() { return 1 print 2; }".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let result = Parser::new(tokens).parse();
+
+        let error = result.unwrap_err();
+        assert!(error.message.contains("expected ';'"));
+    }
+
+    #[test]
+    fn test_print_separator_and_terminator() {
+        let program = parse_program("print a, b, sep = \", \", end = \"\";");
+        let expected = vec![Stmt::Print(PrintArgs {
+            values: vec![
+                Expr::Variable("a".to_string()),
+                Expr::Variable("b".to_string()),
+            ],
+            sep: Some(Expr::String(", ".to_string())),
+            end: Some(Expr::String("".to_string())),
+        })];
+        assert_eq!(program, expected);
+
+        // only `sep =` and `end =` after a value are options, other
+        // assignments are values
+        let program = parse_program("eprint end = 0, end, sep == 1, x = 2;");
+        let expected = vec![Stmt::Eprint(PrintArgs::new(vec![
+            Expr::Assign {
+                name: "end".to_string(),
+                value: Box::new(Expr::Number(0.0)),
+            },
+            Expr::Variable("end".to_string()),
+            Expr::Binary {
+                left: Box::new(Expr::Variable("sep".to_string())),
+                operator: BinaryOp::Equal,
+                right: Box::new(Expr::Number(1.0)),
+                location: Location::new(),
+            },
+            Expr::Assign {
+                name: "x".to_string(),
+                value: Box::new(Expr::Number(2.0)),
+            },
+        ]))];
+        assert_eq!(program, expected);
+
+        for (source, expected) in [
+            (
+                "print 1, sep = \"-\", 2;",
+                "values to print must come before 'sep' and 'end'",
+            ),
+            (
+                "print 1, end = \"\", end = \"!\";",
+                "duplicate print option 'end'",
+            ),
+        ] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let error = Parser::new(tokens).parse().unwrap_err();
+            assert_eq!(error.message, expected, "for {source:?}");
+        }
+    }
+
+    #[test]
+    fn test_keywords_cannot_be_names() {
+        for (source, keyword, column) in [
+            ("let fn = 1;", "fn", 5),
+            ("let a, while;", "while", 8),
+            ("fn f(x, return) {}", "return", 9),
+            ("fn print() {}", "print", 4),
+            ("let (a, nil) = [1, 2];", "nil", 9),
+            ("while let true = x {}", "true", 11),
+        ] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let error = Parser::new(tokens).parse().unwrap_err();
+            assert_eq!(
+                error.message,
+                format!("'{keyword}' is a reserved keyword and cannot be used as a name"),
+                "for {source:?}"
+            );
+            assert_eq!(error.location.map(|l| l.column), Some(column));
+        }
+
+        let tokens = Lexer::new("let 1 = 2;".to_string()).tokenize().unwrap();
+        let error = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(error.message, "expected identifier");
+    }
+
+    #[test]
+    fn test_missing_paren_messages_name_the_context() {
+        for (source, expected, column) in [
+            ("len(abs(x);", "expected ')' to close call to 'len'", 11),
+            ("print (len(x);", "expected ')' to close expression", 14),
+            ("f(1)(2;", "expected ')' to close call", 7),
+            ("let y = (1 + 2;", "expected ')' to close expression", 15),
+            ("g((1 + 2);", "expected ')' to close call to 'g'", 10),
+        ] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let error = Parser::new(tokens).parse().unwrap_err();
+            assert_eq!(error.message, expected, "for {source:?}");
+            assert_eq!(error.location.map(|l| l.column), Some(column));
+        }
+    }
+
+    #[test]
+    fn test_unterminated_block_span() {
+        let source = "fn f() {\n  let x = 1;\n";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let error = Parser::new(tokens).parse().unwrap_err();
+
+        assert_eq!(error.location.map(|l| (l.line, l.column)), Some((1, 8)));
+        assert_eq!(error.end.map(|l| l.line), Some(3));
+        let expected = [
+            "1:8: Syntax Error: unterminated block, expected '}'",
+            "   1 | fn f() {",
+            "     |        ^",
+            "   2 |   let x = 1;",
+            "     |   ^^^^^^^^^^",
+        ]
+        .join("\n");
+        assert_eq!(error.with_source(source).to_string(), expected);
+    }
+
+    #[test]
+    fn test_semicolon_after_block_is_empty_statement() {
+        let program = parse_program("while x { };\nfn f() { ; }");
+        let expected = vec![
+            Stmt::While {
+                condition: Expr::Variable("x".to_string()),
+                body: Box::new(located(Stmt::Block(vec![]))),
+            },
+            Stmt::Empty,
+            Stmt::FuncDecl {
+                name: "f".to_string(),
+                params: vec![],
+                body: vec![located(Stmt::Empty)],
+                doc: None,
+            },
+        ];
+        assert_eq!(program, expected);
+    }
+
     #[test]
     fn test_block_statement() {
         let result = parse_program("{ let x = 1; x; }");
         let expected = vec![Stmt::Block(vec![
-            Stmt::VarDecl {
+            located(Stmt::VarDecl {
                 name: "x".to_string(),
                 initializer: Some(Expr::Number(1.0)),
-            },
-            Stmt::Expression(Expr::Variable("x".to_string())),
+            }),
+            located(Stmt::Expression(Expr::Variable("x".to_string()))),
         ])];
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_nested_statement_locations() {
+        let tokens = Lexer::new("fn f() {\n  if true {\n    print 1;\n  }\n}".to_string())
+            .tokenize()
+            .unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let Stmt::FuncDecl { body, .. } = program[0].as_inner() else {
+            panic!("expected a function declaration");
+        };
+        assert_eq!((body[0].location().line, body[0].location().column), (2, 3));
+
+        let Stmt::If { then_branch, .. } = body[0].as_inner() else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(
+            (then_branch.location().line, then_branch.location().column),
+            (2, 11)
+        );
+        let Stmt::Block(stmts) = then_branch.as_inner() else {
+            panic!("expected a block");
+        };
+        assert_eq!(
+            (stmts[0].location().line, stmts[0].location().column),
+            (3, 5)
+        );
+    }
+
+    #[test]
+    fn test_unbraced_bodies_have_locations() {
+        let tokens = Lexer::new(
+            "while x
+  if y
+    print 1;
+  else
+    z = 2;"
+                .to_string(),
+        )
+        .tokenize()
+        .unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let Stmt::While { body, .. } = program[0].as_inner() else {
+            panic!("expected a while loop");
+        };
+        assert_eq!((body.location().line, body.location().column), (2, 3));
+        let Stmt::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } = body.as_inner()
+        else {
+            panic!("expected an if statement with an else branch");
+        };
+        assert_eq!(
+            (then_branch.location().line, then_branch.location().column),
+            (3, 5)
+        );
+        assert_eq!(
+            (else_branch.location().line, else_branch.location().column),
+            (5, 5)
+        );
+    }
+
+    #[test]
+    fn test_binary_location_is_operator() {
+        let tokens = Lexer::new("x = 1 +\n  y * 2;".to_string())
+            .tokenize()
+            .unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let Stmt::Expression(Expr::Assign { value, .. }) = program[0].as_inner() else {
+            panic!("expected an assignment");
+        };
+        let Expr::Binary {
+            right, location, ..
+        } = value.as_ref()
+        else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!((location.line, location.column), (1, 7));
+
+        let Expr::Binary { location, .. } = right.as_ref() else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!((location.line, location.column), (2, 5));
+    }
+
     #[test]
     fn test_nested_blocks() {
         let input = r#"
@@ -648,21 +1606,22 @@ mod parser_tests {
         "#;
         let result = parse_program(input);
         let expected = vec![Stmt::Block(vec![
-            Stmt::VarDecl {
+            located(Stmt::VarDecl {
                 name: "x".to_string(),
                 initializer: Some(Expr::Number(1.0)),
-            },
-            Stmt::Block(vec![
-                Stmt::VarDecl {
+            }),
+            located(Stmt::Block(vec![
+                located(Stmt::VarDecl {
                     name: "y".to_string(),
                     initializer: Some(Expr::Number(2.0)),
-                },
-                Stmt::Expression(Expr::Binary {
+                }),
+                located(Stmt::Expression(Expr::Binary {
                     left: Box::new(Expr::Variable("x".to_string())),
                     operator: BinaryOp::Add,
                     right: Box::new(Expr::Variable("y".to_string())),
-                }),
-            ]),
+                    location: Location::new(),
+                })),
+            ])),
         ])];
         assert_eq!(result, expected);
     }
@@ -676,13 +1635,16 @@ mod parser_tests {
                     left: Box::new(Expr::Variable("a".to_string())),
                     operator: BinaryOp::Add,
                     right: Box::new(Expr::Variable("b".to_string())),
+                    location: Location::new(),
                 },
                 Expr::Binary {
                     left: Box::new(Expr::Variable("c".to_string())),
                     operator: BinaryOp::Multiply,
                     right: Box::new(Expr::Variable("d".to_string())),
+                    location: Location::new(),
                 },
             ],
+            location: Location::new(),
         })];
         assert_eq!(result, expected);
     }
@@ -699,7 +1661,9 @@ mod parser_tests {
                         left: Box::new(Expr::Variable("a".to_string())),
                         operator: BinaryOp::Add,
                         right: Box::new(Expr::Variable("b".to_string())),
+                        location: Location::new(),
                     }],
+                    location: Location::new(),
                 },
                 Expr::Call {
                     callee: Box::new(Expr::Variable("func3".to_string())),
@@ -709,10 +1673,14 @@ mod parser_tests {
                         right: Box::new(Expr::Call {
                             callee: Box::new(Expr::Variable("func4".to_string())),
                             arguments: vec![Expr::Variable("d".to_string())],
+                            location: Location::new(),
                         }),
+                        location: Location::new(),
                     }],
+                    location: Location::new(),
                 },
             ],
+            location: Location::new(),
         })];
         assert_eq!(result, expected);
     }
@@ -743,9 +1711,10 @@ mod parser_tests {
             Stmt::FuncDecl {
                 name: "test".to_string(),
                 params: vec![],
-                body: vec![Stmt::Return {
+                body: vec![located(Stmt::Return {
                     value: Some(Expr::Variable("x".to_string())),
-                }],
+                })],
+                doc: None,
             },
         ];
         assert_eq!(result, expected);
@@ -765,7 +1734,9 @@ mod parser_tests {
                     left: Box::new(Expr::Variable("b".to_string())),
                     operator: BinaryOp::Multiply,
                     right: Box::new(Expr::Variable("c".to_string())),
+                    location: Location::new(),
                 }),
+                location: Location::new(),
             }),
         })];
         assert_eq!(result, expected);
@@ -781,13 +1752,30 @@ mod parser_tests {
                 left: Box::new(Expr::Variable("a".to_string())),
                 operator: BinaryOp::LessThan,
                 right: Box::new(Expr::Variable("b".to_string())),
+                location: Location::new(),
             }),
             operator: BinaryOp::LogicalAnd,
             right: Box::new(Expr::Binary {
                 left: Box::new(Expr::Variable("b".to_string())),
                 operator: BinaryOp::LessThan,
                 right: Box::new(Expr::Variable("c".to_string())),
+                location: Location::new(),
             }),
+            location: Location::new(),
+        })];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_chained_comparison_desugar() {
+        let result = parse_program("a < b <= c;");
+        let expected = vec![Stmt::Expression(Expr::ChainedComparison {
+            operands: vec![
+                Expr::Variable("a".to_string()),
+                Expr::Variable("b".to_string()),
+                Expr::Variable("c".to_string()),
+            ],
+            operators: vec![BinaryOp::LessThan, BinaryOp::LessEqual],
         })];
         assert_eq!(result, expected);
     }
@@ -803,7 +1791,9 @@ mod parser_tests {
                     right: Box::new(Expr::Call {
                         callee: Box::new(Expr::Variable("func".to_string())),
                         arguments: vec![Expr::Variable("y".to_string())],
+                        location: Location::new(),
                     }),
+                    location: Location::new(),
                 })],
             ),
             (
@@ -813,7 +1803,9 @@ mod parser_tests {
                     arguments: vec![Expr::Call {
                         callee: Box::new(Expr::Variable("func2".to_string())),
                         arguments: vec![Expr::Variable("x".to_string())],
+                        location: Location::new(),
                     }],
+                    location: Location::new(),
                 })],
             ),
             (
@@ -822,12 +1814,15 @@ mod parser_tests {
                     left: Box::new(Expr::Call {
                         callee: Box::new(Expr::Variable("func".to_string())),
                         arguments: vec![Expr::Variable("x".to_string())],
+                        location: Location::new(),
                     }),
                     operator: BinaryOp::Add,
                     right: Box::new(Expr::Call {
                         callee: Box::new(Expr::Variable("func".to_string())),
                         arguments: vec![Expr::Variable("y".to_string())],
+                        location: Location::new(),
                     }),
+                    location: Location::new(),
                 })],
             ),
         ];
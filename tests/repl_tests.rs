@@ -0,0 +1,132 @@
+use mylang::{
+    error::ErrorType,
+    repl::{Backend, Repl},
+};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// Writer handle whose contents remain readable after the REPL takes ownership
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    /// What has been written since the last call
+    fn take(&self) -> String {
+        String::from_utf8(self.0.take()).unwrap()
+    }
+}
+
+#[test]
+fn test_switching_backends() {
+    let output = SharedBuffer::default();
+    let mut repl = Repl::with_output(output.clone());
+    assert_eq!(repl.backend(), Backend::TreeWalk);
+
+    for (input, expected) in [
+        ("let x = 2;", ""),
+        ("fn square(n) { return n * n; }", ""),
+        ("print square(x);", "4\n"),
+    ] {
+        assert_eq!(repl.handle(input).unwrap(), None);
+        assert_eq!(output.take(), expected, "tree-walk output of {input:?}");
+    }
+
+    assert!(repl.handle(":vm").unwrap().is_some());
+    assert_eq!(repl.backend(), Backend::Vm);
+    // each backend keeps its own globals
+    let error = repl.handle("print x;").unwrap_err();
+    assert_eq!(error.error_type, ErrorType::VmRuntime);
+
+    // functions from earlier lines stay callable as later lines add code
+    for (input, expected) in [
+        ("let y = 3;", ""),
+        ("fn cube(n) { return n * n * n; }", ""),
+        ("print cube(y);", "27\n"),
+        ("y = cube(2);", ""),
+        ("print y, cube(y);", "8 512\n"),
+    ] {
+        assert_eq!(repl.handle(input).unwrap(), None);
+        assert_eq!(output.take(), expected, "vm output of {input:?}");
+    }
+
+    // a failed line leaves the session usable
+    assert!(repl.handle("print 1 / nil;").is_err());
+    assert_eq!(repl.handle("print cube(y) - 500;").unwrap(), None);
+    assert_eq!(output.take(), "12\n");
+
+    repl.handle(":tr").unwrap();
+    assert_eq!(repl.backend(), Backend::TreeWalk);
+    assert_eq!(repl.handle("print square(x);").unwrap(), None);
+    assert_eq!(output.take(), "4\n");
+    let error = repl.handle("print y;").unwrap_err();
+    assert_eq!(error.error_type, ErrorType::Runtime);
+}
+
+#[test]
+fn test_dumping_the_last_input() {
+    let mut repl = Repl::with_output(io::sink());
+    assert_eq!(repl.handle(":ast").unwrap().unwrap(), "no input yet");
+
+    repl.handle(":vm").unwrap();
+    repl.handle("let total = 1;").unwrap();
+    repl.handle("print total + 2;").unwrap();
+
+    let ast = repl.handle(":ast").unwrap().unwrap();
+    assert!(ast.starts_with("Print("), "{ast}");
+    assert!(ast.contains("Variable(\n"), "{ast}");
+
+    // only the last line's code is listed, though it uses an earlier global
+    let bytecode = repl.handle(":bytecode").unwrap().unwrap();
+    let code = bytecode.split("=== Code ===\n").nth(1).unwrap();
+    assert_eq!(code.lines().count(), 4, "{code}");
+    assert!(code.contains("GetGlobal"), "{code}");
+    assert!(!code.contains("DefineGlobal"), "{code}");
+
+    let reply = repl.handle(":quit").unwrap().unwrap();
+    assert!(reply.starts_with("unknown command ':quit'"), "{reply}");
+}
+
+#[test]
+fn test_lone_expressions_show_their_value() {
+    let output = SharedBuffer::default();
+    let mut repl = Repl::with_output(output.clone());
+
+    for backend in [":tr", ":vm"] {
+        repl.handle(backend).unwrap();
+        for (input, shown) in [
+            ("3 * 4", Some("12")),
+            ("3 * 4;", Some("12")),
+            ("let x = [1, 2];", None),
+            ("x", Some("[1, 2]")),
+            ("x[0] = \"one\"", None),
+            ("len(x) > 1 and x[0]", Some("one")),
+            ("nil", None),
+            ("print x", None),
+        ] {
+            let reply = repl.handle(input).unwrap();
+            assert_eq!(reply.as_deref(), shown, "{backend} reply to {input:?}");
+        }
+        // only `print` writes to the output; shown values are replies
+        assert_eq!(output.take(), "[one, 2]\n");
+
+        let error = repl.handle("1 / nil").unwrap_err();
+        assert_eq!(error.location.map(|l| l.line), Some(1));
+        // errors point at the input as typed, not with a `;` added
+        let error = repl.handle("let = 1").unwrap_err();
+        assert_eq!(error.error_type, ErrorType::Syntax);
+        assert_eq!(error.location.map(|l| l.column), Some(5));
+    }
+}
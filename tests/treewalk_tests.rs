@@ -31,11 +31,13 @@ mod file_tests {
         test_arrays => "arrays.myl",
         test_break_continue => "break_continue.myl",
         test_builtins => "builtins.myl",
+        test_chained_comparison => "chained_comparison.myl",
         test_complex_for_break_continue => "complex_for_break_continue.myl",
         test_complex_break_continue => "complex_break_continue.myl",
         test_complex_closures => "complex_closures.myl",
         test_compound_assignment => "compound_assignment.myl",
         test_conditionals => "conditionals.myl",
+        test_destructuring => "destructuring.myl",
         test_edge_cases => "edge_cases.myl",
         test_else_if => "else_if.myl",
         test_enclosing => "enclosing.myl",
@@ -43,9 +45,282 @@ mod file_tests {
         test_fibonacci => "fibonacci.myl",
         test_functions => "functions.myl",
         test_hello => "hello.myl",
+        test_hoisting => "hoisting.myl",
+        test_loop_locals => "loop_locals.myl",
         test_loops => "loops.myl",
+        test_repeat => "repeat.myl",
         test_scoping => "scoping.myl",
         test_short_circuit => "short_circuit.myl",
-        test_variables => "variables.myl"
+        test_variables => "variables.myl",
+        test_while_let => "while_let.myl"
     );
 }
+
+#[cfg(test)]
+mod interpreter_tests {
+    use super::*;
+    use mylang::error::ErrorType;
+
+    fn run(source: &str, interpreter: &mut Interpreter) -> mylang::error::Result<()> {
+        run_with_tr(source.to_string(), interpreter)
+    }
+
+    #[test]
+    fn test_destructure_length_mismatch() {
+        let mut interpreter = Interpreter::new();
+        let error = run("let (a, b) = divmod(1, 2) + [3];", &mut interpreter).unwrap_err();
+        assert!(error
+            .message
+            .contains("cannot destructure array of length 3 into 2 names"));
+    }
+
+    #[test]
+    fn test_nested_error_reports_own_line() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn f(a) {\n  if true {\n    return a[5];\n  }\n}\nf([1]);";
+        let error = run(source, &mut interpreter).unwrap_err();
+        assert_eq!(error.location.unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_binary_error_reports_operator_column() {
+        let mut interpreter = Interpreter::new();
+        let source = "let x = 1;\nlet y = x + \"a\";";
+        let error = run(source, &mut interpreter).unwrap_err();
+        let location = error.location.unwrap();
+        assert_eq!((location.line, location.column), (2, 11));
+    }
+
+    #[test]
+    fn test_failed_assert_reports_call_location() {
+        let mut interpreter = Interpreter::new();
+        let source = "let x = 1;\nif (x == 1)\n    assert(x == 2, \"x should be 2\");";
+        let error = run(source, &mut interpreter).unwrap_err();
+        assert_eq!(error.message, "assertion failed: x should be 2");
+        let location = error.location.unwrap();
+        assert_eq!((location.line, location.column), (3, 5));
+    }
+
+    #[test]
+    fn test_nested_function_captures_enclosing_locals() {
+        let mut interpreter = Interpreter::new();
+        let source = r#"
+            fn make_adder(n) {
+                let offset = 1;
+                fn add(x) {
+                    return x + n + offset;
+                }
+                offset = 2;
+                return add;
+            }
+            let add_ten = make_adder(10);
+            let add_twenty = make_adder(20);
+            assert(add_ten(1) == 13, "should see the parameter and the last write to offset");
+            assert(add_twenty(1) == 23, "each call should capture its own locals");
+
+            fn make_cell() {
+                let value = 0;
+                fn set(x) { value = x; }
+                fn get() { return value; }
+                return [set, get];
+            }
+            let cell = make_cell();
+            let set = cell[0];
+            let get = cell[1];
+            set(5);
+            assert(get() == 5, "sibling closures should share the captured local");
+        "#;
+        run(source, &mut interpreter).unwrap();
+    }
+
+    #[test]
+    fn test_floor_div_by_zero() {
+        let mut interpreter = Interpreter::new();
+        let error = run("floor_div(1, 0);", &mut interpreter).unwrap_err();
+        assert_eq!(error.message, "floor_div() division by zero");
+    }
+
+    #[test]
+    fn test_numeric_builtins_reject_non_numbers() {
+        let mut interpreter = Interpreter::new();
+        let error = run(r#"sign("a");"#, &mut interpreter).unwrap_err();
+        assert_eq!(error.message, "sign() expects a number, found 'string'");
+        let error = run("is_nan();", &mut interpreter).unwrap_err();
+        assert_eq!(error.message, "is_nan() takes exactly 1 argument (0 given)");
+    }
+
+    #[test]
+    fn test_hex_and_bin_reject_non_integers() {
+        let mut interpreter = Interpreter::new();
+        let error = run("hex(2.5);", &mut interpreter).unwrap_err();
+        assert_eq!(
+            error.message,
+            "hex() expects a non-negative integer, got 2.5"
+        );
+        let error = run("bin(-1);", &mut interpreter).unwrap_err();
+        assert_eq!(
+            error.message,
+            "bin() expects a non-negative integer, got -1"
+        );
+        // past u64, where `as u64` would saturate
+        let error = run("bin(1000000000000000000000000000000);", &mut interpreter).unwrap_err();
+        assert_eq!(
+            error.message,
+            "bin() expects a non-negative integer, got 1000000000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_break_out_of_block_restores_scope() {
+        let mut interpreter = Interpreter::new();
+        let source = r#"
+            let x = 1;
+            while true {
+                let x = 2;
+                break;
+            }
+            assert(x == 1, "the loop body's scope should be closed by break");
+        "#;
+        run(source, &mut interpreter).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_array_index() {
+        let mut interpreter = Interpreter::new();
+        run("let arr = [1, 2];", &mut interpreter).unwrap();
+        for (source, message) in [
+            (
+                "arr[100000000000000000000];",
+                "array index 100000000000000000000 is out of range",
+            ),
+            (
+                "arr[-1];",
+                "array index must be a non-negative integer, got -1",
+            ),
+            (
+                "arr[0.5] = 3;",
+                "array index must be a non-negative integer, got 0.5",
+            ),
+        ] {
+            let error = run(source, &mut interpreter).unwrap_err();
+            assert_eq!(error.message, message, "for {source}");
+        }
+    }
+
+    #[test]
+    fn test_repeat_invalid_count() {
+        let mut interpreter = Interpreter::new();
+        for (source, shown) in [
+            ("repeat 1.5 {}", "1.5"),
+            ("repeat -1 {}", "-1"),
+            ("repeat \"3\" {}", "3"),
+        ] {
+            let error = run(source, &mut interpreter).unwrap_err();
+            assert_eq!(
+                error.message,
+                format!("repeat count must be a non-negative integer, got {shown}")
+            );
+        }
+    }
+
+    const ARRAY_ALIASING: &str = r#"
+        let a = [1, [2]];
+        let b = a;
+        b[0] = 10;
+        let inner = b[1];
+        inner[0] = 20;
+        fn mutate(arr) {
+            arr[0] = 30;
+        }
+        mutate(a);
+    "#;
+
+    #[test]
+    fn test_arrays_share_by_default() {
+        let mut interpreter = Interpreter::new();
+        run(ARRAY_ALIASING, &mut interpreter).unwrap();
+        run(
+            "assert(a[0] == 30); assert(b[0] == 30); assert(a[1][0] == 20);",
+            &mut interpreter,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_copy_arrays_gives_value_semantics() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_copy_arrays(true);
+        run(ARRAY_ALIASING, &mut interpreter).unwrap();
+        run(
+            "assert(a[0] == 1); assert(a[1][0] == 2); assert(b[0] == 10); assert(b[1][0] == 2);",
+            &mut interpreter,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_file_access_disabled_by_default() {
+        let mut interpreter = Interpreter::new();
+        let error = run(r#"read_file("x.txt");"#, &mut interpreter).unwrap_err();
+        assert_eq!(error.message, "file access is disabled");
+    }
+
+    #[test]
+    fn test_read_and_write_file_when_enabled() {
+        let path = std::env::temp_dir().join(format!("mylang_tr_file_{}.txt", std::process::id()));
+        let source = format!(
+            r#"let path = "{}"; write_file(path, "hello"); assert(read_file(path) == "hello");"#,
+            path.display()
+        );
+        let mut interpreter = Interpreter::new();
+        interpreter.set_file_access(true);
+        let result = run(&source, &mut interpreter);
+        let contents = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+
+        result.unwrap();
+        assert_eq!(contents.unwrap(), "hello");
+
+        // the capability survives a reset and can be revoked
+        interpreter.reset();
+        let error = run(r#"read_file("/no/such/file");"#, &mut interpreter).unwrap_err();
+        assert_eq!(error.error_type, ErrorType::Io);
+        interpreter.set_file_access(false);
+        let error = run(r#"read_file("x.txt");"#, &mut interpreter).unwrap_err();
+        assert_eq!(error.message, "file access is disabled");
+    }
+
+    #[test]
+    fn test_last_value_tracks_the_latest_statement() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.last_value().to_string(), "nil");
+
+        run("let x = 2;", &mut interpreter).unwrap();
+        assert_eq!(interpreter.last_value().to_string(), "2");
+        run("x * 21; if x > 0 {}", &mut interpreter).unwrap();
+        assert_eq!(interpreter.last_value().to_string(), "42");
+        run(
+            "fn f() { return \"inner\"; } let y = f(); x;",
+            &mut interpreter,
+        )
+        .unwrap();
+        assert_eq!(interpreter.last_value().to_string(), "2");
+
+        interpreter.reset();
+        assert_eq!(interpreter.last_value().to_string(), "nil");
+    }
+
+    #[test]
+    fn test_reset_clears_definitions() {
+        let mut interpreter = Interpreter::new();
+        run("let x = 1; fn f() { return x; }", &mut interpreter).unwrap();
+        assert!(run("assert(f() == 1);", &mut interpreter).is_ok());
+
+        interpreter.reset();
+
+        let error = run("x;", &mut interpreter).unwrap_err();
+        assert!(error.message.contains("name 'x' is not defined"));
+        assert!(run("f();", &mut interpreter).is_err());
+        assert!(run("assert(len([1, 2]) == 2);", &mut interpreter).is_ok());
+    }
+}
@@ -30,11 +30,13 @@ mod file_tests {
         test_arrays => "arrays.myl",
         test_break_continue => "break_continue.myl",
         test_builtins => "builtins.myl",
+        test_chained_comparison => "chained_comparison.myl",
         test_complex_for_break_continue => "complex_for_break_continue.myl",
         test_complex_break_continue => "complex_break_continue.myl",
         test_complex_closures => "complex_closures.myl",
         test_compound_assignment => "compound_assignment.myl",
         test_conditionals => "conditionals.myl",
+        test_destructuring => "destructuring.myl",
         test_edge_cases => "edge_cases.myl",
         test_else_if => "else_if.myl",
         test_enclosing => "enclosing.myl",
@@ -42,9 +44,883 @@ mod file_tests {
         test_fibonacci => "fibonacci.myl",
         test_functions => "functions.myl",
         test_hello => "hello.myl",
+        test_hoisting => "hoisting.myl",
+        test_loop_locals => "loop_locals.myl",
         test_loops => "loops.myl",
+        test_repeat => "repeat.myl",
         test_scoping => "scoping.myl",
         test_short_circuit => "short_circuit.myl",
-        test_variables => "variables.myl"
+        test_variables => "variables.myl",
+        test_while_let => "while_let.myl"
     );
 }
+
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+
+    #[test]
+    fn test_destructure_length_mismatch() {
+        let error = run_with_vm("let (a, b) = [1, 2, 3];".to_string()).unwrap_err();
+        assert!(error
+            .message
+            .contains("cannot destructure array of length 3 into 2 names"));
+    }
+
+    #[test]
+    fn test_nested_error_reports_own_line() {
+        let source = "fn f(a) {\n  if true {\n    return a[5];\n  }\n}\nf([1]);";
+        let error = run_with_vm(source.to_string()).unwrap_err();
+        assert_eq!(error.error_type, mylang::error::ErrorType::Runtime);
+        assert_eq!(error.location.unwrap().line, 3);
+
+        let source = "fn f() {\n  while true {\n    1(2);\n  }\n}";
+        let error = run_with_vm(source.to_string()).unwrap_err();
+        assert!(error.is_compilation());
+        assert_eq!(error.location.unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_binary_error_reports_operator_column() {
+        let source = "let x = 1;\nlet y = x + \"a\";";
+        let error = run_with_vm(source.to_string()).unwrap_err();
+        let location = error.location.unwrap();
+        assert_eq!((location.line, location.column), (2, 11));
+    }
+
+    #[test]
+    fn test_failed_assert_reports_call_location() {
+        let source = "let x = 1;\nif (x == 1)\n    assert(x == 2, \"x should be 2\");";
+        let error = run_with_vm(source.to_string()).unwrap_err();
+        assert_eq!(error.message, "assertion failed: x should be 2");
+        let location = error.location.unwrap();
+        assert_eq!((location.line, location.column), (3, 5));
+    }
+
+    #[test]
+    fn test_nested_statement_errors_report_their_own_line() {
+        let source = "let x = 1;\nif x\n  while true\n    print y;";
+        let error = run_with_vm(source.to_string()).unwrap_err();
+        assert_eq!(error.message, "undefined variable 'y'");
+        let location = error.location.unwrap();
+        assert_eq!((location.line, location.column), (4, 5));
+    }
+
+    #[test]
+    fn test_floor_div_by_zero() {
+        let error = run_with_vm("floor_div(1, 0);".to_string()).unwrap_err();
+        assert_eq!(error.message, "floor_div() division by zero");
+    }
+
+    #[test]
+    fn test_numeric_builtins_reject_non_numbers() {
+        let error = run_with_vm(r#"sign("a");"#.to_string()).unwrap_err();
+        assert_eq!(error.message, "sign() expects a number, found 'string'");
+        let error = run_with_vm("is_nan();".to_string()).unwrap_err();
+        assert_eq!(error.message, "is_nan() takes exactly 1 argument (0 given)");
+    }
+
+    #[test]
+    fn test_hex_and_bin_reject_non_integers() {
+        let error = run_with_vm("hex(2.5);".to_string()).unwrap_err();
+        assert_eq!(
+            error.message,
+            "hex() expects a non-negative integer, got 2.5"
+        );
+        let error = run_with_vm("bin(-1);".to_string()).unwrap_err();
+        assert_eq!(
+            error.message,
+            "bin() expects a non-negative integer, got -1"
+        );
+        // past u64, where `as u64` would saturate
+        let error = run_with_vm("bin(1000000000000000000000000000000);".to_string()).unwrap_err();
+        assert_eq!(
+            error.message,
+            "bin() expects a non-negative integer, got 1000000000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_invalid_array_index() {
+        for (source, message) in [
+            (
+                "[1, 2][100000000000000000000];",
+                "array index 100000000000000000000 is out of range",
+            ),
+            (
+                "[1, 2][-1];",
+                "array index must be a non-negative integer, got -1",
+            ),
+            (
+                "let arr = [1]; arr[0.5] = 3;",
+                "array index must be a non-negative integer, got 0.5",
+            ),
+        ] {
+            let error = run_with_vm(source.to_string()).unwrap_err();
+            assert_eq!(error.message, message, "for {source}");
+        }
+    }
+
+    #[test]
+    fn test_repeat_invalid_count() {
+        for (source, shown) in [
+            ("repeat 1.5 {}", "1.5"),
+            ("repeat -1 {}", "-1"),
+            ("repeat \"3\" {}", "3"),
+        ] {
+            let error = run_with_vm(source.to_string()).unwrap_err();
+            assert_eq!(
+                error.message,
+                format!("repeat count must be a non-negative integer, got {shown}")
+            );
+        }
+    }
+
+    #[test]
+    fn test_call_literal_is_compile_error() {
+        for source in ["5(1);", "\"x\"();", "nil();", "[1, 2](0);"] {
+            let error = run_with_vm(source.to_string()).unwrap_err();
+            assert!(error.is_compilation(), "{source} should fail to compile");
+            assert!(
+                error.message.contains("cannot call a"),
+                "unexpected message for {source}: {}",
+                error.message
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod chunk_tests {
+    use mylang::compiler::{Chunk, OpCode, Value};
+
+    fn compile(source: &str) -> Chunk {
+        mylang::compile(source).unwrap()
+    }
+
+    fn decode_all(chunk: &Chunk) -> Vec<(usize, OpCode)> {
+        let mut decoded = Vec::new();
+        let mut ip = 0;
+        while let Some((op, next)) = chunk.decode_at(ip) {
+            decoded.push((ip, op));
+            ip = next;
+        }
+        assert_eq!(ip, chunk.current_ip(), "decoding should consume all code");
+        decoded
+    }
+
+    #[test]
+    fn test_decode_simple_expression() {
+        let chunk = compile("1 + 2;");
+        assert_eq!(
+            decode_all(&chunk),
+            vec![
+                (0, OpCode::Constant),
+                (2, OpCode::Constant),
+                (4, OpCode::Add),
+                (5, OpCode::Pop),
+            ]
+        );
+        assert_eq!(chunk.decode_at(chunk.current_ip()), None);
+    }
+
+    #[test]
+    fn test_negative_literal_is_folded() {
+        let ops = |source| {
+            decode_all(&compile(source))
+                .into_iter()
+                .map(|(_, op)| op)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ops("print -42;"), vec![OpCode::Constant, OpCode::Print]);
+        assert_eq!(
+            ops("--42;"),
+            vec![OpCode::Constant, OpCode::Negate, OpCode::Pop]
+        );
+        assert!(ops("let x = 1; --x;").contains(&OpCode::Negate));
+
+        // `-0` is its own constant, apart from `0`
+        let chunk = compile("print 0, -0;");
+        assert_eq!(
+            decode_all(&chunk)
+                .into_iter()
+                .map(|(_, op)| op)
+                .collect::<Vec<_>>(),
+            vec![OpCode::Constant, OpCode::Constant, OpCode::Print]
+        );
+        assert!(matches!(chunk.constant(1), Some(Value::Number(n)) if n.is_sign_negative()));
+    }
+
+    #[test]
+    fn test_unused_constants_are_dropped() {
+        let ops = |chunk: &Chunk| {
+            decode_all(chunk)
+                .into_iter()
+                .map(|(_, op)| op)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ops(&compile("1; \"unused\"; nil;")),
+            vec![OpCode::Nil, OpCode::Pop]
+        );
+        assert_eq!(
+            ops(&compile("1;\n2;\nprint 3;")),
+            vec![OpCode::Constant, OpCode::Print]
+        );
+
+        // functions and error locations still land in the right places
+        let source = "fn f(n) {\n  1;\n  if n { 2; }\n  3;\n  return [n][1];\n}\n4;\nf(5);";
+        let chunk = compile(source);
+        assert!(!ops(&chunk).contains(&OpCode::Nop));
+        let error = mylang::run_with_vm(source.to_string()).unwrap_err();
+        assert_eq!(error.line(), Some(5));
+    }
+
+    #[test]
+    fn test_decode_jumps_and_closures() {
+        let chunk = compile("let x = 1; fn f(a) { return a + x; } if x { print f(1); }");
+        let ops = decode_all(&chunk)
+            .into_iter()
+            .map(|(_, op)| op)
+            .collect::<Vec<_>>();
+
+        assert!(ops.contains(&OpCode::Closure));
+        assert!(ops.contains(&OpCode::JumpIfFalse));
+        assert!(ops.contains(&OpCode::Print));
+    }
+
+    #[test]
+    fn test_assignment_as_condition_warns() {
+        let chunk = compile("let x = 1;\nif x = 5 {}\nwhile x = nil {}");
+        let warnings = chunk
+            .warnings()
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect::<Vec<_>>();
+        let message =
+            "Warning: assignment used as a condition; use '==' to compare, or wrap it in parentheses";
+        assert_eq!(
+            warnings,
+            vec![format!("2:4: {message}"), format!("3:7: {message}")]
+        );
+
+        for source in [
+            "let x = 1;\nif x == 5 {}",
+            "let x = 1;\nif (x = 5) {}",
+            "let a = [0];\nwhile (a[0] = nil) {}",
+        ] {
+            assert!(compile(source).warnings().is_empty(), "for {source:?}");
+        }
+    }
+
+    #[test]
+    fn test_dead_branches_warn_and_emit_nothing() {
+        let chunk = compile("if false { print 1; }");
+        assert_eq!(decode_all(&chunk), vec![]);
+        let warnings = chunk.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].to_string(),
+            "1:1: Warning: unreachable code: condition is always false"
+        );
+
+        let chunk =
+            compile("let x = 1;\nwhile !true { print x; }\nif nil { x; } else { print x; }");
+        let ops = decode_all(&chunk);
+        assert_eq!(ops.iter().filter(|(_, op)| *op == OpCode::Print).count(), 1);
+        assert!(!ops.iter().any(|(_, op)| *op == OpCode::JumpIfFalse));
+        let lines = chunk
+            .warnings()
+            .iter()
+            .map(|warning| warning.location.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![2, 3]);
+
+        assert!(compile("let x = 1; if x { print x; }")
+            .warnings()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_dead_code_is_still_checked_for_errors() {
+        for source in [
+            "if false { missing = 1; }",
+            "if true { print 1; } else { missing = 1; }",
+            "while false { missing = 1; }",
+            "for let i = 0; false; missing = 1 {}",
+            "let x = 1; while x { if false { fn f() { missing = x; } } break; }",
+        ] {
+            let error = mylang::compile(source).unwrap_err();
+            assert!(error.is_compilation(), "for {source:?}");
+            assert_eq!(
+                error.message, "Assignment to undeclared variable 'missing'",
+                "for {source:?}"
+            );
+        }
+
+        // checking leaves no trace of the dead code behind
+        let source =
+            "let x = 0;\nwhile x < 2 {\n  if false { let y = 2; break; }\n  x += 1;\n}\nprint x;";
+        let chunk = compile(source);
+        assert!(!decode_all(&chunk).iter().any(|(_, op)| *op == OpCode::Jump));
+        assert!(mylang::run_with_vm(source.to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_constant_conditions_emit_only_the_live_path() {
+        let folded = compile("if false { print 1; } else { print 2; }");
+        let ops = decode_all(&folded);
+        assert_eq!(
+            ops.iter().map(|(_, op)| *op).collect::<Vec<_>>(),
+            vec![OpCode::Constant, OpCode::Print]
+        );
+        assert_eq!(folded.constant(0), Some(&Value::Number(2.0)));
+        assert_eq!(folded.current_ip(), compile("print 2;").current_ip());
+
+        // an always-true loop has no test to run, only the jump back
+        for source in [
+            "let i = 0; while true { i += 1; if i == 3 { break; } }",
+            "let i = 0; for ;; i += 1 { if i == 3 { break; } }",
+        ] {
+            let ops = decode_all(&compile(source));
+            assert_eq!(
+                ops.iter()
+                    .filter(|(_, op)| *op == OpCode::JumpIfFalse)
+                    .count(),
+                1,
+                "only the `if` should test a condition: {source}"
+            );
+            assert!(ops.iter().any(|(_, op)| *op == OpCode::Loop));
+        }
+
+        // the initializer of a loop that never runs still does
+        let chunk = compile("for let i = 0; false; i += 1 { print i; }");
+        let ops = decode_all(&chunk);
+        assert!(!ops.iter().any(|(_, op)| *op == OpCode::Print));
+        assert_eq!(chunk.warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_reassigning_a_function_warns() {
+        let chunk = compile("fn f() { return 1; }\nf = 5;");
+        let warnings = chunk.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].to_string(),
+            "2:1: Warning: function 'f' is reassigned to a number"
+        );
+
+        // any value that cannot be a function warns, not only literals
+        let source = "fn f() {}\nlet n = 1;\nf = n + 1;\nf = n < 2;\nf = -n;\nf = n > 0 or n - 1;\nf = [n][0:1];";
+        let warnings = compile(source)
+            .warnings()
+            .iter()
+            .map(|warning| warning.message.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                "function 'f' is reassigned to a non-function value",
+                "function 'f' is reassigned to a boolean",
+                "function 'f' is reassigned to a number",
+                "function 'f' is reassigned to a non-function value",
+                "function 'f' is reassigned to an array",
+            ]
+        );
+
+        // another function, or a value that may be one, is fine
+        let chunk = compile("fn f() {}\nfn g() {}\nf = g;\nf = [g][0];\nf = f and g;\nf = g();");
+        assert!(chunk.warnings().is_empty());
+        // as is a local shadowing the name
+        let chunk = compile("fn f() {}\nfn h() { let f = 1; f = 2; }");
+        assert!(chunk.warnings().is_empty());
+        let chunk = compile("let v = 1;\nv = nil;");
+        assert!(chunk.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_repeated_literals_share_one_constant() {
+        let uses = ["0"; 10].join(", ");
+        let chunk = compile(&format!(
+            "fn zeros() {{ return [{uses}]; }}\nprint zeros();"
+        ));
+
+        let constants = (0..)
+            .map_while(|index| chunk.constant(index))
+            .collect::<Vec<_>>();
+        assert_eq!(constants, vec![&Value::Number(0.0)]);
+
+        let operands = decode_all(&chunk)
+            .into_iter()
+            .filter(|(_, op)| *op == OpCode::Constant)
+            .map(|(ip, _)| chunk.code(ip + 1).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(operands, vec![0; 10]);
+
+        // equal-looking values of different types stay apart
+        let chunk = compile("print 0, \"0\", false, 0;");
+        let count = (0..).map_while(|index| chunk.constant(index)).count();
+        assert_eq!(count, 3);
+
+        // so do numbers that `==` counts as equal but are not the same
+        let chunk = compile(
+            "print 0.3, 0.30000000000000004, 0.00000000000000000001, 0.00000000000000000002;",
+        );
+        let count = (0..).map_while(|index| chunk.constant(index)).count();
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn test_jumps_too_large_are_errors() {
+        // each statement compiles to 8 bytes of code
+        let body = |statements: usize| "x = x + 1;\n".repeat(statements);
+
+        let source = format!("let x = 0;\nif x == 0 {{\n{}}}", body(9000));
+        let error = mylang::compile(&source).unwrap_err();
+        assert!(error.is_compilation());
+        assert_eq!(error.message, "jump too large (at most 65535 bytes)");
+
+        let source = format!("let x = 0;\nwhile x < 1 {{\n{}}}", body(9000));
+        let error = mylang::compile(&source).unwrap_err();
+        assert_eq!(error.message, "jump too large (at most 65535 bytes)");
+
+        // just under the limit, control flow still lands where it should
+        let source = format!(
+            "let x = 0;\nwhile x < 8000 {{\n{}}}\nassert(x == 8000);",
+            body(8000)
+        );
+        mylang::run_with_vm(source).unwrap();
+    }
+
+    #[test]
+    fn test_compiling_onto_an_existing_chunk() {
+        use mylang::{compiler::Compiler, parse, vm::VM};
+
+        let first = compile(
+            "let total = 0;\nfn add(n) { if n > 0 { total += n; } return total; }\nfor let i = 0; i < 3; i += 1 { add(i); }",
+        );
+        let first_code = (0..first.current_ip())
+            .map(|ip| first.code(ip).unwrap())
+            .collect::<Vec<_>>();
+
+        let second = Compiler::with_chunk(first)
+            .compile(
+                &parse("add(10);\nwhile total < 20 { add(1); }\nassert(total == 20);").unwrap(),
+            )
+            .unwrap();
+        // the first batch's code is untouched, and its constants are reused
+        let prefix = (0..first_code.len())
+            .map(|ip| second.code(ip).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(prefix, first_code);
+        let constants = (0..)
+            .map_while(|index| second.constant(index))
+            .collect::<Vec<_>>();
+        let ones = constants
+            .iter()
+            .filter(|c| ***c == Value::Number(1.0))
+            .count();
+        assert_eq!(ones, 1);
+
+        // both batches run together, the second using the first's globals
+        VM::new(second).run().unwrap();
+    }
+
+    #[test]
+    fn test_function_bodies_end_with_a_return() {
+        let body = |source| {
+            let chunk = compile(source);
+            let start = chunk.proto(0).unwrap().start_ip;
+            decode_all(&chunk)
+                .into_iter()
+                .filter(|(ip, _)| *ip >= start)
+                .map(|(_, op)| op)
+                .take_while(|op| *op != OpCode::Closure)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(body("fn f() {}"), vec![OpCode::Nil, OpCode::Return]);
+        assert_eq!(
+            body("fn f() { return 1; }"),
+            vec![OpCode::Constant, OpCode::Return]
+        );
+
+        // the body's last byte is an operand equal to `Return`, which must not
+        // be mistaken for one
+        let mut source = String::from("fn f() {");
+        for i in 0..OpCode::Return as usize {
+            source += &format!(" print {i};");
+        }
+        source += " let last = 1000; }";
+        let ops = body(&source);
+        let last = ops.iter().rposition(|op| *op == OpCode::Constant).unwrap();
+        assert_eq!(ops[last + 1..last + 3], [OpCode::Nil, OpCode::Return]);
+    }
+
+    #[test]
+    fn test_nested_functions_populate_protos() {
+        let chunk = compile("fn outer(a) { fn inner(b) { return a + b; } return inner; }");
+
+        // inner finishes compiling first, so it takes the first slot
+        let names = (0..)
+            .map_while(|i| chunk.proto(i))
+            .map(|proto| proto.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["inner", "outer"]);
+        assert_eq!(chunk.proto(0).unwrap().upvalues.len(), 1);
+        assert!(chunk.constant(0).is_none(), "protos stay out of constants");
+
+        let listing = chunk.disassembly("test");
+        assert!(listing.contains("=== Protos ==="));
+        assert!(listing.contains("protos[1] = function outer(a)"));
+        assert!(listing.contains("closure for function 'inner' with 1 upvalues"));
+    }
+
+    #[test]
+    fn test_compiled_code_validates() {
+        let chunk = compile(
+            "fn f(n) { let total = 0; while n > 0 { total += n; n -= 1; } return total; }\n\
+             switch f(3) { case 6: print \"six\"; default: break; }\n\
+             for (i, (k, v)) in enumerate(zip([1], [2])) { print i, k, v; }",
+        );
+        chunk.validate().unwrap();
+        Chunk::new().validate().unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_chunks_fail_validation() {
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Nil as u8);
+        chunk.write(255);
+        let error = chunk.validate().unwrap_err();
+        assert!(error.is_vm_runtime());
+        assert_eq!(error.message, "invalid bytecode at 1: unknown opcode 255");
+
+        // a jump into the middle of the `Constant` after it
+        let mut chunk = Chunk::new();
+        let one = chunk.add_constant(Value::Number(1.0)).unwrap();
+        chunk.write(OpCode::Jump as u8);
+        chunk.write(0);
+        chunk.write(1);
+        chunk.write(OpCode::Constant as u8);
+        chunk.write(one);
+        let error = chunk.validate().unwrap_err();
+        assert_eq!(
+            error.message,
+            "invalid bytecode at 0: jump to 4 is not the start of an instruction"
+        );
+        // which the VM refuses to run at all
+        let error = mylang::vm::VM::new(chunk).run().unwrap_err();
+        assert_eq!(
+            error.message,
+            "invalid bytecode at 0: jump to 4 is not the start of an instruction"
+        );
+
+        for (code, message) in [
+            (
+                vec![OpCode::Constant as u8, 0],
+                "invalid bytecode at 0: constant 0 does not exist",
+            ),
+            (
+                vec![OpCode::Loop as u8, 0, 4],
+                "invalid bytecode at 0: loop goes back past the start of the code",
+            ),
+            (
+                vec![OpCode::Nil as u8, OpCode::JumpIfFalse as u8, 0],
+                "invalid bytecode at 1: JumpIfFalse runs past the end of the code",
+            ),
+        ] {
+            let mut chunk = Chunk::new();
+            for byte in code {
+                chunk.write(byte);
+            }
+            assert_eq!(chunk.validate().unwrap_err().message, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod file_access_tests {
+    use mylang::{compile, error::ErrorType, vm::VM};
+    use std::{env, fs};
+
+    fn run_with_file_access(source: &str, enabled: bool) -> mylang::error::Result<()> {
+        let mut vm = VM::new(compile(source)?);
+        vm.set_file_access(enabled);
+        vm.run().map(|_| ())
+    }
+
+    #[test]
+    fn test_file_access_disabled_by_default() {
+        let error = mylang::run_with_vm(r#"read_file("x.txt");"#.to_string()).unwrap_err();
+        assert_eq!(error.message, "file access is disabled");
+
+        let error = run_with_file_access(r#"write_file("x.txt", "a");"#, false).unwrap_err();
+        assert_eq!(error.message, "file access is disabled");
+    }
+
+    #[test]
+    fn test_read_and_write_file_when_enabled() {
+        let path = env::temp_dir().join(format!("mylang_vm_file_{}.txt", std::process::id()));
+        let source = format!(
+            r#"let path = "{}"; write_file(path, "hello"); assert(read_file(path) == "hello");"#,
+            path.display()
+        );
+        let result = run_with_file_access(&source, true);
+        let contents = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+
+        result.unwrap();
+        assert_eq!(contents.unwrap(), "hello");
+        let error = run_with_file_access(r#"read_file("/no/such/file");"#, true).unwrap_err();
+        assert_eq!(error.error_type, ErrorType::Io);
+    }
+}
+
+#[cfg(test)]
+mod step_tests {
+    use mylang::{
+        compile,
+        compiler::{Chunk, OpCode, Value},
+        error::ErrorType,
+        vm::{StepResult, VM},
+    };
+
+    fn vm_for(source: &str) -> VM {
+        VM::new(compile(source).unwrap())
+    }
+
+    #[test]
+    fn test_breakpoint_pauses_on_line() {
+        let source = "let total = 0;\nfor let i = 0; i < 3; i += 1 { total += i; }\nprint total;\n";
+        let mut vm = vm_for(source);
+        vm.set_breakpoint(1);
+        vm.set_breakpoint(3);
+
+        assert_eq!(vm.run().unwrap(), StepResult::Breakpoint);
+        assert_eq!(vm.ip(), 0);
+
+        assert_eq!(vm.run().unwrap(), StepResult::Breakpoint);
+        assert!(vm.stack().is_empty(), "loop locals should be popped");
+
+        assert_eq!(vm.run().unwrap(), StepResult::Halted);
+    }
+
+    #[test]
+    fn test_breakpoint_in_function_called_from_loop() {
+        let source = "fn bump(n) { return n + 1; }\nlet i = 0;\nwhile i < 3 { i = bump(i); }\n";
+        let mut vm = vm_for(source);
+        vm.set_breakpoint(1);
+
+        let mut pauses = 0;
+        while vm.run().unwrap() == StepResult::Breakpoint {
+            pauses += 1;
+        }
+        assert_eq!(pauses, 4, "once for the declaration, then once per call");
+    }
+
+    #[test]
+    fn test_breakpoint_inside_loop_pauses_each_iteration() {
+        let source = "let total = 0;\nrepeat 3 {\n  total += 1;\n}\n";
+        let mut vm = vm_for(source);
+        vm.set_breakpoint(3);
+
+        let mut pauses = 0;
+        while vm.run().unwrap() == StepResult::Breakpoint {
+            pauses += 1;
+        }
+        assert_eq!(pauses, 3);
+    }
+
+    #[test]
+    fn test_breakpoint_in_while_body_and_called_function() {
+        let source =
+            "fn bump(n) {\n  return n + 1;\n}\nlet i = 0;\nwhile i < 4 {\n  i = bump(i);\n}\n";
+        let mut vm = vm_for(source);
+        vm.set_breakpoint(2);
+        vm.set_breakpoint(6);
+
+        let mut pauses = 0;
+        while vm.run().unwrap() == StepResult::Breakpoint {
+            pauses += 1;
+        }
+        assert_eq!(
+            pauses, 8,
+            "each iteration stops in the body and in the callee"
+        );
+    }
+
+    #[test]
+    fn test_instruction_limit_stops_infinite_loop() {
+        let mut vm = vm_for("let i = 0;\nwhile true {\n  i += 1;\n}");
+        vm.set_instruction_limit(Some(1000));
+        let error = vm.run().unwrap_err();
+        assert!(error.is_vm_runtime());
+        assert_eq!(error.message, "instruction limit of 1000 exceeded");
+
+        let mut vm = vm_for("let i = 0; while i < 3 { i += 1; }");
+        vm.set_instruction_limit(Some(1000));
+        assert_eq!(vm.run().unwrap(), StepResult::Halted);
+    }
+
+    #[test]
+    fn test_instruction_limit_counts_from_when_it_is_set() {
+        use mylang::{compiler::Compiler, parse};
+
+        let source = "let i = 0;\nwhile i < 5 { i += 1; }";
+        let mut vm = vm_for(source);
+        let mut total = 1;
+        while vm.step().unwrap() == StepResult::Continue {
+            total += 1;
+        }
+
+        let mut vm = vm_for(source);
+        vm.set_instruction_limit(Some(total - 1));
+        assert!(vm.run().is_err());
+
+        // setting the limit again starts a fresh count, and `None` lifts it
+        let mut vm = vm_for(source);
+        vm.set_instruction_limit(Some(total / 2 + 1));
+        for _ in 0..total / 2 {
+            vm.step().unwrap();
+        }
+        vm.set_instruction_limit(Some(total / 2 + 1));
+        assert_eq!(vm.run().unwrap(), StepResult::Halted);
+        let mut vm = vm_for(source);
+        vm.set_instruction_limit(Some(1));
+        vm.set_instruction_limit(None);
+        assert_eq!(vm.run().unwrap(), StepResult::Halted);
+
+        // each chunk the VM is extended with gets the whole limit; the
+        // assignment takes one more instruction than the declaration
+        let mut vm = vm_for(source);
+        vm.set_instruction_limit(Some(total + 1));
+        assert_eq!(vm.run().unwrap(), StepResult::Halted);
+        let more = Compiler::with_chunk(vm.chunk().clone())
+            .compile(&parse("i = 0;\nwhile i < 5 { i += 1; }").unwrap())
+            .unwrap();
+        vm.extend_chunk(more);
+        assert_eq!(vm.run().unwrap(), StepResult::Halted);
+    }
+
+    #[test]
+    fn test_break_from_nested_block_unwinds_stack() {
+        let source = "for let i = 0; i < 3; i += 1 {\n  let a = i;\n  {\n    let b = a;\n    if b == 1 { let c = b; break; }\n  }\n}\n";
+        let mut vm = vm_for(source);
+        assert_eq!(vm.run().unwrap(), StepResult::Halted);
+        assert!(vm.stack().is_empty(), "loop locals should all be popped");
+    }
+
+    #[test]
+    fn test_return_from_nested_blocks_unwinds_stack() {
+        let source = "fn f(n) {\n  let a = n;\n  {\n    let b = a * 2;\n    {\n      let c = b + 1;\n      if c > 0 { let d = c; return [a, b, d]; }\n    }\n    let e = 0;\n  }\n  return nil;\n}\nlet before = 100;\nlet r = [before, f(1), f(2), 200];\nassert(r == [100, [1, 2, 3], [2, 4, 5], 200], \"wrong result\");\n";
+        let mut vm = vm_for(source);
+        vm.set_breakpoint(15);
+        assert_eq!(vm.run().unwrap(), StepResult::Breakpoint);
+        assert!(
+            vm.stack().is_empty(),
+            "function locals should all be popped"
+        );
+        assert_eq!(vm.run().unwrap(), StepResult::Halted);
+    }
+
+    #[test]
+    fn test_step_through_addition() {
+        let mut vm = vm_for("1 + 2;");
+        assert_eq!(vm.ip(), 0);
+
+        assert_eq!(vm.step().unwrap(), StepResult::Continue);
+        assert_eq!(vm.stack(), &[Value::Number(1.0)]);
+        assert_eq!(vm.ip(), 2);
+
+        assert_eq!(vm.step().unwrap(), StepResult::Continue);
+        assert_eq!(vm.stack(), &[Value::Number(1.0), Value::Number(2.0)]);
+
+        assert_eq!(vm.step().unwrap(), StepResult::Continue);
+        assert_eq!(vm.stack(), &[Value::Number(3.0)]);
+
+        assert_eq!(vm.step().unwrap(), StepResult::Halted);
+        assert!(vm.stack().is_empty());
+
+        // stepping a halted program does nothing
+        assert_eq!(vm.step().unwrap(), StepResult::Halted);
+        assert_eq!(vm.ip(), 6);
+    }
+
+    #[test]
+    fn test_nops_do_not_change_execution() {
+        // `3 + 4`, the `4` behind a jump on `true`, with `Nop`s in between when asked
+        let program = |nops: bool| {
+            let mut chunk = Chunk::new();
+            let nop = |chunk: &mut Chunk| {
+                if nops {
+                    chunk.write(OpCode::Nop as u8);
+                }
+            };
+            let three = chunk.add_constant(Value::Number(3.0)).unwrap();
+            let four = chunk.add_constant(Value::Number(4.0)).unwrap();
+            chunk.write(OpCode::Constant as u8);
+            chunk.write(three);
+            nop(&mut chunk);
+            chunk.write(OpCode::True as u8);
+            chunk.write(OpCode::JumpIfFalse as u8);
+            let skip = chunk.current_ip();
+            chunk.write(0);
+            chunk.write(0);
+            nop(&mut chunk);
+            chunk.write(OpCode::Constant as u8);
+            chunk.write(four);
+            nop(&mut chunk);
+            chunk.write(OpCode::Add as u8);
+            chunk.patch_jump(skip).unwrap();
+            nop(&mut chunk);
+            chunk
+        };
+
+        let (plain, padded) = (program(false), program(true));
+        assert_eq!(padded.current_ip(), plain.current_ip() + 4);
+        assert!(padded.disassembly("padded").contains("Nop"));
+
+        let mut vm = VM::new(plain);
+        assert_eq!(vm.run().unwrap(), StepResult::Halted);
+        let mut padded_vm = VM::new(padded);
+        assert_eq!(padded_vm.run().unwrap(), StepResult::Halted);
+        assert_eq!(padded_vm.stack(), vm.stack());
+        assert_eq!(vm.stack(), &[Value::Number(7.0)]);
+    }
+
+    #[test]
+    fn test_stack_underflow_names_the_opcode() {
+        // a `Nil` then an `Add`, which needs two operands
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Nil as u8);
+        chunk.write(OpCode::Add as u8);
+
+        let error = VM::new(chunk).run().unwrap_err();
+        assert_eq!(error.error_type, ErrorType::StackUnderflow);
+        assert_eq!(error.message, "stack underflow in Add (at instruction 1)");
+    }
+
+    #[test]
+    fn test_constant_globals_reject_assignment_at_runtime() {
+        // bytecode assigning `limit`, which no compiler check ever saw
+        let mut chunk = Chunk::new();
+        let limit = chunk.add_global("limit".to_string()).unwrap();
+        let two = chunk.add_constant(Value::Number(2.0)).unwrap();
+        chunk.write(OpCode::Constant as u8);
+        chunk.write(two);
+        chunk.write(OpCode::SetGlobal as u8);
+        chunk.write(limit);
+
+        let mut vm = VM::new(chunk);
+        vm.define_constant("limit", Value::Number(1.0));
+        let error = vm.run().unwrap_err();
+        assert!(error.is_vm_runtime());
+        assert_eq!(error.message, "cannot assign to constant 'limit'");
+
+        // a script declaring the same name cannot redefine it either
+        let mut vm = vm_for("let limit = 2;");
+        vm.define_constant("limit", Value::Number(1.0));
+        let error = vm.run().unwrap_err();
+        assert_eq!(error.message, "cannot assign to constant 'limit'");
+    }
+}